@@ -0,0 +1,74 @@
+//! A/B tests of dispatch strategies within a single run.
+//!
+//! The fleet is split into groups A and B, and every `Request` is routed to one of them at
+//! random. Group A dispatches the way the rest of the `Scenario` says, group B the way the
+//! `AbTest` says. Since both groups serve the same stream of riders, the strategies are
+//! compared under the very same demand rather than across separately seeded runs.
+
+use serde::{Deserialize, Serialize};
+
+use crate::scenario::Scenario;
+use crate::zones::{DispatchWeights, MaxPickup, Zones};
+
+/// Names of the groups by index.
+pub const GROUPS: [&str; 2] = ["a", "b"];
+
+/// How group B differs from group A, as configured in the `[ab_test]` section of a
+/// `Scenario`. Whatever is missing is the same for both groups.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AbTest {
+    /// Share of the fleet in group B, spread evenly over the vehicle types.
+    pub taxis: f64,
+
+    /// Share of `Request`s routed to group B. The same as the share of `taxis` if missing, so
+    /// both groups are equally busy.
+    pub requests: Option<f64>,
+
+    pub dispatch_weights: Option<DispatchWeights>,
+    pub matching_interval: Option<u64>,
+    pub max_pickup: Option<MaxPickup>,
+}
+
+impl AbTest {
+    /// Whether the `i`th of the `Taxi`s is in group B, so that every stretch of the fleet has
+    /// about the right share of them.
+    pub fn has_taxi_in_b(&self, i: usize) -> bool {
+        let b_before = |i: usize| (i as f64 * self.taxis).floor();
+        b_before(i + 1) > b_before(i)
+    }
+
+    pub fn request_share(&self) -> f64 {
+        self.requests.unwrap_or(self.taxis)
+    }
+}
+
+/// How group B dispatches in a running `World`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GroupDispatch {
+    pub zones: Zones,
+    pub matching_interval: u64,
+    pub request_share: f64,
+}
+
+impl GroupDispatch {
+    /// Group B of `scenario`, which has to have an `ab_test`.
+    pub fn new(scenario: &Scenario, ab_test: &AbTest) -> GroupDispatch {
+        let matching_interval = ab_test
+            .matching_interval
+            .unwrap_or(scenario.matching_interval);
+        GroupDispatch {
+            zones: Zones::new(scenario.zones, scenario.city_size)
+                .batched(matching_interval > 1)
+                .weighted(
+                    ab_test
+                        .dispatch_weights
+                        .unwrap_or(scenario.dispatch_weights),
+                )
+                .limited(ab_test.max_pickup.or(scenario.max_pickup)),
+            matching_interval,
+            request_share: ab_test.request_share(),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::experiment::path_for_seed;
+use crate::export::RequestRecord;
+use crate::time::minutes_to_ticks;
+use crate::world::Request;
+
+/// What happens to canceled or fulfilled `Request`s, as configured in the `[archive]` section of
+/// a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ArchivePolicy {
+    /// Keep every `Request` in memory forever.
+    Unbounded,
+
+    /// Keep only the most recent `limit` `Request`s in memory.
+    RingBuffer { limit: usize },
+
+    /// Keep only the `Request`s archived within the last `minutes` simulated minutes in memory.
+    Recent { minutes: f64 },
+
+    /// Once `threshold` `Request`s are in memory, append them to the file at `path` and start
+    /// over. `{seed}` in `path` is replaced with the seed of the run so that replications don't
+    /// overwrite each other.
+    Spill { path: PathBuf, threshold: usize },
+}
+
+/// Canceled or fulfilled `Request`s, kept according to an `ArchivePolicy`.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Archive {
+    requests: VecDeque<Request>,
+
+    /// `Request::digest` of every `Request` in `requests` when it was archived.
+    digests: VecDeque<u64>,
+
+    /// Tick every `Request` in `requests` was archived at, only kept for `Recent` and `Spill`
+    /// archives.
+    archived_at: VecDeque<u64>,
+    policy: ArchivePolicy,
+
+    /// Where spilled `Request`s go, opened on the first spill. A deserialized `Archive`
+    /// reopens it to append to it.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    spill_file: Option<BufWriter<File>>,
+
+    /// The first error while spilling, after which the `Request`s stay in memory. Archiving
+    /// can't fail while the `World` runs, so this is reported on `flush` instead.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    error: Option<io::Error>,
+
+    /// How many `Request`s were ever archived, including the ones dropped or spilled since.
+    total: u64,
+}
+
+impl Archive {
+    pub fn new(policy: ArchivePolicy, seed: u64) -> Archive {
+        let policy = match policy {
+            ArchivePolicy::Spill { path, threshold } => ArchivePolicy::Spill {
+                path: path_for_seed(&path, seed),
+                threshold,
+            },
+            policy => policy,
+        };
+        Archive {
+            requests: VecDeque::new(),
+            digests: VecDeque::new(),
+            archived_at: VecDeque::new(),
+            policy,
+            spill_file: None,
+            error: None,
+            total: 0,
+        }
+    }
+
+    /// Archives `request` at tick `now`.
+    pub fn push(&mut self, request: Request, now: u64) {
+        self.total += 1;
+        let digest = request.digest();
+        match self.policy {
+            ArchivePolicy::Recent { minutes } => {
+                let kept = minutes_to_ticks(minutes);
+                while self.archived_at.front().is_some_and(|&t| t + kept < now) {
+                    self.archived_at.pop_front();
+                    self.requests.pop_front();
+                    self.digests.pop_front();
+                }
+                self.requests.push_back(request);
+                self.digests.push_back(digest);
+                self.archived_at.push_back(now);
+            }
+            ArchivePolicy::Unbounded => {
+                self.requests.push_back(request);
+                self.digests.push_back(digest);
+            }
+            ArchivePolicy::RingBuffer { limit } => {
+                if limit == 0 {
+                    return;
+                }
+                if self.requests.len() == limit {
+                    self.requests.pop_front();
+                    self.digests.pop_front();
+                }
+                self.requests.push_back(request);
+                self.digests.push_back(digest);
+            }
+            ArchivePolicy::Spill { threshold, .. } => {
+                self.requests.push_back(request);
+                self.digests.push_back(digest);
+                self.archived_at.push_back(now);
+                if self.error.is_none() && self.requests.len() >= threshold {
+                    self.error = self.spill().err().map(|e| self.unwritable(e));
+                }
+            }
+        }
+    }
+
+    /// Appends all `Request`s in memory to the spill file. Only those which were written are
+    /// dropped from memory if that fails.
+    fn spill(&mut self) -> io::Result<()> {
+        let spilled_before = self.total > self.requests.len() as u64;
+        let file = match (&mut self.spill_file, &self.policy) {
+            (Some(file), _) => file,
+            (file, ArchivePolicy::Spill { path, .. }) => {
+                let opened = if spilled_before {
+                    OpenOptions::new().append(true).open(path)?
+                } else {
+                    File::create(path)?
+                };
+                file.insert(BufWriter::new(opened))
+            }
+            _ => unreachable!("Only spilling archives spill."),
+        };
+        while let (Some(r), Some(&at)) = (self.requests.front(), self.archived_at.front()) {
+            bincode::serialize_into(&mut *file, &RequestRecord::archived_at(r, at))
+                .map_err(io::Error::other)?;
+            self.requests.pop_front();
+            self.digests.pop_front();
+            self.archived_at.pop_front();
+        }
+        Ok(())
+    }
+
+    /// `e` along with the path of the spill file it happened on.
+    fn unwritable(&self, e: io::Error) -> io::Error {
+        match &self.policy {
+            ArchivePolicy::Spill { path, .. } => io::Error::new(
+                e.kind(),
+                format!("spill file {} can't be written: {}", path.display(), e),
+            ),
+            _ => e,
+        }
+    }
+
+    /// Spills whatever is left in memory and makes sure it all actually hits the disk, so the
+    /// spill file is complete at the end of a run. Only spilling archives have anything to do
+    /// here. Fails with the first error of an earlier spill, if there was one.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let ArchivePolicy::Spill { .. } = self.policy {
+            if let Some(e) = self.error.take() {
+                return Err(e);
+            }
+            self.spill().map_err(|e| self.unwritable(e))?;
+        }
+        match &mut self.spill_file {
+            Some(file) => file.flush().map_err(|e| self.unwritable(e)),
+            None => Ok(()),
+        }
+    }
+
+    /// How many `Request`s were archived in total, even if they're no longer kept in memory.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The first `Request` kept in memory which changed since it was archived, if any did.
+    pub fn first_mutated(&self) -> Option<&Request> {
+        self.requests
+            .iter()
+            .zip(&self.digests)
+            .find(|(r, &digest)| r.digest() != digest)
+            .map(|(r, _)| r)
+    }
+
+    /// The `Request`s which are kept in memory, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &Request> {
+        self.requests.iter()
+    }
+}
+
+/// Reads back the `RequestRecord`s of a spill file, encoded with bincode, in the order they
+/// were archived.
+pub fn read_spilled(
+    path: &Path,
+) -> io::Result<impl Iterator<Item = bincode::Result<RequestRecord>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    Ok(std::iter::from_fn(
+        move || match bincode::deserialize_from(&mut reader) {
+            Ok(r) => Some(Ok(r)),
+            Err(e) => match *e {
+                bincode::ErrorKind::Io(ref io) if io.kind() == io::ErrorKind::UnexpectedEof => None,
+                _ => Some(Err(e)),
+            },
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+    use crate::vehicle::VehicleTypes;
+    use crate::world::{RequestOutcome, Trip};
+
+    /// A `Request` which canceled right away.
+    fn canceled(fare: f64) -> Request {
+        let trip = Trip {
+            pickup: Position::new(0.0, 0.0),
+            dropoff: Position::new(1.0, 1.0),
+            stops: vec![],
+            duration: 1,
+            party_size: 2,
+            vehicle_types: VehicleTypes::ALL,
+        };
+        Request::new(0, trip, fare, (0, 0))
+    }
+
+    fn spill(path: PathBuf, threshold: usize) -> ArchivePolicy {
+        ArchivePolicy::Spill { path, threshold }
+    }
+
+    #[test]
+    fn spilled_requests_read_back_in_order() {
+        let path = std::env::temp_dir().join(format!("spill-{}-{{seed}}.bin", std::process::id()));
+        let mut archive = Archive::new(spill(path.clone(), 2), 7);
+        let requests: Vec<Request> = [1.0, 2.0, 3.0].iter().map(|&f| canceled(f)).collect();
+        for (tick, request) in (10..).zip(&requests) {
+            archive.push(request.clone(), tick);
+        }
+        assert_eq!(archive.iter().count(), 1);
+        archive.flush().unwrap();
+        assert_eq!(archive.iter().count(), 0);
+        assert_eq!(archive.total(), 3);
+
+        let path = path_for_seed(&path, 7);
+        let spilled: Vec<RequestRecord> = read_spilled(&path)
+            .unwrap()
+            .collect::<bincode::Result<_>>()
+            .unwrap();
+        let expected: Vec<RequestRecord> = (10..)
+            .zip(&requests)
+            .map(|(tick, r)| RequestRecord::archived_at(r, tick))
+            .collect();
+        assert_eq!(spilled, expected);
+        assert_eq!(spilled[2].outcome, RequestOutcome::Canceled);
+        assert_eq!(spilled[2].fare, 3.0);
+        assert_eq!(spilled[2].party_size, 2);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn requests_which_couldnt_be_spilled_stay_in_memory() {
+        let path = PathBuf::from("/nonexistent/spill.bin");
+        let mut archive = Archive::new(spill(path, 1), 7);
+        archive.push(canceled(1.0), 10);
+        archive.push(canceled(2.0), 11);
+        assert_eq!(archive.iter().count(), 2);
+        let error = archive.flush().unwrap_err();
+        assert!(error.to_string().contains("/nonexistent/spill.bin"));
+    }
+}
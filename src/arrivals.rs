@@ -0,0 +1,287 @@
+//! Arrival processes of riders which are burstier or vary more over the day than one
+//! `request_spawn_chance` per tick.
+//!
+//! Each process draws a Poisson distributed number of `Request`s per tick from the `World`'s
+//! random number generator, at a rate which depends on the process:
+//!
+//! - `poisson` spawns at `rate` per tick, times the factors of the current hour and weekday.
+//! - `on_off` switches between an on phase at `on_rate` and an off phase at `off_rate`, which
+//!   last `mean_on` and `mean_off` minutes on average.
+//! - `hawkes` spawns at `base_rate`, and every `Request` raises the rate for a while after, so
+//!   that `Request`s cluster. On average every `Request` triggers `excitation` more, which
+//!   spawn `decay` minutes after it on average.
+
+use rand::Rng;
+use rand_distr::{Distribution as _, Poisson};
+use serde::{Deserialize, Serialize};
+
+use crate::time::{minutes_as_ticks, SimTime};
+
+/// An arrival process as specified in a `Scenario`, e.g.
+/// `arrivals = { kind = "hawkes", base_rate = 0.1, excitation = 0.5, decay = 5 }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Arrivals {
+    /// `profile` has a factor on `rate` for each hour, starting at midnight on Monday. Hours
+    /// past the end of the list start over at its beginning. `weekdays` has another factor for
+    /// each day of the week, starting on Monday. The rate is constant if both are empty.
+    Poisson {
+        rate: f64,
+        #[serde(default)]
+        profile: Vec<f64>,
+        #[serde(default)]
+        weekdays: Vec<f64>,
+    },
+    OnOff {
+        on_rate: f64,
+        off_rate: f64,
+        mean_on: f64,
+        mean_off: f64,
+    },
+    Hawkes {
+        base_rate: f64,
+        excitation: f64,
+        decay: f64,
+    },
+}
+
+impl Arrivals {
+    /// Why the parameters don't make up a process, if they don't.
+    pub fn problems(&self) -> Vec<String> {
+        let non_negative = |x: f64| x >= 0.0 && x.is_finite();
+        let positive = |x: f64| x > 0.0 && x.is_finite();
+        let mut problems = vec![];
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+        match self {
+            Arrivals::Poisson {
+                rate,
+                profile,
+                weekdays,
+            } => {
+                check(non_negative(*rate), "arrivals need a non-negative rate");
+                check(
+                    profile.iter().chain(weekdays).all(|&f| non_negative(f)),
+                    "arrivals profile and weekdays must not contain negative factors",
+                );
+                check(
+                    weekdays.is_empty() || weekdays.len() == 7,
+                    "arrivals weekdays need a factor for each of the 7 days",
+                );
+            }
+            Arrivals::OnOff {
+                on_rate,
+                off_rate,
+                mean_on,
+                mean_off,
+            } => {
+                check(
+                    non_negative(*on_rate) && non_negative(*off_rate),
+                    "arrivals need non-negative on_rate and off_rate",
+                );
+                check(
+                    positive(*mean_on) && positive(*mean_off),
+                    "arrivals need positive mean_on and mean_off",
+                );
+            }
+            Arrivals::Hawkes {
+                base_rate,
+                excitation,
+                decay,
+            } => {
+                check(
+                    non_negative(*base_rate),
+                    "arrivals need a non-negative base_rate",
+                );
+                check(
+                    (0.0..1.0).contains(excitation),
+                    "arrivals excitation must be at least 0 and below 1",
+                );
+                check(positive(*decay), "arrivals need a positive decay");
+            }
+        }
+        problems
+    }
+
+    /// Long run average of `Request`s per tick.
+    pub fn mean_rate(&self) -> f64 {
+        match self {
+            Arrivals::Poisson {
+                rate,
+                profile,
+                weekdays,
+            } => rate * mean_factor(profile) * mean_factor(weekdays),
+            Arrivals::OnOff {
+                on_rate,
+                off_rate,
+                mean_on,
+                mean_off,
+            } => (on_rate * mean_on + off_rate * mean_off) / (mean_on + mean_off),
+            Arrivals::Hawkes {
+                base_rate,
+                excitation,
+                ..
+            } => base_rate / (1.0 - excitation),
+        }
+    }
+
+    /// Whether `Request`s arrive at one constant rate, like in an M/M/c queue.
+    pub fn is_homogeneous_poisson(&self) -> bool {
+        match self {
+            Arrivals::Poisson {
+                profile, weekdays, ..
+            } => [profile, weekdays]
+                .iter()
+                .all(|factors| factors.windows(2).all(|w| w[0] == w[1])),
+            Arrivals::OnOff {
+                on_rate, off_rate, ..
+            } => on_rate == off_rate,
+            Arrivals::Hawkes { excitation, .. } => *excitation == 0.0,
+        }
+    }
+}
+
+/// An `Arrivals` process along with its state as a run goes on.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ArrivalProcess {
+    arrivals: Arrivals,
+
+    /// Whether an `on_off` process is in its on phase.
+    on: bool,
+
+    /// How much a `hawkes` process is raised above its `base_rate` by earlier `Request`s.
+    excitement: f64,
+}
+
+impl ArrivalProcess {
+    /// An `on_off` process starts in either phase with the share of time it spends in it.
+    pub fn new<R: Rng + ?Sized>(arrivals: &Arrivals, rng: &mut R) -> ArrivalProcess {
+        let on = match *arrivals {
+            Arrivals::OnOff {
+                mean_on, mean_off, ..
+            } => rng.gen_bool(mean_on / (mean_on + mean_off)),
+            _ => false,
+        };
+        ArrivalProcess {
+            arrivals: arrivals.clone(),
+            on,
+            excitement: 0.0,
+        }
+    }
+
+    /// How many `Request`s arrive during the tick at `time`.
+    pub fn sample<R: Rng + ?Sized>(&mut self, rng: &mut R, time: SimTime) -> u64 {
+        match self.arrivals {
+            Arrivals::Poisson {
+                rate,
+                ref profile,
+                ref weekdays,
+            } => {
+                let hour = time.hours() as usize;
+                let factor = |factors: &[f64], i: usize| {
+                    if factors.is_empty() {
+                        1.0
+                    } else {
+                        factors[i % factors.len()]
+                    }
+                };
+                let factor = factor(profile, hour) * factor(weekdays, time.weekday().index());
+                poisson(rng, rate * factor)
+            }
+            Arrivals::OnOff {
+                on_rate,
+                off_rate,
+                mean_on,
+                mean_off,
+            } => {
+                let mean_phase = if self.on { mean_on } else { mean_off };
+                if rng.gen_bool((1.0 / minutes_as_ticks(mean_phase)).min(1.0)) {
+                    self.on = !self.on;
+                }
+                poisson(rng, if self.on { on_rate } else { off_rate })
+            }
+            Arrivals::Hawkes {
+                base_rate,
+                excitation,
+                decay,
+            } => {
+                let arrived = poisson(rng, base_rate + self.excitement);
+                // Every arrival adds a jump which decays exponentially and adds up to
+                // `excitation` arrivals over time.
+                let falloff = 1.0 / minutes_as_ticks(decay);
+                self.excitement =
+                    (self.excitement + excitation * falloff * arrived as f64) * (-falloff).exp();
+                arrived
+            }
+        }
+    }
+}
+
+/// 1 if there are no `factors`.
+fn mean_factor(factors: &[f64]) -> f64 {
+    if factors.is_empty() {
+        1.0
+    } else {
+        factors.iter().sum::<f64>() / factors.len() as f64
+    }
+}
+
+/// How many events a Poisson process at `rate` per tick draws for a tick.
+pub(crate) fn poisson<R: Rng + ?Sized>(rng: &mut R, rate: f64) -> u64 {
+    if rate <= 0.0 {
+        return 0;
+    }
+    Poisson::new(rate)
+        .expect("Positive rates make Poisson distributions.")
+        .sample(rng)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    /// Average of `ticks` samples of `arrivals`, which start at midnight on Monday.
+    fn sampled_rate(arrivals: &Arrivals, ticks: u64) -> f64 {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let mut process = ArrivalProcess::new(arrivals, &mut rng);
+        let arrived: u64 = (0..ticks)
+            .map(|t| process.sample(&mut rng, SimTime::from_seconds(t)))
+            .sum();
+        arrived as f64 / ticks as f64
+    }
+
+    #[test]
+    fn hawkes_processes_are_raised_by_their_excitation() {
+        let hawkes = Arrivals::Hawkes {
+            base_rate: 0.1,
+            excitation: 0.5,
+            decay: 1.0,
+        };
+        assert!((hawkes.mean_rate() - 0.2).abs() < 1e-12);
+        let sampled = sampled_rate(&hawkes, 200_000);
+        assert!((sampled - 0.2).abs() < 0.01, "{} isn't about 0.2", sampled);
+    }
+
+    #[test]
+    fn mean_rates_weigh_the_phases_and_factors() {
+        let on_off = Arrivals::OnOff {
+            on_rate: 0.3,
+            off_rate: 0.0,
+            mean_on: 1.0,
+            mean_off: 2.0,
+        };
+        assert!((on_off.mean_rate() - 0.1).abs() < 1e-12);
+        let poisson = Arrivals::Poisson {
+            rate: 0.1,
+            profile: vec![1.0, 3.0],
+            weekdays: vec![],
+        };
+        assert!((poisson.mean_rate() - 0.2).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::stats::{mean, welch_t_test, Summary};
+use crate::units::Units;
+
+/// Below this p-value a difference between two runs is flagged as significant.
+pub const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+/// The metrics of all replications of a run, one column per metric.
+///
+/// On disk this is a CSV file with a header row of metric names and one row per replication.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunResults {
+    names: Vec<String>,
+    rows: Vec<Vec<f64>>,
+}
+
+impl RunResults {
+    pub fn from_summaries(summaries: &[Summary]) -> RunResults {
+        let names = match summaries.first() {
+            Some(s) => s.metrics().iter().map(|(n, _)| n.to_string()).collect(),
+            None => vec![],
+        };
+        let rows = summaries
+            .iter()
+            .map(|s| s.metrics().iter().map(|(_, v)| *v).collect())
+            .collect();
+        RunResults { names, rows }
+    }
+
+    /// The same results with the metrics labeled with and converted to `units`, e.g. to write
+    /// them out.
+    pub fn in_units(&self, units: &Units) -> RunResults {
+        RunResults {
+            names: self.names.iter().map(|n| units.label(n)).collect(),
+            rows: self
+                .rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .zip(&self.names)
+                        .map(|(&v, n)| units.convert(n, v))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    pub fn read_csv(path: &Path) -> Result<RunResults, csv::Error> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let names = reader.headers()?.iter().map(String::from).collect();
+        let mut rows = vec![];
+        for record in reader.deserialize() {
+            rows.push(record?);
+        }
+        Ok(RunResults { names, rows })
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<(), csv::Error> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(&self.names)?;
+        for row in &self.rows {
+            writer.serialize(row)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// How many replications these results consist of.
+    pub fn replications(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// All replications' values of the metric called `name`.
+    pub fn samples(&self, name: &str) -> Option<Vec<f64>> {
+        let column = self.names.iter().position(|n| n == name)?;
+        Some(self.rows.iter().map(|row| row[column]).collect())
+    }
+}
+
+/// One metric of two runs side by side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricComparison {
+    pub name: String,
+    pub mean_a: f64,
+    pub mean_b: f64,
+
+    /// Relative change from `a` to `b` in percent. `None` if `a` is zero.
+    pub delta_percent: Option<f64>,
+
+    /// Two-sided p-value of Welch's t-test. `None` if there aren't enough replications.
+    pub p_value: Option<f64>,
+}
+
+impl MetricComparison {
+    pub fn is_significant(&self) -> bool {
+        self.p_value.is_some_and(|p| p < SIGNIFICANCE_LEVEL)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub replications_a: usize,
+    pub replications_b: usize,
+    pub metrics: Vec<MetricComparison>,
+}
+
+/// Compares every metric which both `a` and `b` have, in the order of `a`.
+pub fn compare(a: &RunResults, b: &RunResults) -> Comparison {
+    let metrics = a
+        .names
+        .iter()
+        .filter_map(|name| {
+            let samples_a = a.samples(name)?;
+            let samples_b = b.samples(name)?;
+            let mean_a = mean(&samples_a);
+            let mean_b = mean(&samples_b);
+            let delta_percent = if mean_a == 0.0 {
+                None
+            } else {
+                Some((mean_b - mean_a) / mean_a.abs() * 100.0)
+            };
+            Some(MetricComparison {
+                name: name.clone(),
+                mean_a,
+                mean_b,
+                delta_percent,
+                p_value: welch_t_test(&samples_a, &samples_b),
+            })
+        })
+        .collect();
+
+    Comparison {
+        replications_a: a.replications(),
+        replications_b: b.replications(),
+        metrics,
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:>12} {:>12} {:>10} {:>9}",
+            format!("metric (n={}/{})", self.replications_a, self.replications_b),
+            "A",
+            "B",
+            "delta",
+            "p-value"
+        )?;
+        for m in &self.metrics {
+            let delta = match m.delta_percent {
+                Some(d) => format!("{:+.2}%", d),
+                None => "-".to_string(),
+            };
+            let p_value = match m.p_value {
+                Some(p) => format!("{:.4}", p),
+                None => "-".to_string(),
+            };
+            writeln!(
+                f,
+                "{:<24} {:>12.4} {:>12.4} {:>10} {:>9}{}",
+                m.name,
+                m.mean_a,
+                m.mean_b,
+                delta,
+                p_value,
+                if m.is_significant() { " *" } else { "" }
+            )?;
+        }
+        write!(f, "* significant at p < {}", SIGNIFICANCE_LEVEL)
+    }
+}
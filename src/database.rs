@@ -0,0 +1,222 @@
+//! A SQLite database which collects the results of many runs, so they can be queried with SQL.
+//!
+//! Every run is stored with the scenario it ran, one row per finished `Request` like the
+//! requests export, and its summary metrics:
+//!
+//! ```sql
+//! CREATE TABLE scenarios (id INTEGER PRIMARY KEY, hash TEXT NOT NULL UNIQUE, toml TEXT NOT NULL);
+//! CREATE TABLE runs (id INTEGER PRIMARY KEY, scenario INTEGER NOT NULL, seed TEXT NOT NULL,
+//!     started_at INTEGER NOT NULL, ticks INTEGER, truncated INTEGER);
+//! CREATE TABLE requests (run INTEGER NOT NULL, id TEXT NOT NULL, archived_at INTEGER NOT NULL,
+//!     outcome TEXT NOT NULL, assigned_taxi TEXT, waited INTEGER NOT NULL,
+//!     party_size INTEGER NOT NULL, fare REAL NOT NULL, created_at INTEGER NOT NULL,
+//!     offered_at INTEGER, assigned_at INTEGER, picked_up_at INTEGER);
+//! CREATE TABLE metrics (run INTEGER NOT NULL, name TEXT NOT NULL, value REAL NOT NULL);
+//! ```
+//!
+//! Seeds are stored as text since they don't fit into a signed integer. `started_at` is in
+//! seconds since the Unix epoch, and `ticks` and `truncated` stay `NULL` until the run is over.
+//! Several runs may write to the same database at once.
+
+use rusqlite::{params, Connection};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::experiment::scenario_hash;
+use crate::export::RequestRecord;
+use crate::observer::Observer;
+use crate::scenario::Scenario;
+use crate::stats::Summary;
+use crate::world::{Request, World};
+
+/// How many `Request`s are collected before they're written out in one transaction.
+const BATCH_SIZE: usize = 16 * 1024;
+
+/// How long to wait for other runs which are writing to the same database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(60);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS scenarios (
+        id INTEGER PRIMARY KEY,
+        hash TEXT NOT NULL UNIQUE,
+        toml TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY,
+        scenario INTEGER NOT NULL REFERENCES scenarios (id),
+        seed TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        ticks INTEGER,
+        truncated INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS requests (
+        run INTEGER NOT NULL REFERENCES runs (id),
+        id TEXT NOT NULL,
+        archived_at INTEGER NOT NULL,
+        outcome TEXT NOT NULL,
+        assigned_taxi TEXT,
+        waited INTEGER NOT NULL,
+        party_size INTEGER NOT NULL,
+        fare REAL NOT NULL,
+        created_at INTEGER NOT NULL,
+        offered_at INTEGER,
+        assigned_at INTEGER,
+        picked_up_at INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS requests_by_run ON requests (run);
+    CREATE TABLE IF NOT EXISTS metrics (
+        run INTEGER NOT NULL REFERENCES runs (id),
+        name TEXT NOT NULL,
+        value REAL NOT NULL,
+        PRIMARY KEY (run, name)
+    );
+";
+
+fn open(path: &Path) -> io::Result<Connection> {
+    let connection = Connection::open(path).map_err(io::Error::other)?;
+    connection
+        .busy_timeout(BUSY_TIMEOUT)
+        .map_err(io::Error::other)?;
+    Ok(connection)
+}
+
+/// A run as stored in a database, from before it starts until it's over.
+#[derive(Debug)]
+pub struct DatabaseRun {
+    path: PathBuf,
+    connection: Connection,
+    id: i64,
+}
+
+impl DatabaseRun {
+    /// Creates the database at `path` if need be and adds a run of `scenario` with `seed`.
+    pub fn start(path: &Path, scenario: &Scenario, seed: u64) -> io::Result<DatabaseRun> {
+        let connection = open(path)?;
+        connection.execute_batch(SCHEMA).map_err(io::Error::other)?;
+        let toml = toml::to_string(scenario).map_err(io::Error::other)?;
+        let hash = format!("{:016x}", scenario_hash(scenario));
+        connection
+            .execute(
+                "INSERT OR IGNORE INTO scenarios (hash, toml) VALUES (?1, ?2)",
+                params![hash, toml],
+            )
+            .map_err(io::Error::other)?;
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        connection
+            .execute(
+                "INSERT INTO runs (scenario, seed, started_at)
+                 SELECT id, ?2, ?3 FROM scenarios WHERE hash = ?1",
+                params![hash, seed.to_string(), started_at],
+            )
+            .map_err(io::Error::other)?;
+        let id = connection.last_insert_rowid();
+        Ok(DatabaseRun {
+            path: path.to_path_buf(),
+            connection,
+            id,
+        })
+    }
+
+    /// An `Observer` which stores every archived `Request` of the run.
+    pub fn recorder(&self) -> io::Result<DatabaseRecorder> {
+        Ok(DatabaseRecorder {
+            connection: open(&self.path)?,
+            run: self.id,
+            buffer: Vec::with_capacity(BATCH_SIZE),
+            error: None,
+        })
+    }
+
+    /// Stores how far `world` got and the metrics of its `summary`. The `World` has to be
+    /// flushed first, so that all its `Request`s are stored too.
+    pub fn finish(mut self, world: &World, summary: &Summary) -> io::Result<()> {
+        let transaction = self.connection.transaction().map_err(io::Error::other)?;
+        transaction
+            .execute(
+                "UPDATE runs SET ticks = ?2, truncated = ?3 WHERE id = ?1",
+                params![self.id, world.age() as i64, !world.is_done()],
+            )
+            .map_err(io::Error::other)?;
+        {
+            let mut insert = transaction
+                .prepare("INSERT INTO metrics (run, name, value) VALUES (?1, ?2, ?3)")
+                .map_err(io::Error::other)?;
+            for (name, value) in summary.metrics() {
+                insert
+                    .execute(params![self.id, name, value])
+                    .map_err(io::Error::other)?;
+            }
+        }
+        transaction.commit().map_err(io::Error::other)
+    }
+}
+
+/// An `Observer` which stores every archived `Request` in a database, in batches.
+#[derive(Debug)]
+pub struct DatabaseRecorder {
+    connection: Connection,
+    run: i64,
+    buffer: Vec<RequestRecord>,
+
+    /// See `RequestExporter::error`.
+    error: Option<io::Error>,
+}
+
+impl DatabaseRecorder {
+    fn write_buffer(&mut self) -> io::Result<()> {
+        let transaction = self.connection.transaction().map_err(io::Error::other)?;
+        {
+            let mut insert = transaction
+                .prepare(
+                    "INSERT INTO requests
+                     (run, id, archived_at, outcome, assigned_taxi, waited, party_size, fare,
+                      created_at, offered_at, assigned_at, picked_up_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                )
+                .map_err(io::Error::other)?;
+            for r in &self.buffer {
+                insert
+                    .execute(params![
+                        self.run,
+                        r.id,
+                        r.archived_at as i64,
+                        r.outcome.name(),
+                        r.assigned_taxi,
+                        r.waited as i64,
+                        r.party_size,
+                        r.fare,
+                        r.created_at as i64,
+                        r.offered_at.map(|t| t as i64),
+                        r.assigned_at.map(|t| t as i64),
+                        r.picked_up_at.map(|t| t as i64),
+                    ])
+                    .map_err(io::Error::other)?;
+            }
+        }
+        transaction.commit().map_err(io::Error::other)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl Observer for DatabaseRecorder {
+    fn on_archived(&mut self, world: &World, request: &Request) {
+        if self.error.is_some() {
+            return;
+        }
+        self.buffer.push(RequestRecord::new(world, request));
+        if self.buffer.len() >= BATCH_SIZE {
+            self.error = self.write_buffer().err();
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.write_buffer(),
+        }
+    }
+}
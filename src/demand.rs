@@ -0,0 +1,317 @@
+//! Demand composed of independent layers on top of the background `Request`s, e.g. a Poisson
+//! process over the whole city, bursts of riders at the airport and a day of historical trips
+//! replayed as they happened.
+//!
+//! Every layer draws its riders from a random number generator of its own, so adding, dropping
+//! or changing one layer leaves the riders of all the others where they were. Riders are
+//! labeled with the layer they came from, which the summary breaks them down by.
+
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::arrivals::{ArrivalProcess, Arrivals};
+use crate::position::Position;
+use crate::time::SimTime;
+use crate::venue::Burst;
+use crate::zones::Zones;
+
+/// A layer of demand, as configured in the `[[demand]]` sections of a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DemandLayer {
+    /// What the layer's riders are labeled with in the summary.
+    pub name: String,
+
+    /// Seeds the layer's random numbers, so that it draws the same riders in every run, e.g.
+    /// to compare strategies under the same demand. It's derived from the seed of the run
+    /// otherwise.
+    pub seed: Option<u64>,
+
+    pub source: DemandSource,
+}
+
+/// Where the riders of a `DemandLayer` come from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DemandSource {
+    /// Riders spawn like those of the `arrivals` of a `Scenario`, at uniformly random places
+    /// in the dispatch zone at index `zone`, or anywhere if that's missing.
+    Arrivals {
+        process: Arrivals,
+        zone: Option<usize>,
+    },
+
+    /// Riders spawn in `bursts` within `radius` km of `position`, like at a `Venue` but
+    /// without a curb to queue for.
+    Bursts {
+        position: Position,
+        #[serde(default)]
+        radius: f64,
+        bursts: Vec<Burst>,
+    },
+
+    /// The trips in the CSV file at `path`, with the columns `tick`, `pickup_x`, `pickup_y`,
+    /// `dropoff_x` and `dropoff_y`, and optionally `party_size` and `trip_duration` in ticks.
+    /// Parties are of 1 and trips take their straight distance at `taxi_speed` if those are
+    /// missing. Riders are quoted at the current prices, and trips past the runtime are left
+    /// out.
+    Replay { path: PathBuf },
+}
+
+/// A trip of a `Replay` file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ReplayedTrip {
+    pub tick: u64,
+    pub pickup_x: f64,
+    pub pickup_y: f64,
+    pub dropoff_x: f64,
+    pub dropoff_y: f64,
+    pub party_size: Option<u32>,
+    pub trip_duration: Option<u64>,
+}
+
+impl ReplayedTrip {
+    pub fn pickup(&self) -> Position {
+        Position::new(self.pickup_x, self.pickup_y)
+    }
+
+    pub fn dropoff(&self) -> Position {
+        Position::new(self.dropoff_x, self.dropoff_y)
+    }
+}
+
+/// Reads the trips of a `Replay` file, in the order they're requested in.
+pub fn load_replay(path: &Path) -> io::Result<Vec<ReplayedTrip>> {
+    let invalid = |e: csv::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+    let mut trips = csv::Reader::from_path(path)
+        .map_err(invalid)?
+        .deserialize()
+        .collect::<Result<Vec<ReplayedTrip>, _>>()
+        .map_err(invalid)?;
+    trips.sort_by_key(|t| t.tick);
+    Ok(trips)
+}
+
+impl DemandLayer {
+    /// Why the parameters don't make up a layer in a city of `city_size` km with `zones`
+    /// dispatch zones, if they don't. Tries to load a `Replay` file.
+    pub fn problems(&self, zones: usize, city_size: f64) -> Vec<String> {
+        let mut problems = vec![];
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+        let within =
+            |p: Position| (0.0..=city_size).contains(&p.x) && (0.0..=city_size).contains(&p.y);
+        check(!self.name.is_empty(), "demand layers need a name");
+        match &self.source {
+            DemandSource::Arrivals { process, zone } => {
+                for problem in process.problems() {
+                    check(false, &problem);
+                }
+                check(
+                    zone.is_none_or(|z| z < zones),
+                    &format!("zone must be below the {} dispatch zones", zones),
+                );
+            }
+            DemandSource::Bursts {
+                position,
+                radius,
+                bursts,
+            } => {
+                check(within(*position), "position must lie within the city");
+                check(
+                    *radius >= 0.0 && radius.is_finite(),
+                    "radius must not be negative",
+                );
+                check(
+                    bursts
+                        .iter()
+                        .all(|b| b.every.is_none_or(|every| every >= b.duration.max(1))),
+                    "bursts can't repeat before they're over",
+                );
+            }
+            DemandSource::Replay { path } => match load_replay(path) {
+                Ok(trips) => {
+                    check(
+                        trips
+                            .iter()
+                            .all(|t| within(t.pickup()) && within(t.dropoff())),
+                        "replayed pickups and dropoffs must lie within the city",
+                    );
+                    check(
+                        trips.iter().all(|t| t.party_size != Some(0)),
+                        "replayed parties need at least 1 rider",
+                    );
+                }
+                Err(e) => check(
+                    false,
+                    &format!("replay {} can't be read: {}", path.display(), e),
+                ),
+            },
+        }
+        problems
+    }
+
+    /// Riders per tick the layer adds in the long run, if it's a Poisson-like process.
+    pub fn mean_rate(&self) -> Option<f64> {
+        match &self.source {
+            DemandSource::Arrivals { process, .. } => Some(process.mean_rate()),
+            _ => None,
+        }
+    }
+}
+
+/// The seed of the layer at `index`, unless it has a `seed` of its own, in a run seeded with
+/// `seed`.
+fn layer_seed(layer: &DemandLayer, index: usize, seed: u64) -> u64 {
+    layer.seed.unwrap_or_else(|| {
+        // Far apart from the seeds of other replications, which are consecutive.
+        seed ^ (index as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    })
+}
+
+/// A `DemandLayer` along with its state as a run goes on.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Layer {
+    source: DemandSource,
+    process: Option<ArrivalProcess>,
+
+    /// The trips of a `Replay` which are still to come.
+    replay: VecDeque<ReplayedTrip>,
+
+    /// Draws the layer's riders, and everything about them.
+    pub(crate) rng: Pcg64Mcg,
+}
+
+impl Layer {
+    /// The layer at `index` of a run seeded with `seed`. Fails if a `Replay` can't be read.
+    pub fn new(layer: &DemandLayer, index: usize, seed: u64) -> io::Result<Layer> {
+        let mut rng = Pcg64Mcg::seed_from_u64(layer_seed(layer, index, seed));
+        let process = match &layer.source {
+            DemandSource::Arrivals { process, .. } => Some(ArrivalProcess::new(process, &mut rng)),
+            _ => None,
+        };
+        let replay = match &layer.source {
+            DemandSource::Replay { path } => load_replay(path)
+                .map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!("replay {} can't be read: {}", path.display(), e),
+                    )
+                })?
+                .into(),
+            _ => VecDeque::new(),
+        };
+        Ok(Layer {
+            source: layer.source.clone(),
+            process,
+            replay,
+            rng,
+        })
+    }
+
+    /// How many riders arrive during `tick`, which is at `time`, other than replayed ones.
+    pub(crate) fn riders_at(&mut self, tick: u64, time: SimTime) -> u64 {
+        match &self.source {
+            DemandSource::Arrivals { .. } => self
+                .process
+                .as_mut()
+                .expect("Arrivals have a process.")
+                .sample(&mut self.rng, time),
+            DemandSource::Bursts { bursts, .. } => {
+                bursts.iter().map(|b| u64::from(b.riders_at(tick))).sum()
+            }
+            DemandSource::Replay { .. } => 0,
+        }
+    }
+
+    /// The replayed trips which are requested by `tick` and weren't before.
+    pub(crate) fn replayed_until(&mut self, tick: u64) -> Vec<ReplayedTrip> {
+        let due = self.replay.iter().take_while(|t| t.tick <= tick).count();
+        self.replay.drain(..due).collect()
+    }
+
+    /// Where a rider counted by `riders_at` is picked up.
+    pub(crate) fn pickup<R: Rng>(&self, rng: &mut R, zones: &Zones, city_size: f64) -> Position {
+        match &self.source {
+            DemandSource::Arrivals {
+                zone: Some(zone), ..
+            } => zones.random_in(rng, *zone),
+            DemandSource::Bursts {
+                position, radius, ..
+            } => position.random_within(rng, *radius, city_size),
+            _ => Position::random(rng, city_size),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(source: DemandSource) -> DemandLayer {
+        DemandLayer {
+            name: "test".to_string(),
+            seed: Some(1),
+            source,
+        }
+    }
+
+    #[test]
+    fn bursts_release_all_their_riders() {
+        let burst = Burst {
+            at: 5,
+            size: 7,
+            duration: 3,
+            every: None,
+        };
+        let riders: Vec<u32> = (0..10).map(|t| burst.riders_at(t)).collect();
+        assert_eq!(riders.iter().sum::<u32>(), 7);
+        assert_eq!(&riders[5..8], [2, 2, 3]);
+    }
+
+    #[test]
+    fn burst_layers_add_up_every_repetition() {
+        let bursts = vec![
+            Burst {
+                at: 5,
+                size: 7,
+                duration: 3,
+                every: Some(10),
+            },
+            Burst {
+                at: 0,
+                size: 4,
+                duration: 0,
+                every: None,
+            },
+        ];
+        let source = DemandSource::Bursts {
+            position: Position::new(1.0, 1.0),
+            radius: 0.0,
+            bursts,
+        };
+        let mut layer = Layer::new(&layer(source), 0, 1).unwrap();
+        let riders: u64 = (0..20)
+            .map(|t| layer.riders_at(t, SimTime::from_seconds(t)))
+            .sum();
+        assert_eq!(riders, 2 * 7 + 4);
+    }
+
+    #[test]
+    fn layers_whose_replay_is_missing_fail() {
+        let source = DemandSource::Replay {
+            path: PathBuf::from("/nonexistent/trips.csv"),
+        };
+        let error = Layer::new(&layer(source), 0, 1).unwrap_err();
+        assert!(error.to_string().contains("/nonexistent/trips.csv"));
+    }
+}
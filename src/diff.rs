@@ -0,0 +1,324 @@
+//! Finding where two runs which should be the same went apart, e.g. to check that a seed
+//! really determines a run or that swapping a strategy doesn't use up random numbers it
+//! shouldn't.
+//!
+//! Two event logs are compared line by line, which is tick by tick, down to every field of
+//! every event. Ids of `Request`s and `Taxi`s are random in every run, so an id in one log
+//! stands for whichever id is in the same place in the other log where it first shows up, and
+//! only differs when it stands for another one later on.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use uuid::Uuid;
+
+/// Fields which name what an event or a part of it is about, and what it's named as then.
+const ENTITY_FIELDS: [(&str, &str); 4] = [
+    ("request", "request"),
+    ("taxi", "taxi"),
+    ("id", "taxi"),
+    ("incident", "incident"),
+];
+
+/// The first place two event logs differ.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// Line of both logs, starting at 1.
+    pub line: usize,
+
+    /// Tick of the line in the first log, if it has one there.
+    pub tick: Option<u64>,
+
+    /// Kind of the event in the first log, e.g. "request_assigned".
+    pub kind: Option<String>,
+
+    /// What the differing field is about, e.g. "request 6ba7b810-…", if anything in
+    /// particular.
+    pub entity: Option<String>,
+
+    /// Path to the differing field, e.g. "taxis[3].position.x".
+    pub field: String,
+    pub a: String,
+    pub b: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The runs diverge at line {}", self.line)?;
+        if let Some(tick) = self.tick {
+            write!(f, ", tick {}", tick)?;
+        }
+        if let Some(kind) = &self.kind {
+            write!(f, ", in a {} event", kind)?;
+        }
+        if let Some(entity) = &self.entity {
+            write!(f, " of {}", entity)?;
+        }
+        writeln!(f, ":")?;
+        writeln!(f, "{:<24} {}", "field", self.field)?;
+        writeln!(f, "{:<24} {}", "a", self.a)?;
+        write!(f, "{:<24} {}", "b", self.b)
+    }
+}
+
+/// Which ids of one log stand for which of the other.
+#[derive(Debug, Default)]
+struct Ids {
+    a_to_b: HashMap<Uuid, Uuid>,
+    b_to_a: HashMap<Uuid, Uuid>,
+}
+
+impl Ids {
+    /// Whether `a` and `b` stand for the same thing, which they do if neither showed up before.
+    fn matches(&mut self, a: Uuid, b: Uuid) -> bool {
+        match (self.a_to_b.get(&a), self.b_to_a.get(&b)) {
+            (None, None) => {
+                self.a_to_b.insert(a, b);
+                self.b_to_a.insert(b, a);
+                true
+            }
+            (Some(&to_b), Some(&to_a)) => to_b == b && to_a == a,
+            _ => false,
+        }
+    }
+}
+
+/// Where two values first differ, with the path to there and what it's about.
+struct Difference {
+    field: String,
+    entity: Option<String>,
+    a: String,
+    b: String,
+}
+
+fn shown(value: Option<&Value>) -> String {
+    value.map_or("missing".to_string(), Value::to_string)
+}
+
+fn entity_of(value: &Value) -> Option<String> {
+    let object = value.as_object()?;
+    ENTITY_FIELDS.iter().find_map(|(field, name)| {
+        let id = object.get(*field)?;
+        Some(format!(
+            "{} {}",
+            name,
+            id.as_str().map_or(id.to_string(), str::to_string)
+        ))
+    })
+}
+
+fn compare(
+    a: &Value,
+    b: &Value,
+    field: &str,
+    entity: &Option<String>,
+    ids: &mut Ids,
+) -> Option<Difference> {
+    let entity = entity_of(a).or_else(|| entity.clone());
+    let differ = || Difference {
+        field: field.to_string(),
+        entity: entity.clone(),
+        a: shown(Some(a)),
+        b: shown(Some(b)),
+    };
+    let join = |key: &str| {
+        if field.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", field, key)
+        }
+    };
+    match (a, b) {
+        (Value::Object(a_fields), Value::Object(b_fields)) => {
+            let mut keys: Vec<&String> = a_fields.keys().chain(b_fields.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                match (a_fields.get(key), b_fields.get(key)) {
+                    (Some(a), Some(b)) => {
+                        if let Some(difference) = compare(a, b, &join(key), &entity, ids) {
+                            return Some(difference);
+                        }
+                    }
+                    (a, b) => {
+                        return Some(Difference {
+                            field: join(key),
+                            entity: entity.clone(),
+                            a: shown(a),
+                            b: shown(b),
+                        })
+                    }
+                }
+            }
+            None
+        }
+        (Value::Array(a_items), Value::Array(b_items)) => {
+            for (i, (a, b)) in a_items.iter().zip(b_items).enumerate() {
+                let item = format!("{}[{}]", field, i);
+                if let Some(difference) = compare(a, b, &item, &entity, ids) {
+                    return Some(difference);
+                }
+            }
+            if a_items.len() != b_items.len() {
+                return Some(Difference {
+                    field: format!("{}.len", field),
+                    entity,
+                    a: a_items.len().to_string(),
+                    b: b_items.len().to_string(),
+                });
+            }
+            None
+        }
+        (Value::String(a_text), Value::String(b_text)) => {
+            let same = match (Uuid::parse_str(a_text), Uuid::parse_str(b_text)) {
+                (Ok(a_id), Ok(b_id)) => ids.matches(a_id, b_id),
+                _ => a_text == b_text,
+            };
+            if same {
+                None
+            } else {
+                Some(differ())
+            }
+        }
+        _ if a == b => None,
+        _ => Some(differ()),
+    }
+}
+
+fn lines(path: &Path) -> io::Result<impl Iterator<Item = io::Result<Value>>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(reader
+        .lines()
+        .map(|line| line.and_then(|l| serde_json::from_str(&l).map_err(io::Error::from))))
+}
+
+/// Compares the event logs at `a` and `b` line by line, and returns where they first differ
+/// along with how many lines they have in common.
+pub fn diff_logs(a: &Path, b: &Path) -> io::Result<(Option<Divergence>, usize)> {
+    let (mut a_lines, mut b_lines) = (lines(a)?, lines(b)?);
+    let mut ids = Ids::default();
+    let mut line = 0;
+    loop {
+        let (a, b) = match (a_lines.next().transpose()?, b_lines.next().transpose()?) {
+            (None, None) => return Ok((None, line)),
+            (a, b) => (a, b),
+        };
+        line += 1;
+        let reference = a.as_ref().or(b.as_ref()).expect("One of the logs goes on.");
+        let tick = reference.get("tick").and_then(Value::as_u64);
+        let kind = reference
+            .get("kind")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let rest = |line: &Option<Value>| match line {
+            Some(_) => "more events".to_string(),
+            None => "end of log".to_string(),
+        };
+        let difference = match (&a, &b) {
+            (Some(a), Some(b)) => {
+                // Events at another tick or of another kind say more than their first field.
+                ["tick", "kind"]
+                    .iter()
+                    .find(|&&key| a.get(key) != b.get(key))
+                    .map(|&key| Difference {
+                        field: key.to_string(),
+                        entity: None,
+                        a: shown(a.get(key)),
+                        b: shown(b.get(key)),
+                    })
+                    .or_else(|| compare(a, b, "", &None, &mut ids))
+            }
+            (a, b) => Some(Difference {
+                field: "line".to_string(),
+                entity: None,
+                a: rest(a),
+                b: rest(b),
+            }),
+        };
+        if let Some(difference) = difference {
+            let divergence = Divergence {
+                line,
+                tick,
+                kind,
+                entity: difference.entity,
+                field: difference.field,
+                a: difference.a,
+                b: difference.b,
+            };
+            return Ok((Some(divergence), line - 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn id(n: u8) -> Uuid {
+        Uuid::from_bytes([n; 16])
+    }
+
+    /// An event log called `name` with `lines`, in the temporary directory.
+    fn log(name: &str, lines: &[String]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("diff-{}-{}.jsonl", std::process::id(), name));
+        fs::write(&path, lines.join("\n")).expect("The temporary directory is writable.");
+        path
+    }
+
+    fn assigned(tick: u64, request: Uuid, taxi: Uuid) -> String {
+        format!(
+            r#"{{"tick":{},"kind":"request_assigned","request":"{}","taxi":"{}"}}"#,
+            tick, request, taxi
+        )
+    }
+
+    #[test]
+    fn ids_stand_for_whatever_they_first_showed_up_as() {
+        let mut ids = Ids::default();
+        assert!(ids.matches(id(1), id(11)));
+        assert!(ids.matches(id(1), id(11)));
+        assert!(!ids.matches(id(1), id(12)));
+        assert!(!ids.matches(id(2), id(11)));
+        assert!(ids.matches(id(2), id(12)));
+    }
+
+    #[test]
+    fn logs_with_other_ids_in_the_same_places_are_the_same() {
+        let a = log(
+            "same-a",
+            &[assigned(1, id(1), id(2)), assigned(2, id(3), id(2))],
+        );
+        let b = log(
+            "same-b",
+            &[assigned(1, id(11), id(12)), assigned(2, id(13), id(12))],
+        );
+        assert_eq!(diff_logs(&a, &b).unwrap(), (None, 2));
+        fs::remove_file(a).unwrap();
+        fs::remove_file(b).unwrap();
+    }
+
+    #[test]
+    fn logs_diverge_where_an_id_stands_for_another_one() {
+        let a = log(
+            "diverged-a",
+            &[assigned(1, id(1), id(2)), assigned(2, id(3), id(2))],
+        );
+        let b = log(
+            "diverged-b",
+            &[assigned(1, id(11), id(12)), assigned(2, id(13), id(14))],
+        );
+        let (divergence, same) = diff_logs(&a, &b).unwrap();
+        let divergence = divergence.expect("The second taxi isn't the first one.");
+        assert_eq!(same, 1);
+        assert_eq!(divergence.line, 2);
+        assert_eq!(divergence.tick, Some(2));
+        assert_eq!(divergence.field, "taxi");
+        fs::remove_file(a).unwrap();
+        fs::remove_file(b).unwrap();
+    }
+}
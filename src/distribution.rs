@@ -0,0 +1,159 @@
+use rand::distributions::WeightedIndex;
+use rand::Rng;
+use rand_distr::{Distribution as _, Exp, LogNormal, Normal};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// A random quantity as specified in a `Scenario`, e.g.
+/// `trip_duration = { kind = "exponential", mean = 300 }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Distribution {
+    Constant {
+        value: f64,
+    },
+    Uniform {
+        min: f64,
+        max: f64,
+    },
+    Exponential {
+        mean: f64,
+    },
+    Normal {
+        mean: f64,
+        std_dev: f64,
+    },
+
+    /// `mean` and `std_dev` are those of the resulting values, not of the underlying normal
+    /// distribution.
+    LogNormal {
+        mean: f64,
+        std_dev: f64,
+    },
+}
+
+impl Distribution {
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        match *self {
+            Distribution::Constant { value } => value,
+            Distribution::Uniform { min, max } => {
+                if min < max {
+                    rng.gen_range(min, max)
+                } else {
+                    min
+                }
+            }
+            Distribution::Exponential { mean } => Exp::new(1.0 / mean)
+                .expect("Exponential distributions need a positive mean.")
+                .sample(rng),
+            Distribution::Normal { mean, std_dev } => Normal::new(mean, std_dev)
+                .expect("Normal distributions need a non-negative std_dev.")
+                .sample(rng),
+            Distribution::LogNormal { mean, std_dev } => {
+                let sigma_squared = (1.0 + (std_dev / mean).powi(2)).ln();
+                LogNormal::new(mean.ln() - sigma_squared / 2.0, sigma_squared.sqrt())
+                    .expect("Log-normal distributions need a positive mean.")
+                    .sample(rng)
+            }
+        }
+    }
+
+    /// Samples a duration in whole ticks, which is never shorter than a single tick.
+    pub fn sample_ticks<R: Rng + ?Sized>(&self, rng: &mut R) -> u64 {
+        self.sample(rng).round().max(1.0) as u64
+    }
+
+    /// `Err` with the reason if `sample` can't work with these parameters.
+    pub fn validate(&self) -> Result<(), String> {
+        let finite = |name: &str, value: f64| {
+            if value.is_finite() {
+                Ok(())
+            } else {
+                Err(format!("{} must be finite", name))
+            }
+        };
+        match *self {
+            Distribution::Constant { value } => finite("value", value),
+            Distribution::Uniform { min, max } => {
+                finite("min", min)?;
+                finite("max", max)?;
+                if min > max {
+                    return Err("min must not be larger than max".to_string());
+                }
+                Ok(())
+            }
+            Distribution::Exponential { mean } => {
+                if !(mean > 0.0 && mean.is_finite()) {
+                    return Err("mean must be positive".to_string());
+                }
+                Ok(())
+            }
+            Distribution::Normal { mean, std_dev } => {
+                finite("mean", mean)?;
+                if !(std_dev >= 0.0 && std_dev.is_finite()) {
+                    return Err("std_dev must not be negative".to_string());
+                }
+                Ok(())
+            }
+            Distribution::LogNormal { mean, std_dev } => {
+                if !(mean > 0.0 && mean.is_finite()) {
+                    return Err("mean must be positive".to_string());
+                }
+                if !(std_dev >= 0.0 && std_dev.is_finite()) {
+                    return Err("std_dev must not be negative".to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The expected value of `sample`.
+    pub fn mean(&self) -> f64 {
+        match *self {
+            Distribution::Constant { value } => value,
+            Distribution::Uniform { min, max } => (min + max) / 2.0,
+            Distribution::Exponential { mean }
+            | Distribution::Normal { mean, .. }
+            | Distribution::LogNormal { mean, .. } => mean,
+        }
+    }
+}
+
+/// Picks indices with chances proportional to their weights. Unlike a bare `WeightedIndex`, it
+/// remembers the weights, so it can be serialized as them.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "Vec<f64>", into = "Vec<f64>")
+)]
+pub struct Weights {
+    weights: Vec<f64>,
+    index: WeightedIndex<f64>,
+}
+
+impl Weights {
+    /// `Err` unless there's at least one positive weight and no negative ones.
+    pub fn new(weights: Vec<f64>) -> Result<Weights, String> {
+        let index = WeightedIndex::new(&weights).map_err(|e| e.to_string())?;
+        Ok(Weights { weights, index })
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        rng.sample(&self.index)
+    }
+}
+
+impl TryFrom<Vec<f64>> for Weights {
+    type Error = String;
+
+    fn try_from(weights: Vec<f64>) -> Result<Weights, String> {
+        Weights::new(weights)
+    }
+}
+
+impl From<Weights> for Vec<f64> {
+    fn from(weights: Weights) -> Vec<f64> {
+        weights.weights
+    }
+}
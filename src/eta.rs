@@ -0,0 +1,151 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::position::Position;
+use crate::roads::Roads;
+use crate::time::{ticks_as_minutes, SimTime};
+use crate::travel_times::TravelTimeMatrix;
+use crate::world::{Request, Taxi, TaxiState};
+
+/// Predicts how long `Taxi`s take to get to pickups. Both dispatch and riders go by these
+/// predictions.
+///
+/// `Taxi`s drive straight to where they need to be at the top speed of their vehicle type or
+/// the speed limit, whichever is lower, slowed down or sped up by the congestion factor of the
+/// current hour of the day. Vehicles which can't accelerate instantly take a while to get up
+/// to speed and to brake again. Rides themselves
+/// take as long as their sampled trip duration, which already accounts for traffic, scaled by
+/// how much slower or faster the vehicle is than `taxi_speed`. With a `TravelTimeMatrix`, the
+/// time it takes to get somewhere is looked up rather than worked out along the way, unless the
+/// speed limits changed since, e.g. while roads are closed.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EtaEstimator {
+    /// Factors on the speed of every `Taxi` for every hour, starting at midnight on Monday.
+    speed_profile: Vec<f64>,
+
+    roads: Roads,
+
+    travel_times: Option<TravelTimeMatrix>,
+
+    /// Whether `roads` changed since `travel_times` were worked out.
+    stale_travel_times: bool,
+}
+
+impl EtaEstimator {
+    pub fn new(speed_profile: Vec<f64>, roads: Roads) -> EtaEstimator {
+        EtaEstimator {
+            speed_profile,
+            roads,
+            travel_times: None,
+            stale_travel_times: false,
+        }
+    }
+
+    /// Looks up travel times in `matrix` rather than working them out every time.
+    pub fn with_travel_times(mut self, matrix: TravelTimeMatrix) -> EtaEstimator {
+        self.stale_travel_times = !matrix.is_for(&self.roads);
+        self.travel_times = Some(matrix);
+        self
+    }
+
+    /// Has `Taxi`s go by `roads` from now on, e.g. while some of them are closed.
+    pub(crate) fn set_roads(&mut self, roads: Roads) {
+        self.stale_travel_times = self
+            .travel_times
+            .as_ref()
+            .is_some_and(|m| !m.is_for(&roads));
+        self.roads = roads;
+    }
+
+    /// Factor on the speed of every `Taxi` at `time`.
+    pub fn congestion_at(&self, time: SimTime) -> f64 {
+        if self.speed_profile.is_empty() {
+            return 1.0;
+        }
+        let hour = time.hours() as usize % self.speed_profile.len();
+        self.speed_profile[hour]
+    }
+
+    /// Kilometers `taxi` drives per tick at `time` once it's up to speed, wherever it is.
+    pub fn speed_of(&self, taxi: &Taxi, time: SimTime) -> f64 {
+        let top_speed = match self.roads.limit_at(taxi.position()) {
+            Some(limit) => limit.min(taxi.speed()),
+            None => taxi.speed(),
+        };
+        top_speed * self.congestion_at(time)
+    }
+
+    /// Ticks it takes `taxi` to drive from `from` to `to` when starting at `time`, starting
+    /// and ending at a standstill.
+    pub fn travel_time(&self, taxi: &Taxi, from: Position, to: Position, time: SimTime) -> f64 {
+        let congestion = self.congestion_at(time);
+        let cruise_time = self
+            .travel_times
+            .as_ref()
+            .filter(|_| !self.stale_travel_times)
+            .and_then(|m| m.cruise_time(from, to, taxi.speed(), congestion))
+            .unwrap_or_else(|| self.roads.cruise_time(from, to, taxi.speed(), congestion));
+        let acceleration = match taxi.acceleration() {
+            Some(acceleration) if cruise_time > 0.0 && cruise_time.is_finite() => acceleration,
+            _ => return cruise_time,
+        };
+        // Speeding up to and braking down from the average speed takes as long as driving
+        // at it for that time, unless the trip is too short to ever get up to that speed.
+        let distance = from.distance(to);
+        let speed = distance / cruise_time;
+        if distance >= speed * speed / acceleration {
+            cruise_time + speed / acceleration
+        } else {
+            2.0 * (distance / acceleration).sqrt()
+        }
+    }
+
+    /// Ticks until `taxi` could be at the pickup of `request` when asked at `time`. A `Taxi`
+    /// which is busy with its `current` `Request` finishes that one first.
+    pub fn pickup_eta(
+        &self,
+        taxi: &Taxi,
+        current: Option<&Request>,
+        request: &Request,
+        time: SimTime,
+    ) -> f64 {
+        let (busy_for, free_at) = match (taxi.state(), current) {
+            (TaxiState::Occupied, Some(current)) => {
+                let to_pickup = if current.is_picked_up() {
+                    0.0
+                } else {
+                    self.travel_time(taxi, taxi.position(), current.pickup(), time)
+                };
+                (
+                    to_pickup + current.remaining_stop_ticks() as f64,
+                    current.dropoff(),
+                )
+            }
+            (TaxiState::InMaintenance { remaining }, _) => (remaining as f64, taxi.position()),
+            _ => (0.0, taxi.position()),
+        };
+        busy_for + self.travel_time(taxi, free_at, request.pickup(), time.after(busy_for as u64))
+    }
+}
+
+/// Riders who are quoted an ETA of `t` minutes cancel right away with a chance of
+/// `1 - exp(-t / patience)`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EtaCancellation {
+    /// Minutes. The larger, the longer riders are willing to wait.
+    pub patience: f64,
+}
+
+impl EtaCancellation {
+    /// Whether a rider who is quoted `eta` ticks cancels.
+    pub fn cancels<R: Rng + ?Sized>(&self, rng: &mut R, eta: f64) -> bool {
+        let minutes = ticks_as_minutes(eta);
+        if minutes <= 0.0 {
+            return false;
+        }
+        let probability = 1.0 - (-minutes / self.patience).exp();
+        rng.gen_bool(probability.clamp(0.0, 1.0))
+    }
+}
@@ -0,0 +1,222 @@
+//! A log of everything that happens during a run, e.g. to animate it afterwards.
+//!
+//! The log is written as JSON lines, one `LogLine` per line in the order things happened.
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::incidents::Effect;
+use crate::observer::Observer;
+use crate::position::Position;
+use crate::world::{Request, RequestOutcome, TaxiState, World};
+
+/// Something that happened to a `Request` or a `Taxi`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    RequestSpawned {
+        request: Uuid,
+        rider: Uuid,
+        pickup: Position,
+        dropoff: Position,
+        party_size: u32,
+    },
+    /// The rider was quoted a pickup ETA in ticks and accepted it.
+    RequestAssigned {
+        request: Uuid,
+        taxi: Uuid,
+        eta: f64,
+    },
+
+    /// Why dispatch picked `taxi` for `request`, with the closest `candidates` it picked from.
+    /// Only emitted when dispatch is explained.
+    DispatchExplained {
+        request: Uuid,
+        taxi: Uuid,
+
+        /// Zone the match was made in, or `None` when matching across zones.
+        zone: Option<usize>,
+
+        /// How many free `Taxi`s were looked at.
+        considered: usize,
+        candidates: Vec<DispatchCandidate>,
+    },
+
+    /// Dispatch offered the ride to the driver of `taxi`, who'll answer it later.
+    RideOffered {
+        request: Uuid,
+        taxi: Uuid,
+    },
+
+    /// The driver of `taxi` declined the ride, or didn't answer in time if it `timed_out`.
+    RideDeclined {
+        request: Uuid,
+        taxi: Uuid,
+        timed_out: bool,
+    },
+
+    /// The assigned `Taxi` reached the pickup and the ride starts.
+    RequestPickedUp {
+        request: Uuid,
+        taxi: Uuid,
+    },
+    RequestArchived {
+        request: Uuid,
+        outcome: RequestOutcome,
+    },
+
+    /// A parameter of the run changed while it ran, through `/control` or by reloading the
+    /// scenario, to the `value` it has from the next tick on.
+    ParameterChanged {
+        parameter: String,
+        value: toml::Value,
+    },
+
+    /// The `Incident` at index `incident` of all those scheduled or injected started, and
+    /// lasts until its `IncidentEnded`.
+    IncidentStarted {
+        incident: usize,
+        effect: Effect,
+    },
+    IncidentEnded {
+        incident: usize,
+    },
+
+    /// Where all `Taxi`s are. Not emitted by the `World` but written by the `EventLog` every
+    /// so many ticks, since logging every single move would be far too much.
+    TaxiPositions {
+        taxis: Vec<TaxiPosition>,
+    },
+}
+
+/// A `Taxi` which could have taken a `Request`, with its pickup ETA in ticks and the score
+/// dispatch gave it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DispatchCandidate {
+    pub taxi: Uuid,
+    pub eta: f64,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaxiPosition {
+    pub id: Uuid,
+    pub position: Position,
+    pub state: TaxiState,
+}
+
+/// One line of an event log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogLine {
+    /// Age of the `World` when the `event` happened.
+    pub tick: u64,
+
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// An `Observer` which writes every `Event` to a JSON lines file.
+#[derive(Debug)]
+pub struct EventLog {
+    writer: BufWriter<File>,
+
+    /// Every how many ticks the positions of all `Taxi`s are written.
+    position_interval: u64,
+
+    /// See `RequestExporter::error`.
+    error: Option<io::Error>,
+}
+
+impl EventLog {
+    pub fn create(path: &Path, position_interval: u64) -> io::Result<EventLog> {
+        Ok(EventLog {
+            writer: BufWriter::new(File::create(path)?),
+            position_interval: position_interval.max(1),
+            error: None,
+        })
+    }
+
+    fn write(&mut self, tick: u64, event: Event) {
+        if self.error.is_some() {
+            return;
+        }
+        let line = LogLine { tick, event };
+        self.error = serde_json::to_writer(&mut self.writer, &line)
+            .map_err(io::Error::from)
+            .and_then(|()| self.writer.write_all(b"\n"))
+            .err();
+    }
+}
+
+impl Observer for EventLog {
+    fn on_event(&mut self, world: &World, event: &Event) {
+        self.write(world.age(), event.clone());
+    }
+
+    fn on_tick(&mut self, world: &World) {
+        if world.age().is_multiple_of(self.position_interval) {
+            let taxis = world
+                .taxis()
+                .iter()
+                .map(|t| TaxiPosition {
+                    id: t.id(),
+                    position: t.position(),
+                    state: t.state(),
+                })
+                .collect();
+            self.write(world.age(), Event::TaxiPositions { taxis });
+        }
+    }
+
+    fn on_archived(&mut self, world: &World, request: &Request) {
+        self.write(
+            world.age(),
+            Event::RequestArchived {
+                request: request.id(),
+                outcome: request
+                    .outcome()
+                    .expect("Only dead requests can be archived."),
+            },
+        );
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+/// An `Observer` which writes only the `DispatchExplained` events, in the same format as an
+/// `EventLog`.
+#[derive(Debug)]
+pub struct ExplainLog(EventLog);
+
+impl ExplainLog {
+    pub fn create(path: &Path) -> io::Result<ExplainLog> {
+        Ok(ExplainLog(EventLog::create(path, 1)?))
+    }
+}
+
+impl Observer for ExplainLog {
+    fn on_event(&mut self, world: &World, event: &Event) {
+        if let Event::DispatchExplained { .. } = event {
+            self.0.on_event(world, event);
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Reads an event log written by `EventLog` line by line.
+pub fn read_log(path: &Path) -> io::Result<impl Iterator<Item = io::Result<LogLine>>> {
+    let reader = io::BufReader::new(File::open(path)?);
+    Ok(io::BufRead::lines(reader)
+        .map(|line| line.and_then(|l| serde_json::from_str(&l).map_err(io::Error::from))))
+}
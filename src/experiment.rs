@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::scenario::Scenario;
+use crate::stats::Summary;
+use crate::world::World;
+
+/// Fingerprint of everything in `scenario`, which stays the same across platforms and
+/// versions of Rust. FNV-1a of its TOML.
+pub fn scenario_hash(scenario: &Scenario) -> u64 {
+    let toml = toml::to_string(scenario).expect("Scenarios can always be written as TOML.");
+    toml.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// The seeds of `replications` independent runs of `scenario`.
+///
+/// Consecutive seeds starting at the scenario's `seed` are used, so two scenarios with the same
+/// seed are compared under the same random numbers as far as possible. Scenarios without a
+/// `seed` start at their `scenario_hash` instead, so they always yield the same runs too.
+pub fn replication_seeds(scenario: &Scenario, replications: u32) -> Vec<u64> {
+    let base = scenario.seed.unwrap_or_else(|| scenario_hash(scenario));
+    (0..u64::from(replications))
+        .map(|i| base.wrapping_add(i))
+        .collect()
+}
+
+/// Calls `run` for every one of `seeds` on up to `workers` threads at once, and hands each
+/// result to `done` on the calling thread in the order of `seeds`, as soon as all earlier ones
+/// are done. Since every replication only depends on its seed, the number of `workers` never
+/// changes the results or the order they come in.
+pub fn for_each_replication<T: Send>(
+    seeds: &[u64],
+    workers: usize,
+    run: impl Fn(u64) -> T + Sync,
+    mut done: impl FnMut(u64, T),
+) {
+    let next = AtomicUsize::new(0);
+    let (results, finished) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, seeds.len().max(1)) {
+            let results = results.clone();
+            let (next, run) = (&next, &run);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= seeds.len() {
+                    break;
+                }
+                if results.send((i, run(seeds[i]))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(results);
+
+        // Results which came in before some earlier one, by index.
+        let mut pending = BTreeMap::new();
+        let mut emitted = 0;
+        for (i, result) in finished {
+            pending.insert(i, result);
+            while let Some(result) = pending.remove(&emitted) {
+                done(seeds[emitted], result);
+                emitted += 1;
+            }
+        }
+    });
+}
+
+/// Runs `scenario` to completion once per replication, on up to `workers` threads at once.
+pub fn run_replications(
+    scenario: &Scenario,
+    replications: u32,
+    workers: usize,
+) -> io::Result<Vec<Summary>> {
+    let mut summaries = vec![];
+    for_each_replication(
+        &replication_seeds(scenario, replications),
+        workers,
+        |seed| {
+            let mut world = World::try_new(scenario, seed)?;
+            world.run_till_done();
+            Ok(world.summary())
+        },
+        |_, summary| summaries.push(summary),
+    );
+    summaries.into_iter().collect()
+}
+
+/// Replaces `{seed}` in `path` with `seed`, so that output files of replications don't overwrite
+/// each other.
+pub fn path_for_seed(path: &Path, seed: u64) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().replace("{seed}", &seed.to_string()))
+}
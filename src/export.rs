@@ -0,0 +1,385 @@
+//! Per-request and per-tick exports of a run.
+//!
+//! Exports are written as CSV or, with the `arrow` feature, as Parquet files. Which one is
+//! picked by the extension of the output path. Both formats share the same columns:
+//!
+//! Requests, one row per finished `Request` in the order they were archived, with the ticks of
+//! its `Request::timeline`:
+//!
+//! | column          | type             | description                                         |
+//! |-----------------|------------------|-----------------------------------------------------|
+//! | `id`            | string           | UUID of the `Request`                               |
+//! | `archived_at`   | u64              | Tick at which the `Request` was archived            |
+//! | `outcome`       | string           | `fulfilled`, `canceled`, `no_show` or `rejected`    |
+//! | `assigned_taxi` | string, nullable | UUID of the `Taxi` which took the `Request`, if any |
+//! | `waited`        | u64              | Ticks the `Request` waited for a `Taxi`             |
+//! | `party_size`    | u32              | How many people rode together                       |
+//! | `fare`          | f64              | What the rider was quoted for the trip              |
+//! | `created_at`    | u64              | Tick at which the `Request` was submitted           |
+//! | `offered_at`    | u64, nullable    | Tick at which a driver was first offered the ride   |
+//! | `assigned_at`   | u64, nullable    | Tick at which the `Taxi` was assigned               |
+//! | `picked_up_at`  | u64, nullable    | Tick at which the rider got into the `Taxi`         |
+//!
+//! Ticks, one row per tick:
+//!
+//! | column                 | type | description                                         |
+//! |------------------------|------|-----------------------------------------------------|
+//! | `tick`                 | u64  | Age of the `World` at the end of the tick           |
+//! | `waiting_requests`     | u64  | Active `Request`s which have no `Taxi` yet          |
+//! | `assigned_requests`    | u64  | Active `Request`s which are being driven            |
+//! | `idle_taxis`           | u64  | `Taxi`s which are free to take a `Request`          |
+//! | `occupied_taxis`       | u64  | `Taxi`s which are busy with a `Request`             |
+//! | `taxis_in_maintenance` | u64  | `Taxi`s which are off the road for maintenance      |
+//!
+//! The trip histograms of a `Summary` are only written as CSV, one row per bucket:
+//!
+//! | column         | type   | description                                       |
+//! |----------------|--------|---------------------------------------------------|
+//! | `histogram`    | string | `trip_distance` in km or `trip_duration` in ticks |
+//! | `bucket_start` | f64    | Smallest value in the bucket                      |
+//! | `bucket_end`   | f64    | Values in the bucket are less than this           |
+//! | `count`        | u64    | Fulfilled `Request`s in the bucket                |
+//!
+//! The zone breakdown of a `Summary` is only written as CSV too, in long format with one row
+//! per zone, hour of the day and metric:
+//!
+//! | column   | type   | description                                                  |
+//! |----------|--------|--------------------------------------------------------------|
+//! | `zone`   | usize  | Zone the `Request`s spawned in, numbered row by row          |
+//! | `hour`   | u32    | Hour of the day the `Request`s spawned in, from 0 to 23      |
+//! | `metric` | string | `requests_fulfilled`, `requests_unfulfilled`,                |
+//! |          |        | `fulfillment_rate`, `mean_wait` or `supply_hours`            |
+//! | `value`  | f64    | Value of the metric                                          |
+//!
+//! What every driver earned is only written as CSV as well, one row per `Taxi`:
+//!
+//! | column    | type   | description                                  |
+//! |-----------|--------|----------------------------------------------|
+//! | `id`      | string | UUID of the `Taxi`                           |
+//! | `trips`   | u64    | `Request`s the `Taxi` fulfilled              |
+//! | `revenue` | f64    | Sum of the fares of those `Request`s         |
+//! | `costs`   | f64    | Running costs of the `Taxi`                  |
+//! | `profit`  | f64    | `revenue` minus `costs`                      |
+//!
+//! If the `Scenario` has `units`, the trip distance buckets are in its distance unit and the
+//! money columns of drivers are labeled with its currency, e.g. `revenue (EUR)`.
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use crate::observer::Observer;
+use crate::stats::Summary;
+use crate::units::Units;
+use crate::world::{Request, RequestOutcome, TaxiEarnings, TaxiState, World};
+
+#[cfg(feature = "arrow")]
+mod parquet;
+
+/// One row of the requests export, which is also what spilled `Request`s are stored as.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestRecord {
+    pub id: String,
+    pub archived_at: u64,
+    pub outcome: RequestOutcome,
+    pub assigned_taxi: Option<String>,
+    pub waited: u64,
+    pub party_size: u32,
+    pub fare: f64,
+    pub created_at: u64,
+    pub offered_at: Option<u64>,
+    pub assigned_at: Option<u64>,
+    pub picked_up_at: Option<u64>,
+}
+
+impl RequestRecord {
+    pub fn new(world: &World, request: &Request) -> RequestRecord {
+        RequestRecord::archived_at(request, world.age())
+    }
+
+    /// The row of `request`, which was archived at tick `archived_at`.
+    pub fn archived_at(request: &Request, archived_at: u64) -> RequestRecord {
+        RequestRecord {
+            id: request.id().to_string(),
+            archived_at,
+            outcome: request
+                .outcome()
+                .expect("Only dead requests can be archived."),
+            assigned_taxi: request.assigned_taxi().map(|id| id.to_string()),
+            waited: request.waited(),
+            party_size: request.party_size(),
+            fare: request.fare(),
+            created_at: request.spawned_at(),
+            offered_at: request.offered_at(),
+            assigned_at: request.assigned_at(),
+            picked_up_at: request.picked_up_at(),
+        }
+    }
+}
+
+/// One row of the ticks export.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TickRecord {
+    pub tick: u64,
+    pub waiting_requests: u64,
+    pub assigned_requests: u64,
+    pub idle_taxis: u64,
+    pub occupied_taxis: u64,
+    pub taxis_in_maintenance: u64,
+}
+
+impl TickRecord {
+    pub fn new(world: &World) -> TickRecord {
+        let requests = world.active_requests();
+        let assigned_requests = requests
+            .iter()
+            .filter(|r| r.assigned_taxi().is_some())
+            .count() as u64;
+        let count_taxis = |state: fn(&TaxiState) -> bool| {
+            world.taxis().iter().filter(|t| state(&t.state())).count() as u64
+        };
+        TickRecord {
+            tick: world.age(),
+            waiting_requests: requests.len() as u64 - assigned_requests,
+            assigned_requests,
+            idle_taxis: count_taxis(|s| *s == TaxiState::Idle),
+            occupied_taxis: count_taxis(|s| *s == TaxiState::Occupied),
+            taxis_in_maintenance: count_taxis(|s| matches!(s, TaxiState::InMaintenance { .. })),
+        }
+    }
+}
+
+/// A row of an export.
+pub trait ExportRecord: Serialize + Sized {
+    /// Turns `records` into columns. Also called with no records at all to learn the schema.
+    #[cfg(feature = "arrow")]
+    fn to_batch(records: &[Self]) -> arrow_array::RecordBatch;
+}
+
+impl ExportRecord for RequestRecord {
+    #[cfg(feature = "arrow")]
+    fn to_batch(records: &[Self]) -> arrow_array::RecordBatch {
+        parquet::request_batch(records)
+    }
+}
+
+impl ExportRecord for TickRecord {
+    #[cfg(feature = "arrow")]
+    fn to_batch(records: &[Self]) -> arrow_array::RecordBatch {
+        parquet::tick_batch(records)
+    }
+}
+
+/// Where the rows of an export end up.
+#[derive(Debug)]
+enum Sink<R> {
+    Csv(csv::Writer<File>, PhantomData<R>),
+    #[cfg(feature = "arrow")]
+    Parquet(parquet::ParquetSink<R>),
+}
+
+impl<R: ExportRecord> Sink<R> {
+    fn create(path: &Path) -> io::Result<Sink<R>> {
+        match path.extension().and_then(|e| e.to_str()) {
+            #[cfg(feature = "arrow")]
+            Some("parquet") => Ok(Sink::Parquet(parquet::ParquetSink::create(path)?)),
+            #[cfg(not(feature = "arrow"))]
+            Some("parquet") => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Parquet exports need the `arrow` feature",
+            )),
+            _ => Ok(Sink::Csv(csv::Writer::from_path(path)?, PhantomData)),
+        }
+    }
+
+    fn write(&mut self, record: R) -> io::Result<()> {
+        match self {
+            Sink::Csv(writer, _) => writer.serialize(record).map_err(io::Error::from),
+            #[cfg(feature = "arrow")]
+            Sink::Parquet(sink) => sink.write(record),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Csv(writer, _) => writer.flush(),
+            #[cfg(feature = "arrow")]
+            Sink::Parquet(sink) => sink.flush(),
+        }
+    }
+}
+
+/// An `Observer` which writes every archived `Request` to a file.
+#[derive(Debug)]
+pub struct RequestExporter {
+    sink: Sink<RequestRecord>,
+
+    /// The first error while writing. `Observer`s can't fail while the `World` runs, so this is
+    /// reported on `flush` instead.
+    error: Option<io::Error>,
+}
+
+impl RequestExporter {
+    pub fn create(path: &Path) -> io::Result<RequestExporter> {
+        Ok(RequestExporter {
+            sink: Sink::create(path)?,
+            error: None,
+        })
+    }
+}
+
+impl Observer for RequestExporter {
+    fn on_archived(&mut self, world: &World, request: &Request) {
+        if self.error.is_none() {
+            self.error = self.sink.write(RequestRecord::new(world, request)).err();
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.sink.flush(),
+        }
+    }
+}
+
+/// An `Observer` which writes the state of the `World` on every tick to a file.
+#[derive(Debug)]
+pub struct TickExporter {
+    sink: Sink<TickRecord>,
+
+    /// See `RequestExporter::error`.
+    error: Option<io::Error>,
+}
+
+impl TickExporter {
+    pub fn create(path: &Path) -> io::Result<TickExporter> {
+        Ok(TickExporter {
+            sink: Sink::create(path)?,
+            error: None,
+        })
+    }
+}
+
+impl Observer for TickExporter {
+    fn on_tick(&mut self, world: &World) {
+        if self.error.is_none() {
+            self.error = self.sink.write(TickRecord::new(world)).err();
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.sink.flush(),
+        }
+    }
+}
+
+/// One row of the histograms export.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct HistogramRecord {
+    histogram: &'static str,
+    bucket_start: f64,
+    bucket_end: f64,
+    count: u64,
+}
+
+/// One row of the zone export.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ZoneRecord {
+    zone: usize,
+    hour: u32,
+    metric: &'static str,
+    value: f64,
+}
+
+/// Writes the per-zone and per-hour breakdown of `summary` to a CSV file.
+pub fn write_zones(path: &Path, summary: &Summary) -> io::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for z in &summary.by_zone_hour {
+        let metrics = [
+            ("requests_fulfilled", z.requests_fulfilled as f64),
+            ("requests_unfulfilled", z.requests_unfulfilled as f64),
+            ("fulfillment_rate", z.fulfillment_rate),
+            ("mean_wait", z.mean_wait),
+            ("supply_hours", z.supply_hours),
+        ];
+        for (metric, value) in metrics {
+            writer.serialize(ZoneRecord {
+                zone: z.zone,
+                hour: z.hour.expect("The hourly breakdown is by hour."),
+                metric,
+                value,
+            })?;
+        }
+    }
+    writer.flush()
+}
+
+/// One row of the drivers export.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct DriverRecord {
+    id: String,
+    trips: u64,
+    revenue: f64,
+    costs: f64,
+    profit: f64,
+}
+
+/// Writes the `earnings` of every driver to a CSV file, labeled with the currency of `units`.
+pub fn write_drivers(
+    path: &Path,
+    earnings: &[TaxiEarnings],
+    units: Option<&Units>,
+) -> io::Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_path(path)?;
+    let label = |column: &str| units.map_or(column.to_string(), |u| u.label(column));
+    writer.write_record([
+        "id".to_string(),
+        "trips".to_string(),
+        label("revenue"),
+        label("costs"),
+        label("profit"),
+    ])?;
+    for e in earnings {
+        writer.serialize(DriverRecord {
+            id: e.id.to_string(),
+            trips: e.trips,
+            revenue: e.revenue,
+            costs: e.costs,
+            profit: e.profit,
+        })?;
+    }
+    writer.flush()
+}
+
+/// Writes the trip histograms of `summary` to a CSV file.
+pub fn write_histograms(path: &Path, summary: &Summary) -> io::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    let histograms = [
+        ("trip_distance", &summary.trip_distances),
+        ("trip_duration", &summary.trip_durations),
+    ];
+    for (histogram, buckets) in histograms {
+        let convert = |value| {
+            summary
+                .units
+                .as_ref()
+                .map_or(value, |u| u.convert(histogram, value))
+        };
+        for (bucket_start, bucket_end, count) in buckets.buckets() {
+            writer.serialize(HistogramRecord {
+                histogram,
+                bucket_start: convert(bucket_start),
+                bucket_end: convert(bucket_end),
+                count,
+            })?;
+        }
+    }
+    writer.flush()
+}
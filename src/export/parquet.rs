@@ -0,0 +1,123 @@
+//! Parquet files for the exports, written in batches through Arrow.
+
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt32Array, UInt64Array};
+use parquet::arrow::ArrowWriter;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{ExportRecord, RequestRecord, TickRecord};
+
+/// How many rows are collected before they're written out as a row group.
+const BATCH_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub struct ParquetSink<R> {
+    /// `None` once the file is finished.
+    writer: Option<ArrowWriter<File>>,
+    buffer: Vec<R>,
+}
+
+impl<R: ExportRecord> ParquetSink<R> {
+    pub fn create(path: &Path) -> io::Result<ParquetSink<R>> {
+        let schema = R::to_batch(&[]).schema();
+        let writer =
+            ArrowWriter::try_new(File::create(path)?, schema, None).map_err(io::Error::other)?;
+        Ok(ParquetSink {
+            writer: Some(writer),
+            buffer: Vec::with_capacity(BATCH_SIZE),
+        })
+    }
+
+    pub fn write(&mut self, record: R) -> io::Result<()> {
+        self.buffer.push(record);
+        if self.buffer.len() >= BATCH_SIZE {
+            self.write_buffer()?;
+        }
+        Ok(())
+    }
+
+    fn write_buffer(&mut self) -> io::Result<()> {
+        let writer = self
+            .writer
+            .as_mut()
+            .ok_or_else(|| io::Error::other("the Parquet file is already finished"))?;
+        writer
+            .write(&R::to_batch(&self.buffer))
+            .map_err(io::Error::other)?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Writes what's left and finishes the file. Parquet files can't be appended to afterwards,
+    /// so this only does something the first time.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.writer.is_none() {
+            return Ok(());
+        }
+        self.write_buffer()?;
+        if let Some(writer) = self.writer.take() {
+            writer.close().map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds a batch from `(name, column, nullable)` triples. Nullability has to be given
+/// explicitly so that the schema doesn't depend on whether a batch happens to contain nulls.
+fn batch(columns: Vec<(&str, ArrayRef, bool)>) -> RecordBatch {
+    RecordBatch::try_from_iter_with_nullable(columns)
+        .expect("Export columns always have the same length.")
+}
+
+pub fn request_batch(records: &[RequestRecord]) -> RecordBatch {
+    let ids = StringArray::from_iter_values(records.iter().map(|r| &r.id));
+    let archived_at = UInt64Array::from_iter_values(records.iter().map(|r| r.archived_at));
+    let outcomes = StringArray::from_iter_values(records.iter().map(|r| r.outcome.name()));
+    let assigned_taxis = StringArray::from(
+        records
+            .iter()
+            .map(|r| r.assigned_taxi.as_deref())
+            .collect::<Vec<_>>(),
+    );
+    let waited = UInt64Array::from_iter_values(records.iter().map(|r| r.waited));
+    let party_sizes = UInt32Array::from_iter_values(records.iter().map(|r| r.party_size));
+    let fares = Float64Array::from_iter_values(records.iter().map(|r| r.fare));
+    let created_at = UInt64Array::from_iter_values(records.iter().map(|r| r.created_at));
+    let ticks = |f: fn(&RequestRecord) -> Option<u64>| -> ArrayRef {
+        Arc::new(UInt64Array::from(records.iter().map(f).collect::<Vec<_>>()))
+    };
+
+    batch(vec![
+        ("id", Arc::new(ids), false),
+        ("archived_at", Arc::new(archived_at), false),
+        ("outcome", Arc::new(outcomes), false),
+        ("assigned_taxi", Arc::new(assigned_taxis), true),
+        ("waited", Arc::new(waited), false),
+        ("party_size", Arc::new(party_sizes), false),
+        ("fare", Arc::new(fares), false),
+        ("created_at", Arc::new(created_at), false),
+        ("offered_at", ticks(|r| r.offered_at), true),
+        ("assigned_at", ticks(|r| r.assigned_at), true),
+        ("picked_up_at", ticks(|r| r.picked_up_at), true),
+    ])
+}
+
+pub fn tick_batch(records: &[TickRecord]) -> RecordBatch {
+    let column = |f: fn(&TickRecord) -> u64| -> ArrayRef {
+        Arc::new(UInt64Array::from_iter_values(records.iter().map(f)))
+    };
+    batch(vec![
+        ("tick", column(|r| r.tick), false),
+        ("waiting_requests", column(|r| r.waiting_requests), false),
+        ("assigned_requests", column(|r| r.assigned_requests), false),
+        ("idle_taxis", column(|r| r.idle_taxis), false),
+        ("occupied_taxis", column(|r| r.occupied_taxis), false),
+        (
+            "taxis_in_maintenance",
+            column(|r| r.taxis_in_maintenance),
+            false,
+        ),
+    ])
+}
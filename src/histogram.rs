@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Widest bar when a `Histogram` is displayed.
+const MAX_BAR_WIDTH: u64 = 40;
+
+/// Bucket widths of the histograms in the `Summary`, as configured in the `[histograms]`
+/// section of a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistogramBuckets {
+    /// Kilometers per bucket of the trip distances, or whatever the distance unit of the
+    /// `Scenario`'s `units` is.
+    pub trip_distance: f64,
+
+    /// Ticks per bucket of the trip durations.
+    pub trip_duration: f64,
+}
+
+impl Default for HistogramBuckets {
+    fn default() -> HistogramBuckets {
+        HistogramBuckets {
+            trip_distance: 1.0,
+            trip_duration: 300.0,
+        }
+    }
+}
+
+/// Counts of non-negative values in buckets of equal width, starting at 0.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Histogram {
+    bucket_width: f64,
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    pub fn new(bucket_width: f64) -> Histogram {
+        Histogram {
+            bucket_width,
+            counts: vec![],
+        }
+    }
+
+    pub fn record(&mut self, value: f64) {
+        let bucket = (value.max(0.0) / self.bucket_width) as usize;
+        if bucket >= self.counts.len() {
+            self.counts.resize(bucket + 1, 0);
+        }
+        self.counts[bucket] += 1;
+    }
+
+    /// The same counts with the bucket bounds times `factor`, e.g. to convert them to other
+    /// units.
+    pub fn scaled(&self, factor: f64) -> Histogram {
+        Histogram {
+            bucket_width: self.bucket_width * factor,
+            counts: self.counts.clone(),
+        }
+    }
+
+    /// `(start, end, count)` of every bucket up to the last one with something in it.
+    pub fn buckets(&self) -> impl Iterator<Item = (f64, f64, u64)> + '_ {
+        self.counts.iter().enumerate().map(move |(i, &count)| {
+            let start = i as f64 * self.bucket_width;
+            (start, start + self.bucket_width, count)
+        })
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        for (start, end, count) in self.buckets() {
+            let bar = "#".repeat((count * MAX_BAR_WIDTH).div_ceil(max) as usize);
+            writeln!(f, "{:>10.1} - {:<10.1} {:>10} {}", start, end, count, bar)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+//! Adverse events which hit a running `World`, to see how dispatch and repositioning cope with
+//! them.
+//!
+//! `Incident`s are either scheduled in the `[[incidents]]` sections of a `Scenario` or injected
+//! into a running `World` through `World::inject_incident`. Either way, every one of them is
+//! announced in the event stream when it starts and when it ends.
+
+use serde::{Deserialize, Serialize};
+
+use crate::position::Position;
+use crate::roads::SpeedLimit;
+
+/// Something going wrong for `duration` ticks starting at tick `at`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Incident {
+    pub at: u64,
+    pub duration: u64,
+    pub effect: Effect,
+}
+
+/// What goes wrong during an `Incident`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Effect {
+    /// Another `requests_per_minute` riders spawn with pickups in the dispatch zone at index
+    /// `zone`, on top of the usual demand.
+    DemandSpike {
+        zone: usize,
+        requests_per_minute: f64,
+    },
+
+    /// This share of the drivers who are free when the `Incident` starts log off until it
+    /// ends.
+    DriverLogoff { share: f64 },
+
+    /// No `Taxi` drives faster than `speed` km/h through the rectangle spanned by `from` and
+    /// `to`, like with a `SpeedLimit`, since they have to find their way around.
+    RoadClosure {
+        from: Position,
+        to: Position,
+        speed: f64,
+    },
+
+    /// Dispatch only gets to see `Request`s this many ticks after they were made.
+    DispatcherLatency { delay: u64 },
+}
+
+impl Incident {
+    /// Why the `Incident` can't happen in a city of `zones` dispatch zones, if it can't.
+    pub fn problems(&self, zones: usize) -> Vec<String> {
+        let mut problems = vec![];
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+        check(self.duration > 0, "incident duration must be at least 1");
+        match &self.effect {
+            Effect::DemandSpike {
+                zone,
+                requests_per_minute,
+            } => {
+                check(
+                    *zone < zones,
+                    &format!("demand spike zone must be below {}", zones),
+                );
+                check(
+                    *requests_per_minute > 0.0 && requests_per_minute.is_finite(),
+                    "demand spike requests_per_minute must be positive",
+                );
+            }
+            Effect::DriverLogoff { share } => check(
+                (0.0..=1.0).contains(share),
+                "driver logoff share must be between 0 and 1",
+            ),
+            Effect::RoadClosure { speed, .. } => check(
+                *speed > 0.0 && speed.is_finite(),
+                "road closure speed must be positive",
+            ),
+            Effect::DispatcherLatency { delay } => {
+                check(*delay > 0, "dispatcher latency delay must be at least 1")
+            }
+        }
+        problems
+    }
+
+    /// The first tick after the `Incident`.
+    pub fn end(&self) -> u64 {
+        self.at + self.duration
+    }
+}
+
+/// The `Incident`s of a running `World`, by whether they started yet.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Incidents {
+    /// Every `Incident` ever scheduled or injected, which they're referred to by index in.
+    incidents: Vec<Incident>,
+
+    /// Indices of those which didn't start yet.
+    pending: Vec<usize>,
+
+    /// Indices of those which started but didn't end yet.
+    active: Vec<usize>,
+
+    /// The speed limits of the `Scenario`, which road closures add to.
+    speed_limits: Vec<SpeedLimit>,
+}
+
+impl Incidents {
+    pub fn new(incidents: &[Incident], speed_limits: &[SpeedLimit]) -> Incidents {
+        let mut all = Incidents {
+            speed_limits: speed_limits.to_vec(),
+            ..Incidents::default()
+        };
+        for incident in incidents {
+            all.schedule(incident.clone());
+        }
+        all
+    }
+
+    /// Adds `incident` and returns its index.
+    pub fn schedule(&mut self, incident: Incident) -> usize {
+        self.pending.push(self.incidents.len());
+        self.incidents.push(incident);
+        self.incidents.len() - 1
+    }
+
+    pub fn get(&self, index: usize) -> &Incident {
+        &self.incidents[index]
+    }
+
+    /// Moves the `Incident`s which start or end at `tick` along, and returns their indices.
+    pub(crate) fn advance(&mut self, tick: u64) -> (Vec<usize>, Vec<usize>) {
+        let incidents = &self.incidents;
+        let ended: Vec<usize> = self
+            .active
+            .iter()
+            .copied()
+            .filter(|&i| incidents[i].end() <= tick)
+            .collect();
+        self.active.retain(|i| !ended.contains(i));
+        let started: Vec<usize> = self
+            .pending
+            .iter()
+            .copied()
+            .filter(|&i| incidents[i].at <= tick)
+            .collect();
+        self.pending.retain(|i| !started.contains(i));
+        // Those which started and ended since the last tick still get to start.
+        self.active.extend(&started);
+        (started, ended)
+    }
+
+    /// The `Effect`s of the `Incident`s which are going on.
+    pub fn active(&self) -> impl Iterator<Item = &Effect> {
+        self.active.iter().map(move |&i| &self.incidents[i].effect)
+    }
+
+    /// Ticks after `tick` until the next `Incident` starts or ends, if any will.
+    pub fn next_after(&self, tick: u64) -> Option<u64> {
+        let starts = self.pending.iter().map(|&i| self.incidents[i].at);
+        let ends = self.active.iter().map(|&i| self.incidents[i].end());
+        starts.chain(ends).map(|t| t.saturating_sub(tick + 1)).min()
+    }
+
+    /// The speed limits of the `Scenario` along with the road closures going on.
+    pub fn speed_limits(&self) -> Vec<SpeedLimit> {
+        let closures = self.active().filter_map(|effect| match effect {
+            Effect::RoadClosure { from, to, speed } => Some(SpeedLimit {
+                name: "road closure".to_string(),
+                from: *from,
+                to: *to,
+                speed: *speed,
+            }),
+            _ => None,
+        });
+        self.speed_limits.iter().cloned().chain(closures).collect()
+    }
+
+    /// Ticks dispatch lags behind, the longest of all `DispatcherLatency`s going on.
+    pub fn dispatch_delay(&self) -> u64 {
+        self.active()
+            .filter_map(|effect| match effect {
+                Effect::DispatcherLatency { delay } => Some(*delay),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latency(at: u64, duration: u64) -> Incident {
+        Incident {
+            at,
+            duration,
+            effect: Effect::DispatcherLatency { delay: 5 },
+        }
+    }
+
+    #[test]
+    fn incidents_start_at_their_tick_and_end_after_their_duration() {
+        let mut incidents = Incidents::new(&[latency(10, 5)], &[]);
+        assert_eq!(incidents.advance(9), (vec![], vec![]));
+        assert_eq!(incidents.advance(10), (vec![0], vec![]));
+        assert_eq!(incidents.dispatch_delay(), 5);
+        assert_eq!(incidents.advance(14), (vec![], vec![]));
+        assert_eq!(incidents.advance(15), (vec![], vec![0]));
+        assert_eq!(incidents.dispatch_delay(), 0);
+        assert_eq!(incidents.advance(16), (vec![], vec![]));
+    }
+
+    #[test]
+    fn incidents_which_were_skipped_over_still_start() {
+        let mut incidents = Incidents::new(&[latency(3, 1)], &[]);
+        assert_eq!(incidents.advance(10), (vec![0], vec![]));
+        assert_eq!(incidents.advance(11), (vec![], vec![0]));
+    }
+
+    #[test]
+    fn next_after_counts_the_ticks_in_between() {
+        let mut incidents = Incidents::new(&[latency(10, 5), latency(20, 1)], &[]);
+        assert_eq!(incidents.next_after(0), Some(9));
+        assert_eq!(incidents.next_after(9), Some(0));
+        incidents.advance(10);
+        assert_eq!(incidents.next_after(10), Some(4));
+        incidents.advance(15);
+        assert_eq!(incidents.next_after(15), Some(4));
+        incidents.advance(20);
+        incidents.advance(21);
+        assert_eq!(incidents.next_after(21), None);
+    }
+}
@@ -0,0 +1,44 @@
+//! A discrete simulation of a fleet of `Taxi`s serving `Request`s.
+
+pub mod ab_test;
+pub mod archive;
+pub mod arrivals;
+pub mod compare;
+#[cfg(feature = "sqlite")]
+pub mod database;
+pub mod demand;
+pub mod diff;
+pub mod distribution;
+pub mod eta;
+pub mod events;
+pub mod experiment;
+pub mod export;
+pub mod histogram;
+pub mod incidents;
+pub mod observer;
+pub mod offers;
+pub mod optimize;
+pub mod position;
+pub mod pricing;
+pub mod quantile;
+pub mod queueing;
+pub mod reload;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod rider_choice;
+pub mod roads;
+pub mod scenario;
+pub mod server;
+pub mod stats;
+pub mod time;
+pub mod transit;
+pub mod travel_times;
+pub mod units;
+pub mod vehicle;
+pub mod venue;
+pub mod window;
+pub mod world;
+pub mod zones;
+
+pub use crate::scenario::Scenario;
+pub use crate::world::{Request, RequestOutcome, Stop, Taxi, TaxiEarnings, TaxiState, Trip, World};
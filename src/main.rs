@@ -1,217 +1,639 @@
-use rand::prelude::*;
-use std::convert::TryInto;
-use std::fmt;
-use uuid::Uuid;
-
-/// Somebody who tries to hail a `Taxi` will issue a `Request`.
-/// A `Request` is therefore represents somebody's desire to be picked up by a `Taxi`.
-/// It has a `max_lifetime` which expires the `Request` as if it timed out because it didn't
-/// fulfilled quickly enough.
-#[derive(Debug, Clone)]
-pub struct Request {
-    id: Uuid,
-    remaining_waiting_time: u64,
-    assigned_taxi: Option<Uuid>,
-    fulfillment_time: u64,
-}
+use clap::{Args, Parser, Subcommand};
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-impl Request {
-    pub fn new() -> Request {
-        Request {
-            id: Uuid::new_v4(),
-            remaining_waiting_time: 100,
-            assigned_taxi: None,
-            fulfillment_time: 100,
-        }
-    }
+use taxi_simulation::compare::{compare, RunResults};
+#[cfg(feature = "sqlite")]
+use taxi_simulation::database::DatabaseRun;
+use taxi_simulation::diff::diff_logs;
+use taxi_simulation::events::{EventLog, ExplainLog};
+use taxi_simulation::experiment::{
+    for_each_replication, path_for_seed, replication_seeds, run_replications,
+};
+use taxi_simulation::export::{
+    write_drivers, write_histograms, write_zones, RequestExporter, TickExporter,
+};
+use taxi_simulation::observer::StatusLog;
+use taxi_simulation::optimize::{optimize, Goal, Target};
+use taxi_simulation::queueing::MmcPrediction;
+use taxi_simulation::reload::ScenarioWatcher;
+use taxi_simulation::server::{apply_controls, Metrics, MetricsRecorder, MetricsServer};
+use taxi_simulation::stats::Summary;
+use taxi_simulation::units::DistanceUnit;
+use taxi_simulation::{Scenario, World};
 
-    pub fn is_alive(&self) -> bool {
-        self.remaining_waiting_time > 0 && self.fulfillment_time > 0
-    }
-}
+/// Set when the user hits Ctrl-C during a run, which then stops after the current tick.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
-#[derive(Debug)]
-pub struct Taxi {
-    id: Uuid,
-    is_occupied: bool,
+#[derive(Debug, Parser)]
+#[command(about = "Simulates a fleet of taxis serving ride requests")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
 }
 
-impl Taxi {
-    pub fn new() -> Taxi {
-        Taxi {
-            id: Uuid::new_v4(),
-            is_occupied: false,
-        }
-    }
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run a scenario and print a summary of its key metrics.
+    ///
+    /// Ctrl-C stops the run after the current tick, writes out what was exported so far and
+    /// prints a summary of the ticks run up to then. Pressing it again quits right away.
+    Run(Box<RunOptions>),
+
+    /// Compare the key metrics of two runs side by side.
+    ///
+    /// Each side is either a CSV result file written by `run --output` or a scenario file
+    /// which is run on the spot.
+    Compare {
+        a: PathBuf,
+        b: PathBuf,
+
+        /// How many replications to run for sides which are scenario files.
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+        replications: u32,
+
+        /// See `run --workers`.
+        #[arg(long, default_value_t = 1)]
+        workers: usize,
+    },
+
+    /// Find the first place where two event logs written by `run --events-out` differ, e.g. to
+    /// check that two runs with the same seed are the same.
+    ///
+    /// The logs are compared line by line down to every field. Ids of requests and taxis are
+    /// random in every run, so they're matched up by where they first show up. Fails if the
+    /// runs diverge.
+    DiffRuns { a: PathBuf, b: PathBuf },
+
+    /// Search for the smallest fleet which meets a target, or the one which maximizes a metric.
+    ///
+    /// Every fleet size it tries runs the same replications, and is judged by the mean of the
+    /// metric over them. Prints every fleet size as soon as it ran.
+    Optimize {
+        /// Scenario file in TOML. Uses the default scenario if missing.
+        #[arg(long)]
+        scenario: Option<PathBuf>,
+
+        /// Find the fewest taxis which meet this, e.g. `mean_wait<=120` or
+        /// `fulfillment_rate>=0.95`. Assumes that more taxis never miss it by more.
+        #[arg(
+            long,
+            required_unless_present = "maximize",
+            conflicts_with = "maximize"
+        )]
+        target: Option<Target>,
+
+        /// Find the fleet size with the highest mean of this metric, e.g. `fleet_profit`.
+        /// Assumes that it has a single peak.
+        #[arg(long)]
+        maximize: Option<String>,
+
+        /// Smallest fleet size to try.
+        #[arg(long, default_value_t = 1)]
+        min_taxis: u32,
+
+        /// Largest fleet size to try. Twice the fleet of the scenario if missing.
+        #[arg(long)]
+        max_taxis: Option<u32>,
+
+        /// How many replications to run of every fleet size.
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+        replications: u32,
+
+        /// See `run --workers`.
+        #[arg(long, default_value_t = 1)]
+        workers: usize,
+
+        /// Write every fleet size which was tried, in order, to this CSV file.
+        #[arg(long)]
+        trace_out: Option<PathBuf>,
+    },
+
+    /// Run a scenario as a long-running server which can be monitored while it runs.
+    ///
+    /// Prometheus metrics are served at `/metrics`. Posting e.g.
+    /// `/control?request_spawn_chance=0.5&matching_interval=10` changes those parameters from the
+    /// next tick on. Besides those two, the `surge_cap` can be changed. Ctrl-C stops the run
+    /// like for `run`.
+    ///
+    /// With `--watch`, changes to the `request_spawn_chance` and `pricing` in the scenario file
+    /// are picked up on the fly as well.
+    Serve {
+        /// Scenario file in TOML. Uses the default scenario if missing.
+        #[arg(long)]
+        scenario: Option<PathBuf>,
+
+        /// Address to serve metrics on.
+        #[arg(long, default_value = "127.0.0.1:9898")]
+        listen: String,
+
+        /// Ticks per second of wall-clock time. Runs as fast as possible if 0.
+        #[arg(long, default_value_t = 0.0)]
+        tick_rate: f64,
+
+        /// See `run --threads`.
+        #[arg(long, default_value_t = 1)]
+        threads: usize,
+
+        /// Reload the scenario file whenever it changes. Changes to parameters which can't be
+        /// reloaded are ignored.
+        #[arg(long, requires = "scenario")]
+        watch: bool,
+
+        /// Write a log of everything that happens to this JSON lines file, including every
+        /// parameter which was changed through `/control` or `--watch`.
+        #[arg(long)]
+        events_out: Option<PathBuf>,
+
+        /// See `run --events-interval`.
+        #[arg(long, default_value_t = 10)]
+        events_interval: u64,
+    },
+
+    /// Animate an event log written by `run --events-out`.
+    ///
+    /// GIFs are drawn directly, other formats such as MP4 need `ffmpeg`. Only available with
+    /// the `render` feature.
+    Render {
+        #[arg(long)]
+        input: PathBuf,
+
+        #[arg(long)]
+        out: PathBuf,
+
+        /// Width and height of the animation in pixels.
+        #[arg(long, default_value_t = 800)]
+        size: u32,
+
+        #[arg(long, default_value_t = 30)]
+        fps: u32,
+
+        /// Scenario file in TOML whose `units` the scale bar is in. It's in km if missing.
+        #[arg(long)]
+        scenario: Option<PathBuf>,
+    },
 }
 
-#[derive(Debug)]
-pub struct World {
-    /// How long the `World` updates for in ticks/seconds.
-    runtime: u64,
+/// Options of `Command::Run`, boxed since there are so many of them.
+#[derive(Debug, Args)]
+struct RunOptions {
+    /// Scenario file in TOML. Uses the default scenario if missing.
+    #[arg(long)]
+    scenario: Option<PathBuf>,
+
+    /// How many independent replications to run.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    replications: u32,
+
+    /// Write the metrics of every replication to this CSV file.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Write every archived request to this file. Parquet if it ends in `.parquet`, CSV
+    /// otherwise. `{seed}` is replaced with the seed of the replication.
+    #[arg(long)]
+    requests_out: Option<PathBuf>,
 
-    /// How long the `World` has been running for.
-    age: u64,
+    /// Write the state of the world on every tick to this file, like `--requests-out`.
+    #[arg(long)]
+    ticks_out: Option<PathBuf>,
 
-    /// Change to spawn a request per tick.
-    request_spawn_chance: f64,
+    /// Write the trip histograms of every replication to this CSV file, like
+    /// `--requests-out`.
+    #[arg(long)]
+    histograms_out: Option<PathBuf>,
 
-    /// When the number of `active_requests` reaches this number, no further requests will be
-    /// allowed to spawn.
-    max_active_requests: u32,
+    /// Write fulfillment, waiting times and supply per zone and hour of the day of every
+    /// replication to this CSV file, like `--requests-out`.
+    #[arg(long)]
+    zones_out: Option<PathBuf>,
 
-    /// Current `Taxi`s in the `World`.
-    taxis: Vec<Taxi>,
+    /// Write the trips, revenue, costs and profit of every driver of every replication to
+    /// this CSV file, like `--requests-out`.
+    #[arg(long)]
+    drivers_out: Option<PathBuf>,
 
-    /// Currently active `Request`s in the `World`. These are either being waited for or are
-    /// being driven.
-    active_requests: Vec<Request>,
+    /// Write a log of everything that happens to this JSON lines file, e.g. to `render`
+    /// it. `{seed}` is replaced with the seed of the replication.
+    #[arg(long)]
+    events_out: Option<PathBuf>,
 
-    /// Canceled or fulfilled requests. Append only.
-    archived_requests: Vec<Request>,
+    /// Store every replication with its scenario, requests and metrics in this SQLite
+    /// database, which is created if need be. Only available with the `sqlite` feature.
+    #[arg(long)]
+    database: Option<PathBuf>,
 
-    rng: SmallRng,
+    /// Write why dispatch picked each taxi, with the closest candidates it picked from, to
+    /// this JSON lines file, like `--events-out`.
+    #[arg(long)]
+    explain_out: Option<PathBuf>,
+
+    /// Every how many ticks the event log records where all taxis are.
+    #[arg(long, default_value_t = 10)]
+    events_interval: u64,
+
+    /// How many threads to dispatch the zones of the city on. Only changes how fast a run
+    /// is, never its outcome.
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+
+    /// How many replications to run at once. Their results are the same and printed in
+    /// the same order no matter how many run at once.
+    #[arg(long, default_value_t = 1)]
+    workers: usize,
+
+    /// Skip ahead over ticks in which nothing happens, such as quiet nights. Changes the
+    /// random numbers a run uses, and per-tick exports leave out the ticks skipped over.
+    #[arg(long)]
+    adaptive_time_step: bool,
+
+    /// Print the state of the world on every tick.
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Check the invariants of the world after every tick and abort as soon as one is
+    /// violated. Slow, meant for debugging.
+    #[arg(long)]
+    check_invariants: bool,
+
+    /// Only validate the scenario and print it with all defaults filled in, without
+    /// running it.
+    #[arg(long)]
+    check: bool,
 }
 
-impl World {
-    /// `runtime` is simulation seconds.
-    pub fn new(
-        runtime: u64,
-        request_spawn_chance: f64,
-        max_active_requests: u32,
-        number_of_taxis: u32,
-    ) -> World {
-        let taxis = (0..number_of_taxis).map(|_| Taxi::new()).collect();
-        let rng = SmallRng::from_rng(thread_rng()).unwrap();
-
-        World {
-            runtime,
-            age: 0,
-            request_spawn_chance,
-            max_active_requests,
-            taxis,
-            active_requests: vec![],
-            archived_requests: vec![],
-            rng,
-        }
+fn main() {
+    if let Err(e) = run(Cli::parse().command) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
     }
-
-    /// Debug print `World` info.
-    pub fn info(&self) {
-        println!("{}", self);
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        process::exit(130);
     }
+}
 
-    /// Spawns requests with a small chance.
-    pub fn maybe_spawn_request(&mut self) {
-        if self.active_requests.len() < self.max_active_requests.try_into().unwrap()
-            && self.rng.gen_bool(self.request_spawn_chance)
-        {
-            self.active_requests.push(Request::new())
+/// Lets Ctrl-C set `INTERRUPTED` instead of killing the process, unless it was set already.
+fn stop_on_interrupt() {
+    let handler = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(130);
         }
+    });
+    if let Err(e) = handler {
+        eprintln!(
+            "Warning: Ctrl-C will quit without writing any results: {}",
+            e
+        );
     }
+}
 
-    /// Try to distribute all waiting `Request`s to unoccupied `Taxi`s.
-    pub fn distribute_unfulfilled_requests(&mut self) {
-        let waiting_requests = self
-            .active_requests
-            .iter_mut()
-            .filter(|r| r.assigned_taxi.is_none());
+fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
 
-        for r in waiting_requests {
-            let unoccupied_taxi = self.taxis.iter_mut().find(|t| !t.is_occupied);
+/// Introduces the summary of `world`, pointing out if the run was cut short.
+fn heading(run: &str, seed: u64, world: &World) -> String {
+    if world.is_done() {
+        format!("{} with seed {}:", run, seed)
+    } else {
+        format!(
+            "{} with seed {}, TRUNCATED by Ctrl-C after {} of {} ticks at {}:",
+            run,
+            seed,
+            world.age(),
+            world.runtime(),
+            world
+                .units()
+                .cloned()
+                .unwrap_or_default()
+                .time_of_day(world.now())
+        )
+    }
+}
 
-            if let Some(taxi) = unoccupied_taxi {
-                r.assigned_taxi = Some(taxi.id);
-                taxi.is_occupied = true;
-            } else {
-                break;
+fn run(command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Run(options) => {
+            let RunOptions {
+                scenario,
+                replications,
+                output,
+                requests_out,
+                ticks_out,
+                histograms_out,
+                zones_out,
+                drivers_out,
+                database,
+                events_out,
+                explain_out,
+                events_interval,
+                threads,
+                workers,
+                adaptive_time_step,
+                verbose,
+                check_invariants,
+                check,
+            } = *options;
+            let scenario = match scenario {
+                Some(path) => Scenario::load(&path)?,
+                None => Scenario::default(),
+            };
+            if check {
+                scenario.validate()?;
+                print!("{}", toml::to_string(&scenario)?);
+                return Ok(());
+            }
+
+            #[cfg(not(feature = "sqlite"))]
+            if database.is_some() {
+                return Err("databases need the `sqlite` feature".into());
+            }
+
+            stop_on_interrupt();
+            let run_replication = |seed| -> io::Result<Option<(String, Summary)>> {
+                if interrupted() {
+                    return Ok(None);
+                }
+                let mut world = World::try_new(&scenario, seed)?;
+                #[cfg(feature = "sqlite")]
+                let database_run = match &database {
+                    Some(path) => {
+                        let run = DatabaseRun::start(path, &scenario, seed)?;
+                        world.add_observer(Box::new(run.recorder()?));
+                        Some(run)
+                    }
+                    None => None,
+                };
+                world.set_threads(threads);
+                world.set_check_invariants(check_invariants);
+                world.set_adaptive_time_step(adaptive_time_step);
+                if let Some(path) = &requests_out {
+                    world.add_observer(Box::new(RequestExporter::create(&path_for_seed(
+                        path, seed,
+                    ))?));
+                }
+                if let Some(path) = &ticks_out {
+                    world.add_observer(Box::new(TickExporter::create(&path_for_seed(path, seed))?));
+                }
+                if let Some(path) = &events_out {
+                    world.add_observer(Box::new(EventLog::create(
+                        &path_for_seed(path, seed),
+                        events_interval,
+                    )?));
+                }
+                if let Some(path) = &explain_out {
+                    world.set_explain_dispatch(true);
+                    world.add_observer(Box::new(ExplainLog::create(&path_for_seed(path, seed))?));
+                }
+                if verbose {
+                    world.add_observer(Box::new(StatusLog::new(io::stdout())));
+                }
+                while !world.is_done() && !interrupted() {
+                    world.tick();
+                }
+                world.flush()?;
+                let summary = world.summary();
+                if let Some(path) = &histograms_out {
+                    write_histograms(&path_for_seed(path, seed), &summary)?;
+                }
+                if let Some(path) = &zones_out {
+                    write_zones(&path_for_seed(path, seed), &summary)?;
+                }
+                if let Some(path) = &drivers_out {
+                    write_drivers(
+                        &path_for_seed(path, seed),
+                        &world.taxi_earnings(),
+                        world.units(),
+                    )?;
+                }
+                #[cfg(feature = "sqlite")]
+                if let Some(run) = database_run {
+                    run.finish(&world, &summary)?;
+                }
+                Ok(Some((heading("Replication", seed, &world), summary)))
+            };
+            let mut summaries = vec![];
+            let mut error = None;
+            for_each_replication(
+                &replication_seeds(&scenario, replications),
+                workers,
+                run_replication,
+                |_, result| match result {
+                    Ok(Some((heading, summary))) if error.is_none() => {
+                        println!("{}\n{}", heading, summary);
+                        summaries.push(summary);
+                    }
+                    Ok(_) => (),
+                    Err(e) => {
+                        error.get_or_insert(e);
+                    }
+                },
+            );
+            if let Some(e) = error {
+                return Err(e.into());
             }
-        }
-    }
 
-    /// Update and tick down all `Request`s.
-    pub fn update_requests(&mut self) {
-        for r in &mut self.active_requests {
-            if r.assigned_taxi.is_some() {
-                r.fulfillment_time -= 1;
+            if interrupted() {
+                println!("No M/M/c baseline because the run was truncated.");
             } else {
-                r.remaining_waiting_time -= 1;
+                match MmcPrediction::for_scenario(&scenario) {
+                    Ok(prediction) => println!("{}", prediction.check(&summaries)),
+                    Err(reason) => println!("No M/M/c baseline because {}.", reason),
+                }
             }
-        }
-    }
 
-    /// Moved `Request`s from `active_requests` to `archived_requests` if they have either:
-    /// 1) reached their `fulfillment_time` or
-    /// 2) reached their `remaining_waiting_time`.
-    pub fn cleanup_requests(&mut self) {
-        // First step is to clone all eligible `Request`s from `active_requests` to
-        // `archived_requests`.
-        for r in &self.active_requests {
-            if !r.is_alive() {
-                self.archived_requests.push(r.clone());
-
-                // Don't forget to reset the `Taxi` so that it may now take a `Request` again.
-                // However, this is only important if this `Request` actually had a `Taxi`
-                // assigned. In the case of a canceled `Request`, it didn't have a `Taxi`.
-                if let Some(taxi_id) = r.assigned_taxi {
-                    let taxi = self
-                        .taxis
-                        .iter_mut()
-                        .find(|t| t.id == taxi_id)
-                        .expect("We expected to find a Taxi but didn't find one.");
-                    taxi.is_occupied = false;
+            if let Some(output) = output {
+                let results = RunResults::from_summaries(&summaries);
+                match &scenario.units {
+                    Some(units) => results.in_units(units).write_csv(&output)?,
+                    None => results.write_csv(&output)?,
                 }
             }
         }
+        Command::Compare {
+            a,
+            b,
+            replications,
+            workers,
+        } => {
+            let a = load_results(&a, replications, workers)?;
+            let b = load_results(&b, replications, workers)?;
+            println!("{}", compare(&a, &b));
+        }
+        Command::DiffRuns { a, b } => match diff_logs(&a, &b)? {
+            (Some(divergence), same) => {
+                println!("{}", divergence);
+                return Err(format!("the runs diverged after {} identical lines", same).into());
+            }
+            (None, same) => println!("The runs are the same for all {} lines.", same),
+        },
+        Command::Optimize {
+            scenario,
+            target,
+            maximize,
+            min_taxis,
+            max_taxis,
+            replications,
+            workers,
+            trace_out,
+        } => {
+            let scenario = match scenario {
+                Some(path) => Scenario::load(&path)?,
+                None => Scenario::default(),
+            };
+            let goal = match (target, maximize) {
+                (Some(target), _) => Goal::Meet(target),
+                (None, Some(metric)) => Goal::Maximize(metric),
+                (None, None) => unreachable!("clap requires a target or a metric to maximize."),
+            };
+            let max_taxis = max_taxis.unwrap_or(2 * scenario.fleet_size().max(1));
+            let header = match &goal {
+                Goal::Meet(target) => {
+                    format!("{:<24} {:>12} {:>12}", "taxis", target.metric, "target")
+                }
+                Goal::Maximize(metric) => format!("{:<24} {:>12}", "taxis", metric),
+            };
+            println!("{}", header);
+            let search = optimize(
+                &scenario,
+                goal,
+                min_taxis..=max_taxis,
+                replications,
+                workers,
+                |step| println!("{}", step),
+            )?;
+            println!("{}", search);
+            if let Some(path) = trace_out {
+                search.write_trace(&path)?;
+            }
+        }
+        Command::Serve {
+            scenario: path,
+            listen,
+            tick_rate,
+            threads,
+            watch,
+            events_out,
+            events_interval,
+        } => {
+            let scenario = match &path {
+                Some(path) => Scenario::load(path)?,
+                None => Scenario::default(),
+            };
+            let mut watcher = match &path {
+                Some(path) if watch => Some(ScenarioWatcher::new(path, scenario.clone())),
+                _ => None,
+            };
+            let metrics = Arc::new(Mutex::new(Metrics::default()));
+            let (controls, control_requests) = mpsc::channel();
+            let server = MetricsServer::spawn(&listen, metrics.clone(), controls)?;
+            println!("Serving metrics on http://{}/metrics", server.address());
 
-        // Second step is to bulk delete all th
-        self.active_requests.retain(|r| r.is_alive());
+            stop_on_interrupt();
+            let seed = replication_seeds(&scenario, 1)[0];
+            let mut world = World::try_new(&scenario, seed)?;
+            world.set_threads(threads);
+            world.add_observer(Box::new(MetricsRecorder::new(metrics)));
+            if let Some(path) = &events_out {
+                world.add_observer(Box::new(EventLog::create(path, events_interval)?));
+            }
+            let start = Instant::now();
+            while !world.is_done() && !interrupted() {
+                match watcher.as_mut().and_then(ScenarioWatcher::poll) {
+                    Some(Ok(reload)) => {
+                        for parameter in &reload.ignored {
+                            eprintln!("Ignoring {} since it can't be reloaded.", parameter);
+                        }
+                        for problem in reload.apply(&mut world) {
+                            eprintln!("Couldn't reload: {}.", problem);
+                        }
+                    }
+                    Some(Err(e)) => eprintln!("Not reloading the scenario: {}", e),
+                    None => (),
+                }
+                for (changes, reply) in control_requests.try_iter() {
+                    let result = apply_controls(&changes, &mut world);
+                    // The client may have given up waiting already.
+                    let _ = reply.send(result);
+                }
+                world.tick();
+                if tick_rate > 0.0 {
+                    let due = start + Duration::from_secs_f64(world.age() as f64 / tick_rate);
+                    if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                        thread::sleep(wait);
+                    }
+                }
+            }
+            world.flush()?;
+            println!("{}\n{}", heading("Run", seed, &world), world.summary());
+        }
+        Command::Render {
+            input,
+            out,
+            size,
+            fps,
+            scenario,
+        } => {
+            let distance = match scenario {
+                Some(path) => Scenario::load(&path)?.units.unwrap_or_default().distance,
+                None => DistanceUnit::Km,
+            };
+            render(&input, &out, size, fps, distance)?
+        }
     }
+    Ok(())
+}
 
-    /// Runs until `age` reaches `runtime`.
-    pub fn run_till_done(&mut self) {
-        while self.age <= self.runtime {
-            self.info();
-            self.age += 1;
+#[cfg(feature = "render")]
+fn render(
+    input: &Path,
+    output: &Path,
+    size: u32,
+    fps: u32,
+    distance: DistanceUnit,
+) -> Result<(), Box<dyn Error>> {
+    use taxi_simulation::render::{render, RenderOptions};
 
-            self.maybe_spawn_request();
-            self.distribute_unfulfilled_requests();
-            self.update_requests();
-            self.cleanup_requests();
-        }
-    }
+    let options = RenderOptions {
+        size,
+        frames_per_second: fps,
+        distance,
+    };
+    Ok(render(input, output, options)?)
 }
 
-impl fmt::Display for World {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let num_occupied_taxis = self.taxis.iter().filter(|t| t.is_occupied).count();
-        let num_total_taxis = self.taxis.len();
-        let num_assigned_requests = self
-            .active_requests
-            .iter()
-            .filter(|r| r.assigned_taxi.is_some())
-            .count();
-        let num_waiting_requests = self
-            .active_requests
-            .iter()
-            .filter(|r| r.assigned_taxi.is_none())
-            .count();
-        let num_archived_requests = self.archived_requests.len();
-        write!(
-            f,
-            "Age: {}/{}, Taxis: {} Occ/{} Tot, Requests: {} Asnd/{} Wai/{} Arch",
-            self.age,
-            self.runtime,
-            num_occupied_taxis,
-            num_total_taxis,
-            num_assigned_requests,
-            num_waiting_requests,
-            num_archived_requests,
-        )
-    }
+#[cfg(not(feature = "render"))]
+fn render(
+    _input: &Path,
+    _output: &Path,
+    _size: u32,
+    _fps: u32,
+    _distance: DistanceUnit,
+) -> Result<(), Box<dyn Error>> {
+    Err("rendering needs the `render` feature".into())
 }
 
-fn main() {
-    let mut world = World::new(86400, 0.2, 2000, 200);
-    world.run_till_done();
+/// CSV files are taken as results of earlier runs, everything else is run as a scenario.
+fn load_results(
+    path: &Path,
+    replications: u32,
+    workers: usize,
+) -> Result<RunResults, Box<dyn Error>> {
+    if path.extension().is_some_and(|e| e == "csv") {
+        Ok(RunResults::read_csv(path)?)
+    } else {
+        let scenario = Scenario::load(path)?;
+        Ok(RunResults::from_summaries(&run_replications(
+            &scenario,
+            replications,
+            workers,
+        )?))
+    }
 }
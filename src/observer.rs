@@ -0,0 +1,59 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::events::Event;
+use crate::world::{Request, World};
+
+/// Something which wants to follow a `World` as it runs, such as an exporter.
+///
+/// All methods do nothing by default so that implementors only need to care about what
+/// they're interested in.
+pub trait Observer: fmt::Debug {
+    /// Called at the end of every tick.
+    fn on_tick(&mut self, _world: &World) {}
+
+    /// Called whenever a `Request` is archived because it was canceled or fulfilled.
+    fn on_archived(&mut self, _world: &World, _request: &Request) {}
+
+    /// Called right after something happened to a `Request` within a tick.
+    fn on_event(&mut self, _world: &World, _event: &Event) {}
+
+    /// Called once the run is over or whenever everything buffered should be written out.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// An `Observer` which writes a line about the state of the `World` after every tick, using its
+/// `Display` implementation.
+#[derive(Debug)]
+pub struct StatusLog<W: Write + fmt::Debug> {
+    writer: W,
+
+    /// See `RequestExporter::error`.
+    error: Option<io::Error>,
+}
+
+impl<W: Write + fmt::Debug> StatusLog<W> {
+    pub fn new(writer: W) -> StatusLog<W> {
+        StatusLog {
+            writer,
+            error: None,
+        }
+    }
+}
+
+impl<W: Write + fmt::Debug> Observer for StatusLog<W> {
+    fn on_tick(&mut self, world: &World) {
+        if self.error.is_none() {
+            self.error = writeln!(self.writer, "{}", world).err();
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush(),
+        }
+    }
+}
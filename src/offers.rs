@@ -0,0 +1,167 @@
+//! Dispatch offering rides to drivers rather than assigning them outright.
+//!
+//! Dispatch picks a `Taxi` for a `Request` as usual, but then offers the ride to its driver,
+//! who accepts or declines after a while, depending on their `DriverProfile`. Drivers who don't
+//! answer within the `window` time out. The ride is offered to the next best `Taxi` then,
+//! leaving out those which already declined it. Once `max_offers` drivers declined, the
+//! `escalation` decides what happens to the `Request`.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::distribution::Distribution;
+
+/// The offer protocol, as configured in the `[offers]` section of a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Offers {
+    /// Seconds a driver has to answer an offer.
+    pub window: u64,
+
+    /// How many drivers a ride is offered to before escalating.
+    pub max_offers: u32,
+
+    #[serde(default)]
+    pub escalation: Escalation,
+
+    /// Every driver gets one of these at random, weighted by their `share`.
+    pub profiles: Vec<DriverProfile>,
+}
+
+/// What happens to a `Request` which `max_offers` drivers declined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Escalation {
+    /// Dispatch assigns the next best `Taxi` without asking its driver.
+    #[default]
+    Assign,
+
+    /// The `Request` is rejected, like one no `Taxi` is within `max_pickup` of.
+    Reject,
+}
+
+/// How drivers of one kind answer offers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DriverProfile {
+    pub share: f64,
+
+    /// Chance of accepting an offer they answer in time.
+    pub acceptance: f64,
+
+    /// Seconds until they answer.
+    pub response_time: Distribution,
+}
+
+impl Offers {
+    /// Why the parameters don't make up a protocol, if they don't.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+        check(self.window > 0, "offers.window must be at least 1");
+        check(self.max_offers > 0, "offers.max_offers must be at least 1");
+        check(
+            self.profiles.iter().any(|p| p.share > 0.0)
+                && self
+                    .profiles
+                    .iter()
+                    .all(|p| p.share >= 0.0 && p.share.is_finite()),
+            "offers.profiles need non-negative shares and at least one positive one",
+        );
+        for (i, profile) in self.profiles.iter().enumerate() {
+            check(
+                (0.0..=1.0).contains(&profile.acceptance),
+                &format!("offers.profiles[{}].acceptance must be between 0 and 1", i),
+            );
+            if let Err(problem) = profile.response_time.validate() {
+                check(
+                    false,
+                    &format!("offers.profiles[{}].response_time: {}", i, problem),
+                );
+            }
+        }
+        problems
+    }
+}
+
+/// An offer of a `Request` to a driver which they haven't answered yet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Offer {
+    pub taxi: Uuid,
+
+    /// Tick the driver answers at, or the offer times out at.
+    pub due: u64,
+
+    pub accepted: bool,
+
+    /// Whether the driver doesn't answer within the window.
+    pub times_out: bool,
+}
+
+impl DriverProfile {
+    /// Decides how the driver of `taxi` answers an offer made at tick `now`.
+    pub fn answer<R: Rng + ?Sized>(&self, rng: &mut R, taxi: Uuid, now: u64, window: u64) -> Offer {
+        let response_time = self.response_time.sample(rng).round().max(0.0) as u64;
+        let times_out = response_time > window;
+        Offer {
+            taxi,
+            due: now + response_time.min(window),
+            accepted: !times_out && rng.gen_bool(self.acceptance),
+            times_out,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    fn driver(acceptance: f64, response_time: f64) -> DriverProfile {
+        DriverProfile {
+            share: 1.0,
+            acceptance,
+            response_time: Distribution::Constant {
+                value: response_time,
+            },
+        }
+    }
+
+    #[test]
+    fn drivers_answer_after_their_response_time() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let offer = driver(1.0, 4.0).answer(&mut rng, Uuid::nil(), 100, 10);
+        assert_eq!(offer.due, 104);
+        assert!(offer.accepted);
+        assert!(!offer.times_out);
+    }
+
+    #[test]
+    fn drivers_who_answer_too_late_time_out_at_the_end_of_the_window() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let offer = driver(1.0, 11.0).answer(&mut rng, Uuid::nil(), 100, 10);
+        assert_eq!(offer.due, 110);
+        assert!(!offer.accepted);
+        assert!(offer.times_out);
+
+        // Answering right as the window closes still counts.
+        let offer = driver(1.0, 10.0).answer(&mut rng, Uuid::nil(), 100, 10);
+        assert_eq!(offer.due, 110);
+        assert!(offer.accepted);
+    }
+
+    #[test]
+    fn drivers_who_never_accept_decline_in_time() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let offer = driver(0.0, 2.0).answer(&mut rng, Uuid::nil(), 0, 10);
+        assert!(!offer.accepted);
+        assert!(!offer.times_out);
+    }
+}
@@ -0,0 +1,328 @@
+//! Searching for the fleet size which best serves a `Scenario`.
+//!
+//! Every candidate size runs the same replications, with the seeds of the original
+//! `Scenario`, and candidates are compared by the mean of a metric over them. A `Target` is
+//! looked for by bisection, which assumes that it only gets easier to meet with more `Taxi`s.
+//! A metric is maximized by ternary search, which assumes that it has a single peak, like
+//! `fleet_profit` does as more `Taxi`s first serve more riders and then only cost more.
+
+use std::fmt;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::compare::RunResults;
+use crate::experiment::{for_each_replication, replication_seeds};
+use crate::scenario::Scenario;
+use crate::world::World;
+
+/// A bound on the mean of a metric, e.g. `mean_wait<=120` or `fulfillment_rate>=0.95`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub metric: String,
+    pub bound: f64,
+
+    /// Whether the metric has to stay at or below `bound` rather than reach it.
+    pub at_most: bool,
+}
+
+impl Target {
+    pub fn is_met_by(&self, value: f64) -> bool {
+        if self.at_most {
+            value <= self.bound
+        } else {
+            value >= self.bound
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Target, String> {
+        let (metric, bound, at_most) = match (s.split_once("<="), s.split_once(">=")) {
+            (Some((metric, bound)), None) => (metric, bound, true),
+            (None, Some((metric, bound))) => (metric, bound, false),
+            _ => return Err(format!("{:?} isn't like mean_wait<=120", s)),
+        };
+        let bound = bound
+            .trim()
+            .parse()
+            .map_err(|_| format!("{:?} isn't a number", bound.trim()))?;
+        Ok(Target {
+            metric: metric.trim().to_string(),
+            bound,
+            at_most,
+        })
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relation = if self.at_most { "<=" } else { ">=" };
+        write!(f, "{}{}{}", self.metric, relation, self.bound)
+    }
+}
+
+/// What a fleet size is searched for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Goal {
+    /// The smallest fleet which meets the `Target`.
+    Meet(Target),
+
+    /// The fleet with the highest mean of the metric of this name.
+    Maximize(String),
+}
+
+impl Goal {
+    fn metric(&self) -> &str {
+        match self {
+            Goal::Meet(target) => &target.metric,
+            Goal::Maximize(metric) => metric,
+        }
+    }
+}
+
+/// One candidate fleet size the search ran.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub taxis: u32,
+
+    /// Mean of the metric of the `Goal` over the replications.
+    pub value: f64,
+
+    /// Whether the `value` meets the `Target`, if there is one.
+    pub meets_target: Option<bool>,
+}
+
+/// The steps a search took, in order, and what it found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Search {
+    pub goal: Goal,
+    pub trace: Vec<Step>,
+
+    /// `None` if not even the largest fleet meets the `Target`.
+    pub best: Option<Step>,
+}
+
+/// `scenario` with a fleet of `taxis`, split over its vehicle types in proportion to their
+/// counts, and evenly if they're all 0.
+pub fn with_fleet_size(scenario: &Scenario, taxis: u32) -> Scenario {
+    let mut scenario = scenario.clone();
+    if scenario.vehicle_types.is_empty() {
+        scenario.number_of_taxis = taxis;
+        return scenario;
+    }
+    let total: u32 = scenario.vehicle_types.iter().map(|t| t.count).sum();
+    let shares: Vec<f64> = scenario
+        .vehicle_types
+        .iter()
+        .map(|t| {
+            if total == 0 {
+                1.0 / scenario.vehicle_types.len() as f64
+            } else {
+                f64::from(t.count) / f64::from(total)
+            }
+        })
+        .collect();
+    // Largest remainders, so that the counts add up to `taxis`.
+    let exact: Vec<f64> = shares.iter().map(|s| s * f64::from(taxis)).collect();
+    let mut counts: Vec<u32> = exact.iter().map(|e| e.floor() as u32).collect();
+    let mut by_remainder: Vec<usize> = (0..exact.len()).collect();
+    by_remainder.sort_by(|&a, &b| {
+        (exact[b] - exact[b].floor())
+            .partial_cmp(&(exact[a] - exact[a].floor()))
+            .expect("Remainders are never NaN.")
+    });
+    let missing = taxis - counts.iter().sum::<u32>();
+    for &i in by_remainder.iter().take(missing as usize) {
+        counts[i] += 1;
+    }
+    for (vehicle_type, count) in scenario.vehicle_types.iter_mut().zip(counts) {
+        vehicle_type.count = count;
+    }
+    scenario
+}
+
+/// Runs candidate fleet sizes and remembers their results, so that no size runs twice.
+struct Searcher<'a, F: FnMut(&Step)> {
+    scenario: &'a Scenario,
+    goal: &'a Goal,
+    seeds: Vec<u64>,
+    workers: usize,
+    trace: Vec<Step>,
+    on_step: F,
+}
+
+impl<F: FnMut(&Step)> Searcher<'_, F> {
+    fn value(&mut self, taxis: u32) -> Result<f64, String> {
+        if let Some(step) = self.step(taxis) {
+            return Ok(step.value);
+        }
+        let scenario = with_fleet_size(self.scenario, taxis);
+        let mut summaries = vec![];
+        for_each_replication(
+            &self.seeds,
+            self.workers,
+            |seed| {
+                let mut world = World::try_new(&scenario, seed)?;
+                world.run_till_done();
+                Ok(world.summary())
+            },
+            |_, summary: io::Result<_>| summaries.push(summary),
+        );
+        let summaries = summaries
+            .into_iter()
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(|e| e.to_string())?;
+        let metric = self.goal.metric();
+        let samples = RunResults::from_summaries(&summaries)
+            .samples(metric)
+            .ok_or_else(|| format!("there's no metric called {:?}", metric))?;
+        let value = samples.iter().sum::<f64>() / samples.len().max(1) as f64;
+
+        let step = Step {
+            taxis,
+            value,
+            meets_target: match self.goal {
+                Goal::Meet(target) => Some(target.is_met_by(value)),
+                Goal::Maximize(_) => None,
+            },
+        };
+        (self.on_step)(&step);
+        self.trace.push(step);
+        Ok(value)
+    }
+
+    fn step(&self, taxis: u32) -> Option<Step> {
+        self.trace.iter().find(|s| s.taxis == taxis).cloned()
+    }
+}
+
+/// Searches the fleet sizes in `taxis` for the `goal`, running `replications` of each on up to
+/// `workers` threads at once. `on_step` is called with every candidate as soon as it ran.
+pub fn optimize(
+    scenario: &Scenario,
+    goal: Goal,
+    taxis: RangeInclusive<u32>,
+    replications: u32,
+    workers: usize,
+    on_step: impl FnMut(&Step),
+) -> Result<Search, String> {
+    if taxis.is_empty() {
+        return Err("the smallest fleet size is larger than the largest".to_string());
+    }
+    if replications == 0 {
+        return Err("every fleet size needs at least 1 replication".to_string());
+    }
+    // Before a whole set of replications ran, so that a typo is caught right away.
+    let unrun = World::try_new(scenario, 0).map_err(|e| e.to_string())?;
+    if !unrun
+        .summary()
+        .metrics()
+        .iter()
+        .any(|&(name, _)| name == goal.metric())
+    {
+        return Err(format!("there's no metric called {:?}", goal.metric()));
+    }
+    let mut searcher = Searcher {
+        scenario,
+        goal: &goal,
+        seeds: replication_seeds(scenario, replications),
+        workers,
+        trace: vec![],
+        on_step,
+    };
+    let (mut lo, mut hi) = taxis.into_inner();
+    let best = match &goal {
+        Goal::Meet(target) => {
+            if !target.is_met_by(searcher.value(hi)?) {
+                None
+            } else {
+                while lo < hi {
+                    let mid = lo + (hi - lo) / 2;
+                    if target.is_met_by(searcher.value(mid)?) {
+                        hi = mid;
+                    } else {
+                        lo = mid + 1;
+                    }
+                }
+                searcher.step(hi)
+            }
+        }
+        Goal::Maximize(_) => {
+            while hi - lo > 2 {
+                let m1 = lo + (hi - lo) / 3;
+                let m2 = hi - (hi - lo) / 3;
+                if searcher.value(m1)? < searcher.value(m2)? {
+                    lo = m1 + 1;
+                } else {
+                    hi = m2 - 1;
+                }
+            }
+            for taxis in lo..=hi {
+                searcher.value(taxis)?;
+            }
+            searcher
+                .trace
+                .iter()
+                .max_by(|a, b| a.value.total_cmp(&b.value))
+                .cloned()
+        }
+    };
+    Ok(Search {
+        trace: searcher.trace,
+        goal,
+        best,
+    })
+}
+
+impl Search {
+    /// Writes the `trace` to a CSV file with one row per candidate, in the order they ran.
+    pub fn write_trace(&self, path: &Path) -> Result<(), csv::Error> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(["taxis", self.goal.metric(), "meets_target"])?;
+        for step in &self.trace {
+            writer.write_record([
+                step.taxis.to_string(),
+                step.value.to_string(),
+                step.meets_target.map_or(String::new(), |m| m.to_string()),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<24} {:>12.4}", self.taxis, self.value)?;
+        match self.meets_target {
+            Some(true) => write!(f, " {:>12}", "met"),
+            Some(false) => write!(f, " {:>12}", "missed"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Search {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.goal, &self.best) {
+            (Goal::Meet(target), Some(best)) => write!(
+                f,
+                "{} taxis are the fewest which meet {} with {:.4}.",
+                best.taxis, target, best.value
+            ),
+            (Goal::Meet(target), None) => {
+                write!(f, "Not even the largest fleet meets {}.", target)
+            }
+            (Goal::Maximize(metric), Some(best)) => write!(
+                f,
+                "{} taxis maximize {} with {:.4}.",
+                best.taxis, metric, best.value
+            ),
+            (Goal::Maximize(_), None) => unreachable!("There's always a candidate to maximize."),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A point in the city in kilometers. The city is a square from `(0, 0)` to
+/// `(city_size, city_size)`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Position {
+    pub fn new(x: f64, y: f64) -> Position {
+        Position { x, y }
+    }
+
+    /// A uniformly random point in a city of `city_size`.
+    pub fn random<R: Rng>(rng: &mut R, city_size: f64) -> Position {
+        Position::new(rng.gen::<f64>() * city_size, rng.gen::<f64>() * city_size)
+    }
+
+    /// A point `distance` km away from this one in a random direction which still lies in a
+    /// city of `city_size`. Only gives up and moves back inside the city if no such direction
+    /// comes up after a few tries, e.g. because the city is too small for the distance.
+    pub fn random_at_distance<R: Rng>(
+        &self,
+        rng: &mut R,
+        distance: f64,
+        city_size: f64,
+    ) -> Position {
+        let mut candidate = *self;
+        for _ in 0..8 {
+            let angle = rng.gen::<f64>() * std::f64::consts::TAU;
+            candidate = Position::new(
+                self.x + distance * angle.cos(),
+                self.y + distance * angle.sin(),
+            );
+            if candidate.is_within(city_size) {
+                return candidate;
+            }
+        }
+        candidate.clamped(city_size)
+    }
+
+    /// A uniformly random point at most `radius` km away from this one, moved back inside a
+    /// city of `city_size` if need be.
+    pub fn random_within<R: Rng>(&self, rng: &mut R, radius: f64, city_size: f64) -> Position {
+        let angle = rng.gen::<f64>() * std::f64::consts::TAU;
+        let distance = radius * rng.gen::<f64>().sqrt();
+        Position::new(
+            self.x + distance * angle.cos(),
+            self.y + distance * angle.sin(),
+        )
+        .clamped(city_size)
+    }
+
+    pub fn distance(&self, other: Position) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+
+    /// The point at most `distance` km closer to `target`, straight in its direction.
+    pub fn towards(&self, target: Position, distance: f64) -> Position {
+        let remaining = self.distance(target);
+        if remaining <= distance {
+            return target;
+        }
+        let share = distance / remaining;
+        Position::new(
+            self.x + (target.x - self.x) * share,
+            self.y + (target.y - self.y) * share,
+        )
+    }
+
+    fn is_within(&self, city_size: f64) -> bool {
+        (0.0..=city_size).contains(&self.x) && (0.0..=city_size).contains(&self.y)
+    }
+
+    fn clamped(&self, city_size: f64) -> Position {
+        Position::new(self.x.clamp(0.0, city_size), self.y.clamp(0.0, city_size))
+    }
+}
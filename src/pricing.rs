@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+
+use crate::time::{ticks_as_hours, ticks_as_minutes};
+use crate::vehicle::VehicleType;
+
+/// How trips are priced, as configured in the `[pricing]` section of a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Pricing {
+    /// Flat amount every trip costs.
+    pub base_fare: f64,
+
+    /// Amount per kilometer driven with the rider.
+    pub per_km: f64,
+
+    /// Amount per minute driven with the rider.
+    pub per_minute: f64,
+
+    /// Amount per stop on the way to the dropoff.
+    pub per_stop: f64,
+
+    /// Raises prices while `Request`s wait for too few idle `Taxi`s. Prices never surge if this
+    /// is missing.
+    pub surge: Option<Surge>,
+}
+
+impl Default for Pricing {
+    fn default() -> Pricing {
+        Pricing {
+            base_fare: 3.0,
+            per_km: 1.5,
+            per_minute: 0.3,
+            per_stop: 0.0,
+            surge: None,
+        }
+    }
+}
+
+/// The surge multiplier grows with the ratio of waiting `Request`s to idle `Taxi`s:
+/// `1 + sensitivity * (waiting / idle - 1)`, but never below 1 or above `cap`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Surge {
+    pub sensitivity: f64,
+    pub cap: f64,
+}
+
+impl Pricing {
+    /// What a trip of `kilometers` taking `ticks` with `stops` on the way costs at a surge
+    /// `multiplier`.
+    pub fn fare(&self, kilometers: f64, ticks: u64, stops: usize, multiplier: f64) -> f64 {
+        let minutes = ticks_as_minutes(ticks as f64);
+        (self.base_fare
+            + self.per_km * kilometers
+            + self.per_minute * minutes
+            + self.per_stop * stops as f64)
+            * multiplier
+    }
+
+    /// The surge multiplier for `waiting_requests` competing for `idle_taxis`.
+    pub fn surge_multiplier(&self, waiting_requests: usize, idle_taxis: usize) -> f64 {
+        let surge = match &self.surge {
+            Some(surge) => surge,
+            None => return 1.0,
+        };
+        if waiting_requests == 0 {
+            return 1.0;
+        }
+        if idle_taxis == 0 {
+            return surge.cap.max(1.0);
+        }
+        let ratio = waiting_requests as f64 / idle_taxis as f64;
+        (1.0 + surge.sensitivity * (ratio - 1.0)).clamp(1.0, surge.cap.max(1.0))
+    }
+}
+
+/// What running the fleet costs, as configured in the `[costs]` section of a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Costs {
+    /// Fuel or energy per kilometer driven, with or without a rider.
+    pub per_km: f64,
+
+    /// Fixed amount per `Taxi` and shift, e.g. the driver's wage.
+    pub per_shift: f64,
+
+    /// Hours per shift. Fixed costs are charged pro rata for the time a `Taxi` is in the
+    /// `World`.
+    pub shift_length: f64,
+}
+
+impl Default for Costs {
+    fn default() -> Costs {
+        Costs {
+            per_km: 0.15,
+            per_shift: 100.0,
+            shift_length: 8.0,
+        }
+    }
+}
+
+impl Costs {
+    /// These costs with the per-kilometer costs of `vehicle_type`, if it has its own.
+    pub fn for_vehicle(&self, vehicle_type: &VehicleType) -> Costs {
+        Costs {
+            per_km: vehicle_type.cost_per_km.unwrap_or(self.per_km),
+            ..self.clone()
+        }
+    }
+
+    /// What a `Taxi` which drove `kilometers` in `ticks` costs.
+    pub fn of(&self, kilometers: f64, ticks: u64) -> f64 {
+        let shifts = if self.shift_length > 0.0 {
+            ticks_as_hours(ticks as f64) / self.shift_length
+        } else {
+            0.0
+        };
+        self.per_km * kilometers + self.per_shift * shifts
+    }
+}
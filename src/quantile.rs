@@ -0,0 +1,253 @@
+//! Estimators for quantiles of a stream of values.
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Roughly how many centroids a `TDigest` keeps. Higher is more accurate.
+const DEFAULT_COMPRESSION: f64 = 100.0;
+
+/// How many values a `TDigest` collects before merging them into its centroids.
+const BUFFER_SIZE: usize = 500;
+
+/// How `Statistics` keep track of distributions such as waiting times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatisticsMode {
+    /// Keep every value around and compute exact quantiles. Memory grows with the run.
+    Exact,
+
+    /// Estimate quantiles with a t-digest in bounded memory.
+    Streaming,
+}
+
+/// Tracks a fixed set of quantiles of all values pushed into it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum QuantileEstimator {
+    Exact {
+        quantiles: Vec<f64>,
+        values: Vec<f64>,
+    },
+    Streaming {
+        quantiles: Vec<f64>,
+        digest: TDigest,
+    },
+}
+
+impl QuantileEstimator {
+    pub fn new(mode: StatisticsMode, quantiles: &[f64]) -> QuantileEstimator {
+        match mode {
+            StatisticsMode::Exact => QuantileEstimator::Exact {
+                quantiles: quantiles.to_vec(),
+                values: vec![],
+            },
+            StatisticsMode::Streaming => QuantileEstimator::Streaming {
+                quantiles: quantiles.to_vec(),
+                digest: TDigest::new(DEFAULT_COMPRESSION),
+            },
+        }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        match self {
+            QuantileEstimator::Exact { values, .. } => values.push(value),
+            QuantileEstimator::Streaming { digest, .. } => digest.push(value),
+        }
+    }
+
+    /// The current estimates in the order the quantiles were given in. Zero while empty.
+    pub fn estimates(&self) -> Vec<f64> {
+        match self {
+            QuantileEstimator::Exact { quantiles, values } => {
+                let mut sorted = values.clone();
+                sorted.sort_by(|a, b| a.partial_cmp(b).expect("Values can't be NaN."));
+                quantiles
+                    .iter()
+                    .map(|&p| exact_quantile(&sorted, p))
+                    .collect()
+            }
+            QuantileEstimator::Streaming { quantiles, digest } => {
+                let mut digest = digest.clone();
+                digest.compress();
+                quantiles.iter().map(|&p| digest.quantile(p)).collect()
+            }
+        }
+    }
+}
+
+/// Linearly interpolated quantile `p` of already sorted `values`.
+fn exact_quantile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = p * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// A merging t-digest by Dunning and Ertl.
+///
+/// Values are summarized as weighted centroids which are kept small near the extremes and may
+/// grow large around the median, so the tails stay accurate. Unlike simpler estimators this
+/// copes well with many identical values, such as a lot of zero waiting times.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TDigest {
+    compression: f64,
+
+    /// `(mean, weight)` pairs sorted by mean.
+    centroids: Vec<(f64, f64)>,
+
+    /// Values which weren't merged into `centroids` yet.
+    buffer: Vec<f64>,
+
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    pub fn new(compression: f64) -> TDigest {
+        TDigest {
+            compression,
+            centroids: vec![],
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.buffer.push(value);
+        if self.buffer.len() >= BUFFER_SIZE {
+            self.compress();
+        }
+    }
+
+    /// Merges all buffered values into the centroids.
+    pub fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let mut all: Vec<(f64, f64)> = self.centroids.drain(..).collect();
+        all.extend(self.buffer.drain(..).map(|v| (v, 1.0)));
+        all.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("Values can't be NaN."));
+
+        let total: f64 = all.iter().map(|c| c.1).sum();
+        let mut merged = Vec::with_capacity(all.len());
+        let mut current = all[0];
+        let mut weight_before = 0.0;
+        for &next in &all[1..] {
+            let q_left = weight_before / total;
+            let q_right = (weight_before + current.1 + next.1) / total;
+            if self.scale(q_right) - self.scale(q_left) <= 1.0 {
+                let weight = current.1 + next.1;
+                current = (current.0 + (next.0 - current.0) * next.1 / weight, weight);
+            } else {
+                weight_before += current.1;
+                merged.push(current);
+                current = next;
+            }
+        }
+        merged.push(current);
+        self.centroids = merged;
+    }
+
+    /// The k₁ scale function which bounds how much weight a centroid at quantile `q` may have.
+    fn scale(&self, q: f64) -> f64 {
+        self.compression / (2.0 * PI) * (2.0 * q.min(1.0) - 1.0).asin()
+    }
+
+    /// Estimated quantile `p`, assuming all buffered values were merged. Zero while empty.
+    pub fn quantile(&self, p: f64) -> f64 {
+        if self.centroids.is_empty() {
+            return 0.0;
+        }
+        if self.centroids.len() == 1 {
+            return self.centroids[0].0;
+        }
+
+        // Every centroid is taken to sit at the middle of the weight it covers. In between,
+        // and towards the observed extremes, values are interpolated linearly.
+        let total: f64 = self.centroids.iter().map(|c| c.1).sum();
+        let target = p * total;
+        let first = self.centroids[0];
+        if target < first.1 / 2.0 {
+            return self.min + (first.0 - self.min) * target / (first.1 / 2.0);
+        }
+
+        let mut weight_before = 0.0;
+        for pair in self.centroids.windows(2) {
+            let (left, right) = (pair[0], pair[1]);
+            let left_center = weight_before + left.1 / 2.0;
+            let right_center = weight_before + left.1 + right.1 / 2.0;
+            if target < right_center {
+                let t = (target - left_center) / (right_center - left_center);
+                return left.0 + (right.0 - left.0) * t;
+            }
+            weight_before += left.1;
+        }
+
+        let last = self.centroids[self.centroids.len() - 1];
+        let last_center = total - last.1 / 2.0;
+        let t = ((target - last_center) / (last.1 / 2.0)).min(1.0);
+        last.0 + (self.max - last.0) * t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUANTILES: [f64; 4] = [0.1, 0.5, 0.9, 0.99];
+
+    /// 0 to 9999 in a scrambled order, since 7919 is coprime to 10000.
+    fn scrambled() -> impl Iterator<Item = f64> {
+        (0..10_000u64).map(|i| (i * 7919 % 10_000) as f64)
+    }
+
+    fn estimates(mode: StatisticsMode, values: impl Iterator<Item = f64>) -> Vec<f64> {
+        let mut estimator = QuantileEstimator::new(mode, &QUANTILES);
+        for value in values {
+            estimator.push(value);
+        }
+        estimator.estimates()
+    }
+
+    #[test]
+    fn exact_quantiles_interpolate_between_ranks() {
+        let estimates = estimates(StatisticsMode::Exact, scrambled());
+        for (estimate, expected) in estimates.iter().zip(&[999.9, 4999.5, 8999.1, 9899.01]) {
+            assert!((estimate - expected).abs() < 1e-9, "{}", estimate);
+        }
+    }
+
+    #[test]
+    fn streaming_quantiles_are_close_to_the_exact_ones() {
+        let exact = estimates(StatisticsMode::Exact, scrambled());
+        let streaming = estimates(StatisticsMode::Streaming, scrambled());
+        for (p, (e, s)) in QUANTILES.iter().zip(exact.iter().zip(&streaming)) {
+            // Within 0.05% of the range.
+            assert!((e - s).abs() <= 5.0, "p{}: {} vs. {}", p, s, e);
+        }
+    }
+
+    #[test]
+    fn streaming_quantiles_of_identical_values_are_exact() {
+        let streaming = estimates(StatisticsMode::Streaming, std::iter::repeat_n(0.0, 5000));
+        assert_eq!(streaming, vec![0.0; QUANTILES.len()]);
+    }
+
+    #[test]
+    fn quantiles_are_zero_while_empty() {
+        for mode in [StatisticsMode::Exact, StatisticsMode::Streaming] {
+            assert_eq!(
+                estimates(mode, std::iter::empty()),
+                vec![0.0; QUANTILES.len()]
+            );
+        }
+    }
+}
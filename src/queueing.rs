@@ -0,0 +1,328 @@
+//! Analytical M/M/c predictions to sanity check the simulation against.
+//!
+//! Every tick spawns a `Request` with `request_spawn_chance`, which approximates Poisson arrivals
+//! with rate `λ = request_spawn_chance` per tick. With an `arrivals` process, `λ` is its mean
+//! rate instead, and the mean rates of demand layers with arrival processes add to it. With
+//! exponential trip durations the `Taxi`s then form `c` servers with rate
+//! `μ = 1 / mean trip duration` each.
+
+use std::fmt;
+
+use crate::distribution::Distribution;
+use crate::rider_choice::RiderChoice;
+use crate::scenario::Scenario;
+use crate::stats::Summary;
+
+/// Above this spawn chance Bernoulli arrivals are noticeably less bursty than Poisson ones.
+const MAX_POISSON_LIKE_SPAWN_CHANCE: f64 = 0.1;
+
+/// Above this probability of waiting longer than `max_waiting_time`, cancellations distort the
+/// queue too much for the model, which assumes riders wait forever.
+const MAX_ABANDONMENT_PROBABILITY: f64 = 0.01;
+
+/// Simulated values deviating more than this from the prediction are flagged.
+const RELATIVE_TOLERANCE: f64 = 0.1;
+
+/// Waiting times are whole ticks in the simulation, so tiny absolute deviations are noise.
+const ABSOLUTE_WAIT_TOLERANCE: f64 = 1.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MmcPrediction {
+    /// Share of `Taxi`s which are occupied on average, `ρ = λ / (c μ)`.
+    pub utilization: f64,
+
+    /// Probability that an arriving `Request` has to wait at all (Erlang C).
+    pub probability_of_waiting: f64,
+
+    /// Expected ticks a `Request` waits until a `Taxi` is assigned.
+    pub expected_wait: f64,
+
+    /// Assumptions of the model which the `Scenario` violates. The prediction is less
+    /// trustworthy the more of these there are.
+    pub caveats: Vec<String>,
+}
+
+impl MmcPrediction {
+    /// `Err` with the reason if the `Scenario` is too far from an M/M/c queue to say anything.
+    pub fn for_scenario(scenario: &Scenario) -> Result<MmcPrediction, String> {
+        let mean_trip_duration = match scenario.trip_duration {
+            Distribution::Exponential { mean } => mean,
+            _ => return Err("trip durations aren't exponentially distributed".to_string()),
+        };
+        let fleet_size = scenario.fleet_size();
+        if fleet_size == 0 {
+            return Err("there are no taxis".to_string());
+        }
+
+        let background_rate = match &scenario.arrivals {
+            Some(arrivals) => arrivals.mean_rate(),
+            None => scenario.request_spawn_chance,
+        };
+        let layer_rate: f64 = scenario.demand.iter().filter_map(|l| l.mean_rate()).sum();
+        let arrival_rate = background_rate + layer_rate;
+        let service_rate = 1.0 / mean_trip_duration;
+        let servers = f64::from(fleet_size);
+        let offered_load = arrival_rate / service_rate;
+        let utilization = offered_load / servers;
+        if utilization >= 1.0 {
+            return Err(format!(
+                "the queue is unstable with a utilization of {:.3}",
+                utilization
+            ));
+        }
+
+        let probability_of_waiting = erlang_c(fleet_size, offered_load);
+        let drain_rate = servers * service_rate - arrival_rate;
+        let expected_wait = probability_of_waiting / drain_rate;
+
+        let mut caveats = vec![];
+        if let Some(arrivals) = &scenario.arrivals {
+            if !arrivals.is_homogeneous_poisson() {
+                caveats.push(
+                    "arrivals with a varying rate are burstier than Poisson ones".to_string(),
+                );
+            }
+        } else if arrival_rate > MAX_POISSON_LIKE_SPAWN_CHANCE {
+            caveats.push(format!(
+                "request_spawn_chance above {} makes arrivals less Poisson-like",
+                MAX_POISSON_LIKE_SPAWN_CHANCE
+            ));
+        }
+        let abandonment_probability =
+            probability_of_waiting * (-drain_rate * scenario.max_waiting_time as f64).exp();
+        if abandonment_probability > MAX_ABANDONMENT_PROBABILITY {
+            caveats.push(format!(
+                "{:.1}% of requests are expected to cancel, which the model ignores",
+                abandonment_probability * 100.0
+            ));
+        }
+        if scenario.max_active_requests <= fleet_size {
+            caveats.push("max_active_requests keeps taxis from ever being all busy".to_string());
+        }
+        if scenario.willingness_to_pay.is_some() {
+            caveats.push("riders who are priced out thin out arrivals".to_string());
+        }
+        if scenario.eta_cancellation.is_some() {
+            caveats.push("riders who cancel because of the ETA thin out arrivals".to_string());
+        }
+        if scenario
+            .rider_choice
+            .as_ref()
+            .is_some_and(RiderChoice::turns_riders_away)
+        {
+            caveats.push("riders who turn down their quote thin out arrivals".to_string());
+        }
+        if scenario.no_show.is_some() {
+            caveats.push("taxis waiting for no-shows are busy without a trip".to_string());
+        }
+        if scenario.venues.iter().any(|v| !v.bursts.is_empty()) {
+            caveats.push("bursts of riders at venues aren't Poisson arrivals".to_string());
+        }
+        if scenario.demand.iter().any(|l| l.mean_rate().is_none()) {
+            caveats.push(
+                "bursts and replayed trips of demand layers aren't Poisson arrivals".to_string(),
+            );
+        }
+        if scenario.max_pickup.is_some() {
+            caveats.push("rejecting requests beyond max_pickup turns riders away".to_string());
+        }
+        if scenario.matching_interval > 1 {
+            caveats.push("batch matching delays assignments until the next round".to_string());
+        }
+        if !scenario.incidents.is_empty() {
+            caveats.push("incidents disrupt the steady state for a while".to_string());
+        }
+        if scenario.transit.is_some() {
+            caveats.push("riders who switch to transit thin out arrivals".to_string());
+        }
+        if scenario.offers.is_some() {
+            caveats.push("waiting for drivers to answer offers delays assignments".to_string());
+        }
+        if scenario.ab_test.is_some() {
+            caveats.push("an A/B split serves riders by two smaller fleets".to_string());
+        }
+        if scenario.maintenance.is_some() {
+            caveats.push("maintenance stops shrink the effective fleet".to_string());
+        }
+        if scenario.city_size > 0.0 {
+            caveats
+                .push("driving to pickups keeps taxis busy for longer than the trips".to_string());
+        }
+        if scenario.stop_time.is_some() && scenario.stop_count_weights.len() > 1 {
+            caveats.push("waiting at stops keeps taxis busy for longer than the trips".to_string());
+        }
+        if scenario.boarding_time.is_some() || scenario.alighting_time.is_some() {
+            caveats.push(
+                "boarding and alighting keep taxis busy for longer than the trips".to_string(),
+            );
+        }
+        let fleet = scenario.fleet();
+        let largest_capacity = fleet.iter().map(|t| t.capacity).max().unwrap_or(0);
+        if scenario.party_size_weights.len() > largest_capacity as usize {
+            caveats.push("some parties are too large to fit into any taxi".to_string());
+        }
+        if fleet.len() > 1 {
+            caveats.push(
+                "vehicle types with their own capacities and speeds aren't identical servers"
+                    .to_string(),
+            );
+        }
+        if !scenario.vehicle_preferences.is_empty() {
+            caveats
+                .push("riders who only accept some vehicle types can't use every taxi".to_string());
+        }
+
+        Ok(MmcPrediction {
+            utilization,
+            probability_of_waiting,
+            expected_wait,
+            caveats,
+        })
+    }
+
+    /// Puts the prediction next to the averages of simulated `summaries`.
+    pub fn check(&self, summaries: &[Summary]) -> BaselineCheck {
+        let n = summaries.len().max(1) as f64;
+        let simulated_utilization = summaries.iter().map(|s| s.taxi_utilization).sum::<f64>() / n;
+        let simulated_wait = summaries.iter().map(|s| s.mean_wait).sum::<f64>() / n;
+
+        BaselineCheck {
+            rows: vec![
+                BaselineRow::new(
+                    "taxi_utilization",
+                    simulated_utilization,
+                    self.utilization,
+                    0.0,
+                ),
+                BaselineRow::new(
+                    "mean_wait",
+                    simulated_wait,
+                    self.expected_wait,
+                    ABSOLUTE_WAIT_TOLERANCE,
+                ),
+            ],
+            caveats: self.caveats.clone(),
+        }
+    }
+}
+
+/// Probability of having to wait in an M/M/c queue with `servers` and `offered_load = λ / μ`.
+///
+/// Computed via the Erlang B recursion, which unlike the textbook formula doesn't overflow for
+/// large fleets.
+fn erlang_c(servers: u32, offered_load: f64) -> f64 {
+    let mut erlang_b = 1.0;
+    for k in 1..=servers {
+        erlang_b = offered_load * erlang_b / (f64::from(k) + offered_load * erlang_b);
+    }
+    let utilization = offered_load / f64::from(servers);
+    erlang_b / (1.0 - utilization * (1.0 - erlang_b))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaselineRow {
+    pub name: &'static str,
+    pub simulated: f64,
+    pub predicted: f64,
+
+    /// Whether `simulated` is further off `predicted` than can be explained by noise.
+    pub deviates: bool,
+}
+
+impl BaselineRow {
+    fn new(name: &'static str, simulated: f64, predicted: f64, absolute_tolerance: f64) -> Self {
+        let tolerance = (predicted.abs() * RELATIVE_TOLERANCE).max(absolute_tolerance);
+        BaselineRow {
+            name,
+            simulated,
+            predicted,
+            deviates: (simulated - predicted).abs() > tolerance,
+        }
+    }
+}
+
+/// Simulated results next to the M/M/c prediction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaselineCheck {
+    pub rows: Vec<BaselineRow>,
+    pub caveats: Vec<String>,
+}
+
+impl BaselineCheck {
+    pub fn deviates(&self) -> bool {
+        self.rows.iter().any(|r| r.deviates)
+    }
+}
+
+impl fmt::Display for BaselineCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:>12} {:>12}",
+            "M/M/c baseline", "simulated", "predicted"
+        )?;
+        for r in &self.rows {
+            writeln!(
+                f,
+                "{:<24} {:>12.4} {:>12.4}{}",
+                r.name,
+                r.simulated,
+                r.predicted,
+                if r.deviates { " DEVIATES" } else { "" }
+            )?;
+        }
+        for caveat in &self.caveats {
+            writeln!(f, "caveat: {}", caveat)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} isn't {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn erlang_c_of_a_single_server_is_its_utilization() {
+        for &load in &[0.0, 0.3, 0.9] {
+            assert_close(erlang_c(1, load), load);
+        }
+    }
+
+    #[test]
+    fn erlang_c_matches_the_textbook_formula() {
+        assert_close(erlang_c(2, 1.0), 1.0 / 3.0);
+        assert_close(erlang_c(10, 8.0), 0.409_180_150_796_443_5);
+        // Far past where the factorials of the textbook formula overflow.
+        assert_close(erlang_c(500, 480.0), 0.266_512_519_962_364_1);
+    }
+
+    fn scenario(number_of_taxis: u32) -> Scenario {
+        Scenario {
+            request_spawn_chance: 0.2,
+            trip_duration: Distribution::Exponential { mean: 100.0 },
+            number_of_taxis,
+            ..Scenario::default()
+        }
+    }
+
+    #[test]
+    fn for_scenario_rejects_unstable_queues() {
+        // λ / μ = 20 taxis' worth of trips.
+        for taxis in [20, 10] {
+            let error = MmcPrediction::for_scenario(&scenario(taxis)).unwrap_err();
+            assert!(error.starts_with("the queue is unstable"), "{}", error);
+        }
+        let prediction = MmcPrediction::for_scenario(&scenario(25)).unwrap();
+        assert_close(prediction.utilization, 0.8);
+    }
+}
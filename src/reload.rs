@@ -0,0 +1,172 @@
+//! Reloading the scenario file while a `World` runs, e.g. to try out other prices on a served run.
+//!
+//! Only `RELOADABLE` parameters are safe to change mid-run. Changes to any other parameter are
+//! reported and ignored, since they'd need a fresh `World`. Every applied change is emitted as
+//! an `Event::ParameterChanged` like those made through `/control`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::scenario::{Scenario, ScenarioError};
+use crate::world::World;
+
+/// Parameters of a `Scenario` which a running `World` picks up from the next tick on.
+pub const RELOADABLE: [&str; 2] = ["request_spawn_chance", "pricing"];
+
+/// How often the scenario file is checked for changes at most.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Watches a scenario file for changes by its modification time.
+#[derive(Debug)]
+pub struct ScenarioWatcher {
+    path: PathBuf,
+
+    /// The parameters the `World` runs with, which only differ from the file on disk in
+    /// parameters which can't be reloaded.
+    scenario: Scenario,
+    modified: Option<SystemTime>,
+    checked: Instant,
+}
+
+/// What changed in a scenario file since the last time it was read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reload {
+    scenario: Scenario,
+
+    /// Which of `RELOADABLE` changed.
+    pub changed: Vec<&'static str>,
+
+    /// Top-level parameters which changed but can't be reloaded.
+    pub ignored: Vec<String>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl ScenarioWatcher {
+    /// Watches `path`, which `scenario` was loaded from.
+    pub fn new(path: &Path, scenario: Scenario) -> ScenarioWatcher {
+        ScenarioWatcher {
+            path: path.to_path_buf(),
+            scenario,
+            modified: modified(path),
+            checked: Instant::now(),
+        }
+    }
+
+    /// Reads the scenario file again if it changed since the last call, which checks at most
+    /// every `POLL_INTERVAL`. Invalid scenarios aren't reloaded at all, but are read again
+    /// once they change again.
+    pub fn poll(&mut self) -> Option<Result<Reload, ScenarioError>> {
+        if self.checked.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+        self.checked = Instant::now();
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+
+        let new = match Scenario::load(&self.path) {
+            Ok(new) => new,
+            Err(e) => return Some(Err(e)),
+        };
+        let reload = Reload::between(&self.scenario, new);
+        self.scenario = reload.scenario.clone();
+        Some(Ok(reload))
+    }
+}
+
+impl Reload {
+    /// The changes from `old` to `new`, and `old` with only the reloadable changes applied.
+    pub fn between(old: &Scenario, new: Scenario) -> Reload {
+        let mut scenario = old.clone();
+        let mut changed = vec![];
+        if new.request_spawn_chance != old.request_spawn_chance {
+            scenario.request_spawn_chance = new.request_spawn_chance;
+            changed.push("request_spawn_chance");
+        }
+        if new.pricing != old.pricing {
+            scenario.pricing = new.pricing.clone();
+            changed.push("pricing");
+        }
+
+        let table = |s: &Scenario| {
+            toml::Table::try_from(s).expect("Scenarios can always be written as TOML.")
+        };
+        let (old, new) = (table(old), table(&new));
+        let mut ignored: Vec<String> = old
+            .keys()
+            .chain(new.keys())
+            .filter(|&k| !RELOADABLE.contains(&k.as_str()) && old.get(k) != new.get(k))
+            .cloned()
+            .collect();
+        ignored.sort();
+        ignored.dedup();
+        Reload {
+            scenario,
+            changed,
+            ignored,
+        }
+    }
+
+    /// Applies the `changed` parameters to `world`. Returns the reasons for any which can't be
+    /// applied to it.
+    pub fn apply(&self, world: &mut World) -> Vec<String> {
+        let mut problems = vec![];
+        for &parameter in &self.changed {
+            match parameter {
+                "request_spawn_chance" => {
+                    if !world.set_request_spawn_chance(self.scenario.request_spawn_chance) {
+                        problems.push(
+                            "request_spawn_chance can't be changed with an arrival process"
+                                .to_string(),
+                        );
+                    }
+                }
+                "pricing" => world.set_pricing(self.scenario.pricing.clone()),
+                _ => unreachable!("Only reloadable parameters are changed."),
+            }
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::NoShow;
+
+    #[test]
+    fn unchanged_scenarios_reload_nothing() {
+        let reload = Reload::between(&Scenario::default(), Scenario::default());
+        assert!(reload.changed.is_empty());
+        assert!(reload.ignored.is_empty());
+    }
+
+    #[test]
+    fn only_reloadable_changes_are_applied() {
+        let old = Scenario::default();
+        let mut new = old.clone();
+        new.request_spawn_chance /= 2.0;
+        new.pricing.base_fare += 1.0;
+        new.number_of_taxis += 1;
+        new.no_show = Some(NoShow {
+            probability: 0.1,
+            grace_period: 3.0,
+        });
+        let reload = Reload::between(&old, new.clone());
+        assert_eq!(reload.changed, ["request_spawn_chance", "pricing"]);
+        assert_eq!(reload.ignored, ["no_show", "number_of_taxis"]);
+        assert_eq!(
+            reload.scenario.request_spawn_chance,
+            new.request_spawn_chance
+        );
+        assert_eq!(reload.scenario.pricing, new.pricing);
+        assert_eq!(reload.scenario.number_of_taxis, old.number_of_taxis);
+        assert_eq!(reload.scenario.no_show, None);
+    }
+}
@@ -0,0 +1,320 @@
+//! Animations of event logs written by `EventLog`.
+//!
+//! Every `TaxiPositions` line of the log becomes one frame showing where the `Taxi`s are and
+//! which `Request`s are waiting to be picked up. Canceled `Request`s stay visible in red for a
+//! little while. GIFs are drawn directly, everything else is encoded by piping raw frames to
+//! `ffmpeg`, which has to be installed for that. A scale bar in the bottom left corner is
+//! split into segments of one km or one mile each, whichever the `distance` unit is.
+
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use uuid::Uuid;
+
+use crate::events::{read_log, Event, LogLine};
+use crate::position::Position;
+use crate::units::DistanceUnit;
+use crate::world::{RequestOutcome, TaxiState};
+
+/// How many frames a canceled `Request` stays visible.
+const CANCELED_FRAMES: u64 = 5;
+
+const MARGIN: u32 = 10;
+
+/// Height of the progress bar at the bottom of every frame.
+const PROGRESS_BAR_HEIGHT: u32 = 4;
+
+/// Most segments of the scale bar, which never spans more than a quarter of the frame either.
+const MAX_SCALE_SEGMENTS: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderOptions {
+    /// Width and height of the animation in pixels.
+    pub size: u32,
+    pub frames_per_second: u32,
+
+    /// What a segment of the scale bar is long.
+    pub distance: DistanceUnit,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            size: 800,
+            frames_per_second: 30,
+            distance: DistanceUnit::Km,
+        }
+    }
+}
+
+/// Renders the event log at `input` to an animation at `output`. It's a GIF if `output` ends
+/// in `.gif` and whatever `ffmpeg` makes of the extension otherwise.
+pub fn render(input: &Path, output: &Path, options: RenderOptions) -> io::Result<()> {
+    let bounds = Bounds::of_log(input)?;
+    let size = (options.size, options.size);
+    let mut state = State::default();
+
+    if output.extension().is_some_and(|e| e == "gif") {
+        let delay = 1000 / options.frames_per_second.max(1);
+        let root = BitMapBackend::gif(output, size, delay)
+            .map_err(io::Error::other)?
+            .into_drawing_area();
+        for line in read_log(input)? {
+            if state.apply(line?) {
+                state.draw(&root, &bounds, options.distance)?;
+                root.present().map_err(io::Error::other)?;
+            }
+        }
+        Ok(())
+    } else {
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args([
+                "-y",
+                "-loglevel",
+                "error",
+                "-f",
+                "rawvideo",
+                "-pix_fmt",
+                "rgb24",
+            ])
+            .args(["-s", &format!("{}x{}", size.0, size.1)])
+            .args(["-r", &options.frames_per_second.to_string()])
+            .args(["-i", "-", "-pix_fmt", "yuv420p"])
+            .arg(output)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("couldn't start ffmpeg: {}", e)))?;
+        let mut stdin = ffmpeg
+            .stdin
+            .take()
+            .expect("ffmpeg was started with piped stdin.");
+        let mut frame = vec![0; size.0 as usize * size.1 as usize * 3];
+        for line in read_log(input)? {
+            if state.apply(line?) {
+                {
+                    let root = BitMapBackend::with_buffer(&mut frame, size).into_drawing_area();
+                    state.draw(&root, &bounds, options.distance)?;
+                    root.present().map_err(io::Error::other)?;
+                }
+                stdin.write_all(&frame)?;
+            }
+        }
+        drop(stdin);
+        let status = ffmpeg.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("ffmpeg failed with {}", status)));
+        }
+        Ok(())
+    }
+}
+
+/// Smallest rectangle containing everything in the log, and how long the log goes on for.
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min: Position,
+    max: Position,
+    last_tick: u64,
+}
+
+impl Bounds {
+    fn of_log(path: &Path) -> io::Result<Bounds> {
+        let mut bounds = Bounds {
+            min: Position::new(f64::INFINITY, f64::INFINITY),
+            max: Position::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
+            last_tick: 0,
+        };
+        for line in read_log(path)? {
+            let line = line?;
+            bounds.last_tick = bounds.last_tick.max(line.tick);
+            match line.event {
+                Event::RequestSpawned {
+                    pickup, dropoff, ..
+                } => {
+                    bounds.include(pickup);
+                    bounds.include(dropoff);
+                }
+                Event::TaxiPositions { taxis } => {
+                    for t in taxis {
+                        bounds.include(t.position);
+                    }
+                }
+                _ => (),
+            }
+        }
+        if bounds.min.x > bounds.max.x {
+            bounds.min = Position::default();
+            bounds.max = Position::default();
+        }
+        Ok(bounds)
+    }
+
+    fn include(&mut self, position: Position) {
+        self.min = Position::new(self.min.x.min(position.x), self.min.y.min(position.y));
+        self.max = Position::new(self.max.x.max(position.x), self.max.y.max(position.y));
+    }
+
+    /// How many pixels a km is long in a square frame of `size` pixels.
+    fn scale(&self, size: u32) -> f64 {
+        let span = (self.max.x - self.min.x).max(self.max.y - self.min.y);
+        let drawable = f64::from(size.saturating_sub(2 * MARGIN + PROGRESS_BAR_HEIGHT));
+        if span > 0.0 {
+            drawable / span
+        } else {
+            0.0
+        }
+    }
+
+    /// Where `position` ends up in a square frame of `size` pixels.
+    fn pixel(&self, position: Position, size: u32) -> (i32, i32) {
+        let drawable = f64::from(size.saturating_sub(2 * MARGIN + PROGRESS_BAR_HEIGHT));
+        let scale = self.scale(size);
+        let x = (position.x - self.min.x) * scale;
+        let y = (position.y - self.min.y) * scale;
+        (
+            MARGIN as i32 + x as i32,
+            MARGIN as i32 + (drawable - y) as i32,
+        )
+    }
+}
+
+/// What the log says the `World` looks like at the latest line read so far.
+#[derive(Debug, Default)]
+struct State {
+    tick: u64,
+
+    /// Pickups of `Request`s which haven't been picked up, and whether a `Taxi` is on the way.
+    waiting: HashMap<Uuid, (Position, bool)>,
+
+    /// Pickups of canceled `Request`s with the frame they were canceled in.
+    canceled: Vec<(Position, u64)>,
+
+    taxis: Vec<(Position, TaxiState)>,
+    frames: u64,
+
+    /// Pickups are only in the `RequestSpawned` lines, so they're kept around for cancellations.
+    spawned: HashMap<Uuid, Position>,
+}
+
+impl State {
+    /// Applies `line` and returns whether a new frame is due.
+    fn apply(&mut self, line: LogLine) -> bool {
+        self.tick = line.tick;
+        match line.event {
+            Event::RequestSpawned {
+                request, pickup, ..
+            } => {
+                self.waiting.insert(request, (pickup, false));
+                self.spawned.insert(request, pickup);
+            }
+            Event::RequestAssigned { request, .. } => {
+                if let Some((_, assigned)) = self.waiting.get_mut(&request) {
+                    *assigned = true;
+                }
+            }
+            Event::RequestPickedUp { request, .. } => {
+                self.waiting.remove(&request);
+            }
+            Event::DispatchExplained { .. }
+            | Event::ParameterChanged { .. }
+            | Event::RideOffered { .. }
+            | Event::RideDeclined { .. }
+            | Event::IncidentStarted { .. }
+            | Event::IncidentEnded { .. } => (),
+            Event::RequestArchived { request, outcome } => {
+                self.waiting.remove(&request);
+                let pickup = self.spawned.remove(&request);
+                if let (RequestOutcome::Canceled, Some(pickup)) = (outcome, pickup) {
+                    self.canceled.push((pickup, self.frames));
+                }
+            }
+            Event::TaxiPositions { taxis } => {
+                self.taxis = taxis.into_iter().map(|t| (t.position, t.state)).collect();
+                self.frames += 1;
+                let frames = self.frames;
+                self.canceled
+                    .retain(|&(_, frame)| frames - frame <= CANCELED_FRAMES);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn draw<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        bounds: &Bounds,
+        distance: DistanceUnit,
+    ) -> io::Result<()>
+    where
+        DB::ErrorType: 'static,
+    {
+        let size = root.dim_in_pixel().0;
+        let point = |position, radius, color: RGBColor| {
+            Circle::new(bounds.pixel(position, size), radius, color.filled())
+        };
+
+        root.fill(&WHITE).map_err(io::Error::other)?;
+        for &(pickup, assigned) in self.waiting.values() {
+            let color = if assigned {
+                RGBColor(255, 165, 0)
+            } else {
+                RGBColor(230, 200, 0)
+            };
+            root.draw(&point(pickup, 2, color))
+                .map_err(io::Error::other)?;
+        }
+        for &(pickup, _) in &self.canceled {
+            root.draw(&point(pickup, 3, RED))
+                .map_err(io::Error::other)?;
+        }
+        for &(position, state) in &self.taxis {
+            let color = match state {
+                TaxiState::Idle => RGBColor(0, 150, 0),
+                TaxiState::Occupied => BLUE,
+                TaxiState::InMaintenance { .. } | TaxiState::LoggedOff { .. } => {
+                    RGBColor(150, 150, 150)
+                }
+            };
+            root.draw(&point(position, 3, color))
+                .map_err(io::Error::other)?;
+        }
+
+        let height = root.dim_in_pixel().1 as i32;
+        let segment = bounds.scale(size) * distance.km();
+        if segment >= 1.0 {
+            let segments = ((f64::from(size) / 4.0 / segment) as u32).min(MAX_SCALE_SEGMENTS);
+            let bottom = height - PROGRESS_BAR_HEIGHT as i32 - MARGIN as i32 / 2;
+            for i in 0..segments {
+                let color = if i % 2 == 0 {
+                    BLACK
+                } else {
+                    RGBColor(150, 150, 150)
+                };
+                let left = MARGIN as i32 + (f64::from(i) * segment) as i32;
+                let right = MARGIN as i32 + (f64::from(i + 1) * segment) as i32;
+                root.draw(&Rectangle::new(
+                    [(left, bottom - 3), (right, bottom)],
+                    color.filled(),
+                ))
+                .map_err(io::Error::other)?;
+            }
+        }
+
+        let progress = if bounds.last_tick == 0 {
+            1.0
+        } else {
+            self.tick as f64 / bounds.last_tick as f64
+        };
+        root.draw(&Rectangle::new(
+            [
+                (0, height - PROGRESS_BAR_HEIGHT as i32),
+                ((f64::from(size) * progress) as i32, height),
+            ],
+            BLACK.filled(),
+        ))
+        .map_err(io::Error::other)
+    }
+}
@@ -0,0 +1,182 @@
+//! How riders decide whether to ask for a `Taxi`, whether to take the one they're offered and
+//! when to stop waiting for one.
+//!
+//! A `RiderChoiceModel` makes all three decisions, so that a `World` can be run with riders
+//! who behave differently without touching dispatch. The built-in models are configured in
+//! the `[rider_choice]` section of a `Scenario`, and any other model can be set on a `World`
+//! through `World::set_rider_choice`.
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::distribution::Distribution;
+use crate::eta::EtaCancellation;
+use crate::time::{minutes_as_ticks, ticks_as_minutes};
+use crate::world::Request;
+
+/// What a rider is told before they submit their `Request`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quote {
+    pub fare: f64,
+
+    /// Ticks the ride takes at `taxi_speed`, not counting the pickup.
+    pub trip_duration: u64,
+    pub trip_distance: f64,
+    pub party_size: u32,
+}
+
+/// Decides what riders do. Riders who are still waiting once `max_waiting_time` is up cancel
+/// whatever the model says.
+pub trait RiderChoiceModel: fmt::Debug {
+    /// Whether a rider who is quoted `quote` goes on to submit their `Request`.
+    fn requests(&mut self, rng: &mut dyn RngCore, quote: &Quote) -> bool;
+
+    /// Whether the rider of `request` takes the `Taxi` which is `eta` ticks away from the
+    /// pickup.
+    fn accepts(&mut self, rng: &mut dyn RngCore, request: &Request, eta: f64) -> bool;
+
+    /// Whether the rider of `request`, who's still waiting for a `Taxi`, gives up in this tick.
+    fn cancels(&mut self, _rng: &mut dyn RngCore, _request: &Request) -> bool {
+        false
+    }
+}
+
+/// The built-in `RiderChoiceModel`s, as configured in the `[rider_choice]` section of a
+/// `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RiderChoice {
+    /// Every rider requests a `Taxi` and takes it, however far away it is.
+    AlwaysAccept,
+
+    /// Riders request a `Taxi` unless the fare is above their `willingness_to_pay`, and may
+    /// cancel when they're quoted the ETA, like with the fields of the same names of a
+    /// `Scenario`. Either may be missing.
+    Threshold {
+        willingness_to_pay: Option<Distribution>,
+        eta_cancellation: Option<EtaCancellation>,
+    },
+
+    /// Every rider asks for a quote, and takes the `Taxi` with a chance of
+    /// `1 / (1 + exp(-u))` for the utility `u = intercept + price * fare + eta * minutes`, with
+    /// the ETA in minutes. Both `price` and `eta` are usually negative. Riders who are still
+    /// waiting give up after `patience` minutes on average, if they ever do before
+    /// `max_waiting_time`.
+    Logit {
+        intercept: f64,
+        price: f64,
+        eta: f64,
+        patience: Option<f64>,
+    },
+}
+
+impl RiderChoice {
+    /// Why the parameters don't make up a model, if they don't.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+        match self {
+            RiderChoice::AlwaysAccept => (),
+            RiderChoice::Threshold {
+                willingness_to_pay,
+                eta_cancellation,
+            } => {
+                if let Some(Err(problem)) = willingness_to_pay.as_ref().map(Distribution::validate)
+                {
+                    check(
+                        false,
+                        &format!("rider_choice.willingness_to_pay: {}", problem),
+                    );
+                }
+                check(
+                    !eta_cancellation.as_ref().is_some_and(|c| c.patience <= 0.0),
+                    "rider_choice.eta_cancellation.patience must be positive",
+                );
+            }
+            RiderChoice::Logit {
+                intercept,
+                price,
+                eta,
+                patience,
+            } => {
+                check(
+                    [intercept, price, eta].iter().all(|c| c.is_finite()),
+                    "rider_choice coefficients must be finite",
+                );
+                check(
+                    !patience.is_some_and(|p| p <= 0.0 || !p.is_finite()),
+                    "rider_choice.patience must be positive",
+                );
+            }
+        }
+        problems
+    }
+
+    /// Whether some riders never request a `Taxi` or turn down the one they're offered.
+    pub fn turns_riders_away(&self) -> bool {
+        match self {
+            RiderChoice::AlwaysAccept => false,
+            RiderChoice::Threshold {
+                willingness_to_pay,
+                eta_cancellation,
+            } => willingness_to_pay.is_some() || eta_cancellation.is_some(),
+            RiderChoice::Logit { .. } => true,
+        }
+    }
+}
+
+impl RiderChoiceModel for RiderChoice {
+    fn requests(&mut self, rng: &mut dyn RngCore, quote: &Quote) -> bool {
+        match self {
+            RiderChoice::Threshold {
+                willingness_to_pay: Some(willingness_to_pay),
+                ..
+            } => willingness_to_pay.sample(rng) >= quote.fare,
+            _ => true,
+        }
+    }
+
+    fn accepts(&mut self, rng: &mut dyn RngCore, request: &Request, eta: f64) -> bool {
+        match self {
+            RiderChoice::AlwaysAccept => true,
+            RiderChoice::Threshold {
+                eta_cancellation, ..
+            } => !eta_cancellation
+                .as_ref()
+                .is_some_and(|c| c.cancels(rng, eta)),
+            RiderChoice::Logit {
+                intercept,
+                price,
+                eta: per_minute,
+                ..
+            } => {
+                // Terms without weight are left out, so that an infinite ETA counts for nothing
+                // rather than making the utility NaN.
+                let utility = [
+                    (*price, request.fare()),
+                    (*per_minute, ticks_as_minutes(eta)),
+                ]
+                .iter()
+                .filter(|(weight, _)| *weight != 0.0)
+                .fold(*intercept, |u, (weight, x)| u + weight * x);
+                let probability = 1.0 / (1.0 + (-utility).exp());
+                rng.gen_bool(probability.clamp(0.0, 1.0))
+            }
+        }
+    }
+
+    fn cancels(&mut self, rng: &mut dyn RngCore, _request: &Request) -> bool {
+        match self {
+            RiderChoice::Logit {
+                patience: Some(patience),
+                ..
+            } => rng.gen_bool((1.0 / minutes_as_ticks(*patience)).min(1.0)),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Speed limits on the roads in parts of the city, e.g. in residential areas or along highways.
+//!
+//! `Taxi`s still drive in a straight line, but slow down to the limit of whatever part of the
+//! city they're passing through. Where no limit applies, they drive at their top speed.
+
+use serde::{Deserialize, Serialize};
+
+use crate::position::Position;
+use crate::time::per_tick;
+
+/// A rectangle of the city spanned by `from` and `to` in which no `Taxi` drives faster than
+/// `speed` km/h, as configured in the `[[speed_limits]]` sections of a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpeedLimit {
+    pub name: String,
+    pub from: Position,
+    pub to: Position,
+    pub speed: f64,
+}
+
+/// All `SpeedLimit`s of a city, ready to work out travel times.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Roads {
+    /// Lower and upper corners of every area with its limit in km per tick.
+    limits: Vec<(Position, Position, f64)>,
+}
+
+impl Roads {
+    pub fn new(limits: &[SpeedLimit]) -> Roads {
+        Roads {
+            limits: limits
+                .iter()
+                .map(|l| {
+                    let lower = Position::new(l.from.x.min(l.to.x), l.from.y.min(l.to.y));
+                    let upper = Position::new(l.from.x.max(l.to.x), l.from.y.max(l.to.y));
+                    (lower, upper, per_tick(l.speed))
+                })
+                .collect(),
+        }
+    }
+
+    /// The lowest speed limit at `position` in km per tick, if any applies. Limits overlap
+    /// on their borders.
+    pub fn limit_at(&self, position: Position) -> Option<f64> {
+        self.limits
+            .iter()
+            .filter(|(lower, upper, _)| {
+                (lower.x..=upper.x).contains(&position.x)
+                    && (lower.y..=upper.y).contains(&position.y)
+            })
+            .map(|&(_, _, limit)| limit)
+            .min_by(|a, b| a.partial_cmp(b).expect("Speed limits are never NaN."))
+    }
+
+    /// Ticks it takes to drive straight from `from` to `to` at `top_speed` km per tick, or at
+    /// the speed limit where that's lower, both times `congestion`.
+    pub fn cruise_time(
+        &self,
+        from: Position,
+        to: Position,
+        top_speed: f64,
+        congestion: f64,
+    ) -> f64 {
+        let distance = from.distance(to);
+        if distance == 0.0 {
+            return 0.0;
+        }
+        if self.limits.is_empty() {
+            let speed = top_speed * congestion;
+            return if speed > 0.0 {
+                distance / speed
+            } else {
+                f64::INFINITY
+            };
+        }
+
+        // Shares of the way at which the straight line enters or leaves a limited area.
+        let mut breaks = vec![0.0, 1.0];
+        for (lower, upper, _) in &self.limits {
+            if let Some((enter, leave)) = clip(from, to, *lower, *upper) {
+                breaks.push(enter);
+                breaks.push(leave);
+            }
+        }
+        breaks.sort_by(|a, b| a.partial_cmp(b).expect("Shares are never NaN."));
+
+        let at = |share: f64| {
+            Position::new(
+                from.x + (to.x - from.x) * share,
+                from.y + (to.y - from.y) * share,
+            )
+        };
+        let mut ticks = 0.0;
+        for pair in breaks.windows(2) {
+            let length = (pair[1] - pair[0]) * distance;
+            if length <= 0.0 {
+                continue;
+            }
+            let limit = self.limit_at(at((pair[0] + pair[1]) / 2.0));
+            let speed = limit.map_or(top_speed, |l| l.min(top_speed)) * congestion;
+            if speed <= 0.0 {
+                return f64::INFINITY;
+            }
+            ticks += length / speed;
+        }
+        ticks
+    }
+}
+
+/// Shares of the way from `from` to `to` between which the straight line runs through the
+/// rectangle from `lower` to `upper`, if it does at all.
+fn clip(from: Position, to: Position, lower: Position, upper: Position) -> Option<(f64, f64)> {
+    let (mut enter, mut leave) = (0.0_f64, 1.0_f64);
+    for (start, delta, low, high) in [
+        (from.x, to.x - from.x, lower.x, upper.x),
+        (from.y, to.y - from.y, lower.y, upper.y),
+    ] {
+        if delta == 0.0 {
+            if start < low || start > high {
+                return None;
+            }
+            continue;
+        }
+        let (a, b) = ((low - start) / delta, (high - start) / delta);
+        enter = enter.max(a.min(b));
+        leave = leave.min(a.max(b));
+    }
+    if enter < leave {
+        Some((enter, leave))
+    } else {
+        None
+    }
+}
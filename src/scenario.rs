@@ -0,0 +1,745 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::ab_test::AbTest;
+use crate::archive::ArchivePolicy;
+use crate::arrivals::Arrivals;
+use crate::demand::DemandLayer;
+use crate::distribution::Distribution;
+use crate::eta::EtaCancellation;
+use crate::histogram::HistogramBuckets;
+use crate::incidents::Incident;
+use crate::offers::Offers;
+use crate::pricing::{Costs, Pricing};
+use crate::quantile::StatisticsMode;
+use crate::rider_choice::RiderChoice;
+use crate::roads::SpeedLimit;
+use crate::time::Start;
+use crate::transit::Transit;
+use crate::travel_times::TravelTimes;
+use crate::units::Units;
+use crate::vehicle::{VehiclePreference, VehicleType, MAX_VEHICLE_TYPES};
+use crate::venue::Venue;
+use crate::zones::{DispatchWeights, MaxPickup};
+
+/// Everything needed to set up a `World`. Usually loaded from a TOML file where every key is
+/// optional and falls back to the value from `Scenario::default()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    /// How long the `World` updates for in ticks/seconds.
+    pub runtime: u64,
+
+    /// The weekday and time of day the run starts at. Hourly profiles such as the
+    /// `speed_profile` start at midnight on Monday, so they follow the time of day from
+    /// wherever the run starts. Runs start at midnight on Monday if this is missing.
+    pub start: Option<Start>,
+
+    /// Chance to spawn a request per tick.
+    pub request_spawn_chance: f64,
+
+    /// How many `Request`s arrive per tick instead, if they should arrive in bursts or at a rate
+    /// which varies over the day. See `Arrivals`. Replaces `request_spawn_chance`.
+    pub arrivals: Option<Arrivals>,
+
+    /// When the number of active requests reaches this number, no further requests will be
+    /// allowed to spawn.
+    pub max_active_requests: u32,
+
+    /// How many `Taxi`s the `World` starts out with. Ignored if there are `vehicle_types`.
+    pub number_of_taxis: u32,
+
+    /// How long a `Request` waits for a `Taxi` before it is canceled.
+    pub max_waiting_time: u64,
+
+    /// How long a ride takes once the rider is picked up. The dropoff is however far away a
+    /// `Taxi` gets in that time.
+    pub trip_duration: Distribution,
+
+    /// Relative frequencies of party sizes, starting at a party of one.
+    pub party_size_weights: Vec<f64>,
+
+    /// How many passengers fit into each `Taxi`. Ignored if there are `vehicle_types`.
+    pub taxi_capacity: u32,
+
+    /// How many ticks riders take to get in at the pickup. Sampled for every `Request`, and
+    /// the `Taxi` can't do anything else in the meantime. Boarding is instant if this is
+    /// missing.
+    pub boarding_time: Option<Distribution>,
+
+    /// How many ticks riders take to get out at the dropoff, like `boarding_time`.
+    pub alighting_time: Option<Distribution>,
+
+    /// Relative frequencies of the number of stops on the way to the dropoff, starting at a
+    /// direct ride. The sampled `trip_duration` is split over all legs of the ride.
+    pub stop_count_weights: Vec<f64>,
+
+    /// How many ticks the `Taxi` waits at each stop on the way, like `boarding_time`.
+    pub stop_time: Option<Distribution>,
+
+    /// How fast `Taxi`s drive in km/h. Also the speed the sampled trip durations assume, so
+    /// slower vehicle types take longer for the same trip.
+    pub taxi_speed: f64,
+
+    /// How fast `Taxi`s speed up and brake in m/s². Instantly if this is missing. Ignored if
+    /// there are `vehicle_types`.
+    pub taxi_acceleration: Option<f64>,
+
+    /// The kinds of vehicles in the fleet. If this is empty, the fleet consists of
+    /// `number_of_taxis` vehicles with `taxi_capacity` seats driving at `taxi_speed`.
+    pub vehicle_types: Vec<VehicleType>,
+
+    /// Which vehicle types riders accept, picked by weight for every `Request`. Everybody
+    /// accepts every type if this is empty.
+    pub vehicle_preferences: Vec<VehiclePreference>,
+
+    /// Factors on `taxi_speed` for each hour, starting at midnight on Monday, to model
+    /// congestion on the way to pickups. Hours past the end of the list start over at its
+    /// beginning. There's no congestion if this is empty.
+    pub speed_profile: Vec<f64>,
+
+    /// Areas of the city in which `Taxi`s can't drive as fast as they otherwise would. Where
+    /// several overlap, the lowest limit applies.
+    pub speed_limits: Vec<SpeedLimit>,
+
+    /// Looks up ETAs in a grid of travel times worked out up front, rather than working them
+    /// out along the speed limits for every candidate `Taxi`.
+    pub travel_times: Option<TravelTimes>,
+
+    /// Side length of the square city in km. `Taxi`s start out and `Request`s spawn at
+    /// uniformly random places in it. With a size of 0 everything happens at the same place,
+    /// so `Taxi`s never have to drive to a pickup.
+    pub city_size: f64,
+
+    /// Airports, stadiums and the like which release bursts of riders at scheduled times, on
+    /// top of the `Request`s spawning all over the city.
+    pub venues: Vec<Venue>,
+
+    /// Further demand in independent layers, e.g. a Poisson process in one zone, bursts at an
+    /// airport or trips replayed from a file, on top of the `Request`s spawning all over the
+    /// city. Each draws from random numbers of its own and is broken down in the summary.
+    pub demand: Vec<DemandLayer>,
+
+    /// Demand spikes, driver logoffs, road closures and dispatcher latency scheduled during
+    /// the run, to stress dispatch.
+    pub incidents: Vec<Incident>,
+
+    /// The city is split into a grid of this many zones along each side. `Request`s are
+    /// matched to `Taxi`s within their zone first, which can be spread over several threads,
+    /// and only then across zones.
+    pub zones: u32,
+
+    /// Dispatch only runs every this many ticks and matches all `Request`s which accumulated
+    /// in the meantime at once. Riders wait longer for a `Taxi`, see `mean_wait`, but
+    /// `Taxi`s tend to be closer to them, see `mean_pickup_time`. With 1, `Request`s are
+    /// matched one after the other on every tick.
+    pub matching_interval: u64,
+
+    /// What dispatch optimizes for. By default it only minimizes the pickup ETA, see
+    /// `empty_mileage`, `earnings_stddev` and `earnings_gini` for what the other objectives
+    /// trade that off against.
+    pub dispatch_weights: DispatchWeights,
+
+    /// How far dispatch sends a `Taxi` to a pickup at most. `Request`s which no `Taxi` within
+    /// reach takes are rejected right away, rather than waiting for one. `Taxi`s go any
+    /// distance if this is missing.
+    pub max_pickup: Option<MaxPickup>,
+
+    /// Splits the fleet into groups A and B which dispatch differently, and routes every
+    /// `Request` to one of them at random. There's a single group if this is missing.
+    pub ab_test: Option<AbTest>,
+
+    /// Dispatch offers rides to drivers, who may decline or not answer in time, instead of
+    /// assigning them outright. Every ride is assigned right away if this is missing.
+    pub offers: Option<Offers>,
+
+    /// Public transit which riders take instead if it's cheaper for them, counting their time,
+    /// than the `Taxi` they're quoted. Riders always go by taxi if this is missing.
+    pub transit: Option<Transit>,
+
+    /// Distance unit, currency and clock of reports. Distances are in km, amounts of money
+    /// unlabeled and the clock 24h if this is missing, and nothing is labeled with units.
+    pub units: Option<Units>,
+
+    /// Idle `Taxi`s cruise around the city instead of waiting where they dropped off their last
+    /// rider. They stand still if this is missing.
+    pub cruising: Option<Cruising>,
+
+    /// Regular maintenance and refueling stops. `Taxi`s never stop if this is missing.
+    pub maintenance: Option<Maintenance>,
+
+    /// How much trips cost.
+    pub pricing: Pricing,
+
+    /// How much running the fleet costs.
+    pub costs: Costs,
+
+    /// The most a rider is willing to pay for a trip. Riders who are quoted more than that
+    /// never submit their `Request`. Everybody pays whatever it costs if this is missing.
+    pub willingness_to_pay: Option<Distribution>,
+
+    /// Riders who canceled because of the ETA or were rejected may try again later. Nobody
+    /// does if this is missing.
+    pub re_request: Option<ReRequest>,
+
+    /// Riders who aren't at the pickup when their `Taxi` arrives. Everybody shows up if this is
+    /// missing.
+    pub no_show: Option<NoShow>,
+
+    /// Riders may cancel as soon as they're told how long their `Taxi` takes to arrive. Nobody
+    /// cancels because of that if this is missing.
+    pub eta_cancellation: Option<EtaCancellation>,
+
+    /// How riders decide whether to request a `Taxi`, take the one they're offered and keep
+    /// waiting for one. Replaces `willingness_to_pay` and `eta_cancellation`, which make up a
+    /// `threshold` model if this is missing.
+    pub rider_choice: Option<RiderChoice>,
+
+    /// Whether to compute exact statistics or to estimate them in constant memory, which is
+    /// preferable for long runs.
+    pub statistics: StatisticsMode,
+
+    /// Bucket widths of the trip histograms in the summary.
+    pub histograms: HistogramBuckets,
+
+    /// What to do with canceled or fulfilled `Request`s, which can take up a lot of memory on
+    /// long runs.
+    pub archive: ArchivePolicy,
+
+    /// Minutes the rolling metrics of `World::recent` cover, e.g. for dashboards. 15 if this is
+    /// missing.
+    pub rolling_window: Option<u64>,
+
+    /// Seed of the first replication. Further replications use the following seeds. If this is
+    /// missing, the first seed is a hash of the rest of the `Scenario`.
+    pub seed: Option<u64>,
+}
+
+impl Default for Scenario {
+    fn default() -> Scenario {
+        Scenario {
+            runtime: 86400,
+            start: None,
+            request_spawn_chance: 0.2,
+            arrivals: None,
+            max_active_requests: 2000,
+            number_of_taxis: 200,
+            max_waiting_time: 100,
+            trip_duration: Distribution::Constant { value: 100.0 },
+            party_size_weights: vec![1.0],
+            taxi_capacity: 4,
+            boarding_time: None,
+            alighting_time: None,
+            stop_count_weights: vec![1.0],
+            stop_time: None,
+            taxi_speed: 30.0,
+            taxi_acceleration: None,
+            vehicle_types: vec![],
+            vehicle_preferences: vec![],
+            speed_profile: vec![],
+            speed_limits: vec![],
+            travel_times: None,
+            city_size: 10.0,
+            venues: vec![],
+            demand: vec![],
+            incidents: vec![],
+            zones: 1,
+            matching_interval: 1,
+            dispatch_weights: DispatchWeights::default(),
+            max_pickup: None,
+            ab_test: None,
+            offers: None,
+            transit: None,
+            units: None,
+            cruising: None,
+            maintenance: None,
+            pricing: Pricing::default(),
+            costs: Costs::default(),
+            willingness_to_pay: None,
+            eta_cancellation: None,
+            rider_choice: None,
+            no_show: None,
+            re_request: None,
+            statistics: StatisticsMode::Exact,
+            histograms: HistogramBuckets::default(),
+            archive: ArchivePolicy::Unbounded,
+            rolling_window: None,
+            seed: None,
+        }
+    }
+}
+
+/// A `Taxi` which drove `interval` km since its last stop goes off the road for `duration`
+/// minutes once it finishes its current trip.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Maintenance {
+    pub interval: f64,
+    pub duration: f64,
+}
+
+/// An idle `Taxi` drives to a random place at most `range` km away at `speed` times its usual
+/// speed, then picks the next one, until it gets a `Request`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cruising {
+    pub speed: f64,
+    pub range: f64,
+}
+
+/// A rider doesn't show up at the pickup with `probability`. The `Taxi` waits for
+/// `grace_period` minutes before it gives up on them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NoShow {
+    pub probability: f64,
+    pub grace_period: f64,
+}
+
+/// A rider who canceled because of the ETA or was rejected tries again with the same trip with
+/// `probability` after `cooldown` minutes. They may give up and try again any number of times.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReRequest {
+    pub probability: f64,
+    pub cooldown: f64,
+}
+
+impl Scenario {
+    /// Reads a `Scenario` from a TOML file and validates it.
+    pub fn load(path: &Path) -> Result<Scenario, ScenarioError> {
+        let contents = fs::read_to_string(path)?;
+        let scenario: Scenario = toml::from_str(&contents)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// The kinds of vehicles in the fleet, falling back to a single type made of
+    /// `number_of_taxis`, `taxi_capacity` and `taxi_speed`.
+    pub fn fleet(&self) -> Vec<VehicleType> {
+        if !self.vehicle_types.is_empty() {
+            return self.vehicle_types.clone();
+        }
+        vec![VehicleType {
+            name: "taxi".to_string(),
+            count: self.number_of_taxis,
+            capacity: self.taxi_capacity,
+            speed: self.taxi_speed,
+            acceleration: self.taxi_acceleration,
+            cost_per_km: None,
+        }]
+    }
+
+    /// How many `Taxi`s the `World` starts out with, of all types.
+    pub fn fleet_size(&self) -> u32 {
+        self.fleet().iter().map(|t| t.count).sum()
+    }
+
+    /// How riders behave, either as configured in `rider_choice` or as a `threshold` model of
+    /// `willingness_to_pay` and `eta_cancellation`.
+    pub fn rider_choice(&self) -> RiderChoice {
+        match &self.rider_choice {
+            Some(rider_choice) => rider_choice.clone(),
+            None => RiderChoice::Threshold {
+                willingness_to_pay: self.willingness_to_pay.clone(),
+                eta_cancellation: self.eta_cancellation.clone(),
+            },
+        }
+    }
+
+    /// Checks every parameter and lists all problems at once, so that a `World` is never set
+    /// up with parameters it can't work with.
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        let mut problems = vec![];
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+
+        if let Some(start) = &self.start {
+            check(
+                start.hour < 24 && start.minute < 60,
+                "start must be a time of day between 00:00 and 23:59",
+            );
+        }
+        check(
+            (0.0..=1.0).contains(&self.request_spawn_chance),
+            "request_spawn_chance must be between 0 and 1",
+        );
+        if let Some(arrivals) = &self.arrivals {
+            for problem in arrivals.problems() {
+                check(false, &problem);
+            }
+        }
+        check(
+            !self.party_size_weights.is_empty()
+                && self
+                    .party_size_weights
+                    .iter()
+                    .all(|&w| w >= 0.0 && w.is_finite())
+                && self.party_size_weights.iter().any(|&w| w > 0.0),
+            "party_size_weights need at least one positive weight and no negative ones",
+        );
+        check(
+            !self.stop_count_weights.is_empty()
+                && self
+                    .stop_count_weights
+                    .iter()
+                    .all(|&w| w >= 0.0 && w.is_finite())
+                && self.stop_count_weights.iter().any(|&w| w > 0.0),
+            "stop_count_weights need at least one positive weight and no negative ones",
+        );
+        check(self.taxi_capacity > 0, "taxi_capacity must be at least 1");
+        check(
+            self.taxi_speed > 0.0 && self.taxi_speed.is_finite(),
+            "taxi_speed must be positive",
+        );
+        check(
+            !self
+                .taxi_acceleration
+                .is_some_and(|a| a <= 0.0 || !a.is_finite()),
+            "taxi_acceleration must be positive",
+        );
+        check(
+            self.speed_profile.iter().all(|&f| f > 0.0 && f.is_finite()),
+            "speed_profile factors must be positive",
+        );
+        check(
+            self.city_size >= 0.0 && self.city_size.is_finite(),
+            "city_size must not be negative",
+        );
+        check(self.zones > 0, "zones must be at least 1");
+        check(
+            self.matching_interval > 0,
+            "matching_interval must be at least 1",
+        );
+        let valid_weights = |weights: &DispatchWeights| {
+            [
+                weights.wait,
+                weights.empty_distance,
+                weights.fairness,
+                weights.balance,
+            ]
+            .iter()
+            .all(|&w| w >= 0.0 && w.is_finite())
+        };
+        let valid_max_pickup = |max_pickup: &Option<MaxPickup>| {
+            [
+                max_pickup.and_then(|m| m.distance),
+                max_pickup.and_then(|m| m.eta),
+            ]
+            .iter()
+            .flatten()
+            .all(|&limit| limit >= 0.0 && !limit.is_nan())
+        };
+        check(
+            valid_weights(&self.dispatch_weights),
+            "dispatch_weights must not be negative",
+        );
+        check(
+            valid_max_pickup(&self.max_pickup),
+            "max_pickup limits must not be negative",
+        );
+        let zones = (self.zones.max(1) * self.zones.max(1)) as usize;
+        for (i, incident) in self.incidents.iter().enumerate() {
+            for problem in incident.problems(zones) {
+                check(false, &format!("incidents[{}]: {}", i, problem));
+            }
+        }
+        if let Some(transit) = &self.transit {
+            for problem in transit.problems() {
+                check(false, &problem);
+            }
+        }
+        if let Some(offers) = &self.offers {
+            for problem in offers.problems() {
+                check(false, &problem);
+            }
+        }
+        if let Some(ab_test) = &self.ab_test {
+            let share = |s: f64| (0.0..=1.0).contains(&s);
+            check(
+                share(ab_test.taxis) && ab_test.requests.is_none_or(share),
+                "ab_test shares of taxis and requests must be between 0 and 1",
+            );
+            check(
+                ab_test.matching_interval != Some(0),
+                "ab_test matching_interval must be at least 1",
+            );
+            check(
+                ab_test.dispatch_weights.as_ref().is_none_or(valid_weights),
+                "ab_test dispatch_weights must not be negative",
+            );
+            check(
+                valid_max_pickup(&ab_test.max_pickup),
+                "ab_test max_pickup limits must not be negative",
+            );
+        }
+        check(
+            self.vehicle_types.len() <= MAX_VEHICLE_TYPES,
+            "there can't be more than 64 vehicle_types",
+        );
+        for (i, vehicle_type) in self.vehicle_types.iter().enumerate() {
+            let name = &vehicle_type.name;
+            check(
+                !self.vehicle_types[..i].iter().any(|t| &t.name == name),
+                &format!("vehicle type {:?} is defined twice", name),
+            );
+            check(
+                vehicle_type.capacity > 0,
+                &format!("vehicle type {:?} needs a capacity of at least 1", name),
+            );
+            check(
+                vehicle_type.speed > 0.0 && vehicle_type.speed.is_finite(),
+                &format!("vehicle type {:?} needs a positive speed", name),
+            );
+            check(
+                !vehicle_type
+                    .acceleration
+                    .is_some_and(|a| a <= 0.0 || !a.is_finite()),
+                &format!("vehicle type {:?} needs a positive acceleration", name),
+            );
+            check(
+                !vehicle_type.cost_per_km.is_some_and(|c| c < 0.0),
+                &format!("vehicle type {:?} must not have negative costs", name),
+            );
+        }
+        let fleet = self.fleet();
+        for preference in &self.vehicle_preferences {
+            for name in &preference.types {
+                check(
+                    fleet.iter().any(|t| &t.name == name),
+                    &format!(
+                        "vehicle_preferences name an unknown vehicle type {:?}",
+                        name
+                    ),
+                );
+            }
+        }
+        check(
+            self.vehicle_preferences.is_empty()
+                || self
+                    .vehicle_preferences
+                    .iter()
+                    .all(|p| p.weight >= 0.0 && p.weight.is_finite())
+                    && self.vehicle_preferences.iter().any(|p| p.weight > 0.0),
+            "vehicle_preferences need at least one positive weight and no negative ones",
+        );
+        for (i, limit) in self.speed_limits.iter().enumerate() {
+            let name = &limit.name;
+            check(
+                !self.speed_limits[..i].iter().any(|l| &l.name == name),
+                &format!("speed limit {:?} is defined twice", name),
+            );
+            check(
+                limit.speed > 0.0 && limit.speed.is_finite(),
+                &format!("speed limit {:?} must be positive", name),
+            );
+        }
+        if let Some(travel_times) = &self.travel_times {
+            for problem in travel_times.problems() {
+                check(false, &problem);
+            }
+        }
+        for (i, venue) in self.venues.iter().enumerate() {
+            let name = &venue.name;
+            check(
+                !self.venues[..i].iter().any(|v| &v.name == name),
+                &format!("venue {:?} is defined twice", name),
+            );
+            let within = |c: f64| (0.0..=self.city_size).contains(&c);
+            check(
+                within(venue.position.x) && within(venue.position.y),
+                &format!("venue {:?} must lie within the city", name),
+            );
+            check(
+                venue.radius >= 0.0 && venue.radius.is_finite(),
+                &format!("venue {:?} must not have a negative radius", name),
+            );
+            check(
+                venue.capacity > 0,
+                &format!("venue {:?} needs a capacity of at least 1", name),
+            );
+            for burst in &venue.bursts {
+                check(
+                    burst
+                        .every
+                        .is_none_or(|every| every >= burst.duration.max(1)),
+                    &format!(
+                        "bursts of venue {:?} can't repeat before they're over",
+                        name
+                    ),
+                );
+            }
+        }
+        for (i, layer) in self.demand.iter().enumerate() {
+            check(
+                !self.demand[..i].iter().any(|l| l.name == layer.name),
+                &format!("demand layer {:?} is defined twice", layer.name),
+            );
+            for problem in layer.problems(zones, self.city_size) {
+                check(false, &format!("demand[{}]: {}", i, problem));
+            }
+        }
+        if let Some(cruising) = &self.cruising {
+            check(
+                cruising.speed >= 0.0 && cruising.speed.is_finite(),
+                "cruising.speed must not be negative",
+            );
+            check(
+                cruising.range >= 0.0 && cruising.range.is_finite(),
+                "cruising.range must not be negative",
+            );
+        }
+        if let Some(maintenance) = &self.maintenance {
+            check(
+                maintenance.interval > 0.0 && maintenance.interval.is_finite(),
+                "maintenance.interval must be positive",
+            );
+            check(
+                maintenance.duration >= 0.0 && maintenance.duration.is_finite(),
+                "maintenance.duration must not be negative",
+            );
+        }
+        check(
+            [
+                self.pricing.base_fare,
+                self.pricing.per_km,
+                self.pricing.per_minute,
+            ]
+            .iter()
+            .all(|&p| p >= 0.0 && p.is_finite()),
+            "pricing must not have negative prices",
+        );
+        if let Some(surge) = &self.pricing.surge {
+            check(
+                surge.sensitivity >= 0.0,
+                "pricing.surge.sensitivity must not be negative",
+            );
+            check(surge.cap >= 1.0, "pricing.surge.cap must be at least 1");
+        }
+        check(
+            self.costs.per_km >= 0.0 && self.costs.per_shift >= 0.0,
+            "costs must not be negative",
+        );
+        check(
+            self.costs.shift_length > 0.0,
+            "costs.shift_length must be positive",
+        );
+        if let Some(cancellation) = &self.eta_cancellation {
+            check(
+                cancellation.patience > 0.0,
+                "eta_cancellation.patience must be positive",
+            );
+        }
+        if let Some(rider_choice) = &self.rider_choice {
+            check(
+                self.willingness_to_pay.is_none() && self.eta_cancellation.is_none(),
+                "rider_choice replaces willingness_to_pay and eta_cancellation",
+            );
+            for problem in rider_choice.problems() {
+                check(false, &problem);
+            }
+        }
+        if let Some(no_show) = &self.no_show {
+            check(
+                (0.0..=1.0).contains(&no_show.probability),
+                "no_show.probability must be between 0 and 1",
+            );
+            check(
+                no_show.grace_period >= 0.0 && no_show.grace_period.is_finite(),
+                "no_show.grace_period must not be negative",
+            );
+        }
+        check(
+            self.histograms.trip_distance > 0.0 && self.histograms.trip_duration > 0.0,
+            "histograms need positive bucket widths",
+        );
+        if let Some(re_request) = &self.re_request {
+            check(
+                (0.0..=1.0).contains(&re_request.probability),
+                "re_request.probability must be between 0 and 1",
+            );
+            check(
+                re_request.cooldown >= 0.0 && re_request.cooldown.is_finite(),
+                "re_request.cooldown must not be negative",
+            );
+        }
+        check(
+            self.rolling_window != Some(0),
+            "rolling_window must be at least 1 minute",
+        );
+        if let ArchivePolicy::Recent { minutes } = self.archive {
+            check(
+                minutes >= 0.0 && minutes.is_finite(),
+                "archive minutes must not be negative",
+            );
+        }
+        if let ArchivePolicy::Spill { threshold, .. } = self.archive {
+            check(threshold > 0, "archive.threshold must be at least 1");
+        }
+
+        let distributions = [
+            ("trip_duration", Some(&self.trip_duration)),
+            ("boarding_time", self.boarding_time.as_ref()),
+            ("alighting_time", self.alighting_time.as_ref()),
+            ("stop_time", self.stop_time.as_ref()),
+            ("willingness_to_pay", self.willingness_to_pay.as_ref()),
+        ];
+        for (name, distribution) in distributions {
+            if let Some(Err(problem)) = distribution.map(Distribution::validate) {
+                problems.push(format!("{}: {}", name, problem));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ScenarioError::Invalid(problems))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+
+    /// Everything that's wrong with the parameters.
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "couldn't read scenario: {}", e),
+            ScenarioError::Parse(e) => write!(f, "couldn't parse scenario: {}", e),
+            ScenarioError::Invalid(problems) => {
+                write!(f, "invalid scenario:")?;
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for ScenarioError {}
+
+impl From<io::Error> for ScenarioError {
+    fn from(e: io::Error) -> ScenarioError {
+        ScenarioError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ScenarioError {
+    fn from(e: toml::de::Error) -> ScenarioError {
+        ScenarioError::Parse(e)
+    }
+}
@@ -0,0 +1,435 @@
+//! Long-running server mode which can be monitored while it runs.
+//!
+//! A `MetricsRecorder` follows the `World` and keeps `Metrics` up to date, which a
+//! `MetricsServer` serves in the Prometheus text format at `/metrics`. The HTTP side is
+//! deliberately minimal: one request per connection, handled on a background thread.
+//!
+//! Some parameters can be changed while the `World` runs, to see how it responds, by posting
+//! them as a query string to `/control`, e.g. `POST /control?request_spawn_chance=0.5`. Which
+//! ones are listed in `Control`. The server hands them to whoever runs the `World`, which
+//! applies them between ticks.
+
+use std::fmt::{self, Write as _};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::events::Event;
+use crate::observer::Observer;
+use crate::window::WindowSummary;
+use crate::world::{Request, RequestOutcome, World};
+
+/// What a running `World` looks like to a monitoring system.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metrics {
+    pub age: u64,
+    pub active_requests: u64,
+    pub waiting_requests: u64,
+    pub free_taxis: u64,
+    pub occupied_taxis: u64,
+    pub surge_multiplier: f64,
+
+    /// Wall-clock seconds the last tick took.
+    pub tick_duration: f64,
+
+    /// Over the last `rolling_window` minutes.
+    pub recent: Option<WindowSummary>,
+
+    pub requests_spawned: u64,
+    pub assignments: u64,
+    pub offers_sent: u64,
+    pub offers_declined: u64,
+    pub requests_fulfilled: u64,
+    pub requests_expired: u64,
+    pub requests_no_show: u64,
+    pub requests_rejected: u64,
+}
+
+impl Metrics {
+    /// The metrics in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: &dyn fmt::Display| {
+            let _ = writeln!(out, "# HELP taxi_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE taxi_{} {}", name, kind);
+            let _ = writeln!(out, "taxi_{} {}", name, value);
+        };
+        metric(
+            "world_age_ticks",
+            "gauge",
+            "Ticks the world has run for.",
+            &self.age,
+        );
+        metric(
+            "active_requests",
+            "gauge",
+            "Requests which are waiting or being driven.",
+            &self.active_requests,
+        );
+        metric(
+            "waiting_requests",
+            "gauge",
+            "Requests which have no taxi yet.",
+            &self.waiting_requests,
+        );
+        metric("free_taxis", "gauge", "Idle taxis.", &self.free_taxis);
+        metric(
+            "occupied_taxis",
+            "gauge",
+            "Taxis busy with a request.",
+            &self.occupied_taxis,
+        );
+        metric(
+            "surge_multiplier",
+            "gauge",
+            "Current factor on all fares.",
+            &self.surge_multiplier,
+        );
+        metric(
+            "tick_duration_seconds",
+            "gauge",
+            "Wall-clock time the last tick took.",
+            &self.tick_duration,
+        );
+        metric(
+            "requests_spawned_total",
+            "counter",
+            "Requests submitted by riders.",
+            &self.requests_spawned,
+        );
+        metric(
+            "assignments_total",
+            "counter",
+            "Requests assigned to a taxi.",
+            &self.assignments,
+        );
+        metric(
+            "offers_sent_total",
+            "counter",
+            "Rides offered to drivers.",
+            &self.offers_sent,
+        );
+        metric(
+            "offers_declined_total",
+            "counter",
+            "Rides drivers declined or didn't answer in time.",
+            &self.offers_declined,
+        );
+        metric(
+            "requests_fulfilled_total",
+            "counter",
+            "Requests whose trip is done.",
+            &self.requests_fulfilled,
+        );
+        metric(
+            "requests_expired_total",
+            "counter",
+            "Requests canceled before a taxi picked them up.",
+            &self.requests_expired,
+        );
+        metric(
+            "requests_no_show_total",
+            "counter",
+            "Requests whose rider wasn't at the pickup.",
+            &self.requests_no_show,
+        );
+        metric(
+            "requests_rejected_total",
+            "counter",
+            "Requests no taxi within reach took.",
+            &self.requests_rejected,
+        );
+        if let Some(recent) = &self.recent {
+            metric(
+                "recent_requests_per_minute",
+                "gauge",
+                "Requests submitted per simulated minute over the rolling window.",
+                &recent.requests_per_minute,
+            );
+            metric(
+                "recent_fulfillment_rate",
+                "gauge",
+                "Share of the requests finished over the rolling window which were fulfilled.",
+                &recent.fulfillment_rate,
+            );
+            metric(
+                "recent_mean_wait_ticks",
+                "gauge",
+                "Average wait of the requests fulfilled over the rolling window.",
+                &recent.mean_wait,
+            );
+            metric(
+                "recent_taxi_utilization",
+                "gauge",
+                "Average share of occupied taxis over the rolling window.",
+                &recent.taxi_utilization,
+            );
+        }
+        out
+    }
+}
+
+/// How long a `/control` request waits for the `World` to apply it.
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a client may take to send its request or read the response.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A parameter of a running `World` to change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    RequestSpawnChance(f64),
+    SurgeCap(f64),
+    MatchingInterval(u64),
+}
+
+/// `Control`s to apply, with where to report back whether that worked.
+pub type ControlRequest = (Vec<Control>, Sender<Result<(), String>>);
+
+impl Control {
+    /// Parses `name=value` as it is named in a `Scenario`, checking the value like
+    /// `Scenario::validate` would.
+    pub fn parse(assignment: &str) -> Result<Control, String> {
+        let (name, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("{:?} isn't of the form name=value", assignment))?;
+        let number = || {
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| format!("{} must be a number", name))
+        };
+        match name {
+            "request_spawn_chance" => {
+                let chance = number()?;
+                if !(0.0..=1.0).contains(&chance) {
+                    return Err("request_spawn_chance must be between 0 and 1".to_string());
+                }
+                Ok(Control::RequestSpawnChance(chance))
+            }
+            "surge_cap" => {
+                let cap = number()?;
+                if cap < 1.0 {
+                    return Err("surge_cap must be at least 1".to_string());
+                }
+                Ok(Control::SurgeCap(cap))
+            }
+            "matching_interval" => match value.parse() {
+                Ok(interval) if interval > 0 => Ok(Control::MatchingInterval(interval)),
+                _ => Err("matching_interval must be at least 1".to_string()),
+            },
+            _ => Err(format!(
+                "{} can't be changed, only request_spawn_chance, surge_cap and matching_interval",
+                name
+            )),
+        }
+    }
+
+    /// Why the parameter can't be changed in `world`, if it can't.
+    pub fn check(self, world: &World) -> Result<(), String> {
+        match self {
+            Control::RequestSpawnChance(_) if world.has_arrivals() => {
+                Err("request_spawn_chance can't be changed with an arrival process".to_string())
+            }
+            Control::SurgeCap(_) if !world.has_surge() => {
+                Err("surge_cap can't be changed without surge pricing".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Changes the parameter in `world`, unless `check` fails.
+    pub fn apply(self, world: &mut World) -> Result<(), String> {
+        self.check(world)?;
+        match self {
+            Control::RequestSpawnChance(chance) => {
+                world.set_request_spawn_chance(chance);
+            }
+            Control::SurgeCap(cap) => {
+                world.set_surge_cap(cap);
+            }
+            Control::MatchingInterval(interval) => world.set_matching_interval(interval),
+        }
+        Ok(())
+    }
+}
+
+/// Applies all of `controls` to `world`, or none of them if any can't be.
+pub fn apply_controls(controls: &[Control], world: &mut World) -> Result<(), String> {
+    controls.iter().try_for_each(|c| c.check(world))?;
+    controls.iter().try_for_each(|c| c.apply(world))
+}
+
+/// An `Observer` which keeps shared `Metrics` up to date.
+#[derive(Debug)]
+pub struct MetricsRecorder {
+    metrics: Arc<Mutex<Metrics>>,
+
+    /// When the previous tick ended.
+    last_tick: Option<Instant>,
+}
+
+impl MetricsRecorder {
+    pub fn new(metrics: Arc<Mutex<Metrics>>) -> MetricsRecorder {
+        MetricsRecorder {
+            metrics,
+            last_tick: None,
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Metrics)) {
+        // A panicking reader can't leave the metrics half-updated, so poisoning doesn't matter.
+        let mut metrics = self.metrics.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut metrics);
+    }
+}
+
+impl Observer for MetricsRecorder {
+    fn on_tick(&mut self, world: &World) {
+        let now = Instant::now();
+        let tick_duration = self
+            .last_tick
+            .map_or(0.0, |last| now.duration_since(last).as_secs_f64());
+        self.last_tick = Some(now);
+
+        let requests = world.active_requests();
+        let waiting_requests = requests
+            .iter()
+            .filter(|r| r.assigned_taxi().is_none())
+            .count() as u64;
+        let free_taxis = world.taxis().iter().filter(|t| t.is_idle()).count() as u64;
+        let occupied_taxis = world.taxis().iter().filter(|t| t.is_occupied()).count() as u64;
+        self.update(|m| {
+            m.age = world.age();
+            m.active_requests = requests.len() as u64;
+            m.waiting_requests = waiting_requests;
+            m.free_taxis = free_taxis;
+            m.occupied_taxis = occupied_taxis;
+            m.surge_multiplier = world.surge_multiplier();
+            m.tick_duration = tick_duration;
+            m.recent = Some(world.recent());
+        });
+    }
+
+    fn on_event(&mut self, _world: &World, event: &Event) {
+        match event {
+            Event::RequestSpawned { .. } => self.update(|m| m.requests_spawned += 1),
+            Event::RequestAssigned { .. } => self.update(|m| m.assignments += 1),
+            Event::RideOffered { .. } => self.update(|m| m.offers_sent += 1),
+            Event::RideDeclined { .. } => self.update(|m| m.offers_declined += 1),
+            _ => (),
+        }
+    }
+
+    fn on_archived(&mut self, _world: &World, request: &Request) {
+        self.update(|m| match request.outcome() {
+            Some(RequestOutcome::Fulfilled) => m.requests_fulfilled += 1,
+            Some(RequestOutcome::Canceled) => m.requests_expired += 1,
+            Some(RequestOutcome::NoShow) => m.requests_no_show += 1,
+            Some(RequestOutcome::Rejected) => m.requests_rejected += 1,
+            None => (),
+        });
+    }
+}
+
+/// Serves `Metrics` over HTTP on a background thread, and passes `Control`s on to `controls`.
+#[derive(Debug)]
+pub struct MetricsServer {
+    address: SocketAddr,
+}
+
+impl MetricsServer {
+    /// Starts listening on `address` right away, so that a port which is already taken is
+    /// reported before the run starts.
+    pub fn spawn(
+        address: &str,
+        metrics: Arc<Mutex<Metrics>>,
+        controls: Sender<ControlRequest>,
+    ) -> io::Result<MetricsServer> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // Every client gets a thread of its own, so a slow one holds up nobody else.
+                let (metrics, controls) = (metrics.clone(), controls.clone());
+                thread::spawn(move || {
+                    // A client which hangs up early is its own problem.
+                    let _ = respond(stream, &metrics, &controls);
+                });
+            }
+        });
+        Ok(MetricsServer { address })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+fn respond(
+    stream: TcpStream,
+    metrics: &Mutex<Metrics>,
+    controls: &Sender<ControlRequest>,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers, nothing in them matters here.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let metrics = metrics.lock().unwrap_or_else(|e| e.into_inner());
+            ("200 OK", metrics.to_prometheus())
+        }
+        (Some("POST"), Some(target)) if target.split('?').next() == Some("/control") => {
+            control(target, controls)
+        }
+        _ => ("404 Not Found", "Not found\n".to_string()),
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Parses the query string of a `/control` request and waits for the `World` to apply it.
+fn control(target: &str, controls: &Sender<ControlRequest>) -> (&'static str, String) {
+    let query = target.split_once('?').map_or("", |(_, query)| query);
+    let parsed: Result<Vec<Control>, String> = query
+        .split('&')
+        .filter(|a| !a.is_empty())
+        .map(Control::parse)
+        .collect();
+    let parsed = match parsed {
+        Ok(parsed) if parsed.is_empty() => {
+            return ("400 Bad Request", "Nothing to change\n".to_string())
+        }
+        Ok(parsed) => parsed,
+        Err(problem) => return ("400 Bad Request", format!("{}\n", problem)),
+    };
+    let (reply, result) = mpsc::channel();
+    if controls.send((parsed, reply)).is_err() {
+        return ("503 Service Unavailable", "The run is over\n".to_string());
+    }
+    match result.recv_timeout(CONTROL_TIMEOUT) {
+        Ok(Ok(())) => ("200 OK", "Changed\n".to_string()),
+        Ok(Err(problem)) => ("400 Bad Request", format!("{}\n", problem)),
+        Err(_) => ("503 Service Unavailable", "The run is over\n".to_string()),
+    }
+}
@@ -0,0 +1,1327 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::ab_test::GROUPS;
+use crate::demand::DemandLayer;
+use crate::histogram::{Histogram, HistogramBuckets};
+use crate::pricing::Costs;
+use crate::quantile::{QuantileEstimator, StatisticsMode};
+use crate::time::{ticks_as_hours, Clock, SimTime, SECONDS_PER_HOUR};
+use crate::units::Units;
+use crate::vehicle::VehicleType;
+use crate::venue::Venue;
+use crate::world::{Request, RequestOutcome, Taxi};
+use crate::zones::Zones;
+
+/// Quantiles of the waiting time which end up in the `Summary`.
+const WAIT_QUANTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
+const HOURS_PER_DAY: u64 = 24;
+
+/// Hour of the day `time` falls into, starting at midnight.
+fn hour_of(time: SimTime) -> usize {
+    time.hour_of_day() as usize
+}
+
+/// Running counters which the `World` updates as it goes so that it never has to scan its
+/// archived `Request`s to summarize a run.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Statistics {
+    pub(crate) requests_spawned: u64,
+
+    /// `Request`s by riders who tried again after canceling or being rejected. Also counted as
+    /// spawned.
+    pub(crate) re_requests: u64,
+
+    /// Riders who didn't submit a `Request` because they weren't willing to pay the fare.
+    pub(crate) requests_priced_out: u64,
+
+    requests_fulfilled: u64,
+    requests_canceled: u64,
+    requests_no_show: u64,
+    requests_rejected: u64,
+
+    /// Ticks `Taxi`s spent driving to and waiting for riders who didn't show up.
+    no_show_taxi_ticks: u64,
+
+    /// Riders who canceled because of the pickup ETA they were quoted. Also counted as
+    /// canceled.
+    pub(crate) requests_canceled_on_eta: u64,
+
+    /// Riders who canceled to take transit instead. Also counted as canceled.
+    pub(crate) requests_to_transit: u64,
+
+    /// Sum of the waiting times of all fulfilled `Request`s.
+    total_fulfilled_wait: u64,
+
+    /// Quantiles of the waiting times of all fulfilled `Request`s.
+    fulfilled_wait_quantiles: QuantileEstimator,
+
+    /// Sum of the ticks `Taxi`s drove to the pickups of all fulfilled `Request`s.
+    total_pickup_time: u64,
+
+    /// Stops on the way, legs of the rides and ticks driven on them over all fulfilled
+    /// `Request`s.
+    total_stops: u64,
+    total_legs: u64,
+    total_leg_time: u64,
+
+    /// Sum of the pickup ETAs quoted to all fulfilled `Request`s.
+    total_quoted_eta: f64,
+
+    /// Sum of the fares of all fulfilled `Request`s.
+    revenue: f64,
+
+    /// Rides offered to drivers, how many of those they accepted and how many they didn't
+    /// answer in time.
+    pub(crate) offers_sent: u64,
+    pub(crate) offers_accepted: u64,
+    pub(crate) offers_timed_out: u64,
+
+    /// `Request`s which `max_offers` drivers declined.
+    pub(crate) requests_escalated: u64,
+
+    /// How often dispatch ran with any `Request`s waiting, and their total.
+    matching_rounds: u64,
+    total_batch_size: u64,
+
+    /// Sum over all ticks of the surge multiplier.
+    total_surge_multiplier: f64,
+
+    /// Sum over all ticks of how many `Taxi`s were occupied.
+    occupied_taxi_ticks: u64,
+
+    /// Sum over all ticks of how many `Taxi`s were off the road for maintenance.
+    maintenance_taxi_ticks: u64,
+
+    /// Sum over all ticks of how many `Taxi`s existed.
+    taxi_ticks: u64,
+
+    /// Number of ticks recorded so far.
+    ticks: u64,
+
+    /// How many of those were actually computed rather than skipped over.
+    steps: u64,
+
+    /// Kilometers driven by the whole fleet as of the last tick.
+    fleet_mileage: f64,
+
+    /// Kilometers idle `Taxi`s cruised around.
+    pub(crate) cruising_mileage: f64,
+
+    /// Kilometers `Taxi`s drove to pickups.
+    pub(crate) pickup_mileage: f64,
+
+    by_party_size: BTreeMap<u32, PartySizeStatistics>,
+
+    /// Indexed like the fleet of the `World`.
+    by_vehicle_type: Vec<VehicleTypeStatistics>,
+
+    /// Indexed like the venues of the `World`.
+    by_venue: Vec<VenueStatistics>,
+
+    /// Indexed like the demand layers of the `Scenario`.
+    by_layer: Vec<LayerStatistics>,
+
+    zones: Zones,
+
+    /// To tell which hour of the day a tick falls into.
+    clock: Clock,
+
+    /// Indexed by hour of the day times number of zones plus zone.
+    by_zone_hour: Vec<ZoneHourStatistics>,
+
+    /// Indexed like `ab_test::GROUPS`, but with only group A if there's no A/B test.
+    by_group: Vec<GroupStatistics>,
+
+    /// Realized distances and durations of all fulfilled trips.
+    trip_distances: Histogram,
+    trip_durations: Histogram,
+}
+
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct PartySizeStatistics {
+    requests_fulfilled: u64,
+    requests_canceled: u64,
+    requests_no_show: u64,
+    requests_rejected: u64,
+    total_fulfilled_wait: u64,
+}
+
+/// `Request`s which spawned in one zone during one hour of the day, and the `Taxi`s on the road
+/// there and then.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct ZoneHourStatistics {
+    requests_fulfilled: u64,
+
+    /// Canceled, no-shows or rejected.
+    requests_unfulfilled: u64,
+    total_fulfilled_wait: u64,
+
+    /// Ticks `Taxi`s which weren't in maintenance spent in the zone.
+    supply_ticks: u64,
+}
+
+/// Riders who came out of one `Venue` and what became of them.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct VenueStatistics {
+    name: String,
+    riders: u64,
+
+    /// Riders who made it onto the curb, and the ticks all of them queued inside before that.
+    riders_released: u64,
+    total_queue_time: u64,
+
+    requests_fulfilled: u64,
+
+    /// Canceled, no-shows or rejected.
+    requests_unfulfilled: u64,
+    total_fulfilled_wait: u64,
+}
+
+/// Riders who came from one `DemandLayer` and what became of them.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct LayerStatistics {
+    name: String,
+    riders: u64,
+    requests_fulfilled: u64,
+
+    /// Canceled, no-shows or rejected.
+    requests_unfulfilled: u64,
+    total_fulfilled_wait: u64,
+}
+
+/// `Request`s routed to one group of an A/B test, and the `Taxi`s in it.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct GroupStatistics {
+    requests_fulfilled: u64,
+
+    /// Canceled, no-shows or rejected.
+    requests_unfulfilled: u64,
+    total_fulfilled_wait: u64,
+    total_pickup_time: u64,
+    revenue: f64,
+    occupied_taxi_ticks: u64,
+    taxi_ticks: u64,
+}
+
+/// Like the fleet-wide counters, but only for the `Taxi`s of one vehicle type.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct VehicleTypeStatistics {
+    vehicle_type: VehicleType,
+    occupied_taxi_ticks: u64,
+    taxi_ticks: u64,
+
+    /// As of the last tick.
+    trips: u64,
+    mileage: f64,
+}
+
+impl Statistics {
+    pub fn new(
+        mode: StatisticsMode,
+        buckets: &HistogramBuckets,
+        fleet: &[VehicleType],
+        venues: &[Venue],
+        zones: Zones,
+        clock: Clock,
+        groups: usize,
+    ) -> Statistics {
+        Statistics {
+            requests_spawned: 0,
+            re_requests: 0,
+            requests_priced_out: 0,
+            requests_fulfilled: 0,
+            requests_canceled: 0,
+            requests_no_show: 0,
+            no_show_taxi_ticks: 0,
+            requests_canceled_on_eta: 0,
+            requests_to_transit: 0,
+            requests_rejected: 0,
+            total_fulfilled_wait: 0,
+            fulfilled_wait_quantiles: QuantileEstimator::new(mode, &WAIT_QUANTILES),
+            total_pickup_time: 0,
+            total_stops: 0,
+            total_legs: 0,
+            total_leg_time: 0,
+            total_quoted_eta: 0.0,
+            revenue: 0.0,
+            offers_sent: 0,
+            offers_accepted: 0,
+            offers_timed_out: 0,
+            requests_escalated: 0,
+            matching_rounds: 0,
+            total_batch_size: 0,
+            total_surge_multiplier: 0.0,
+            occupied_taxi_ticks: 0,
+            maintenance_taxi_ticks: 0,
+            taxi_ticks: 0,
+            ticks: 0,
+            steps: 0,
+            fleet_mileage: 0.0,
+            cruising_mileage: 0.0,
+            pickup_mileage: 0.0,
+            by_party_size: BTreeMap::new(),
+            by_vehicle_type: fleet
+                .iter()
+                .map(|vehicle_type| VehicleTypeStatistics {
+                    vehicle_type: vehicle_type.clone(),
+                    occupied_taxi_ticks: 0,
+                    taxi_ticks: 0,
+                    trips: 0,
+                    mileage: 0.0,
+                })
+                .collect(),
+            by_venue: venues
+                .iter()
+                .map(|venue| VenueStatistics {
+                    name: venue.name.clone(),
+                    ..VenueStatistics::default()
+                })
+                .collect(),
+            by_layer: vec![],
+            zones,
+            clock,
+            by_zone_hour: vec![
+                ZoneHourStatistics::default();
+                zones.count() * HOURS_PER_DAY as usize
+            ],
+            by_group: vec![GroupStatistics::default(); groups],
+            trip_distances: Histogram::new(buckets.trip_distance),
+            trip_durations: Histogram::new(buckets.trip_duration),
+        }
+    }
+
+    /// Breaks riders down by the demand `layers` they came from as well.
+    pub fn with_layers(mut self, layers: &[DemandLayer]) -> Statistics {
+        self.by_layer = layers
+            .iter()
+            .map(|layer| LayerStatistics {
+                name: layer.name.clone(),
+                ..LayerStatistics::default()
+            })
+            .collect();
+        self
+    }
+
+    fn zone_hour(&mut self, zone: usize, tick: u64) -> &mut ZoneHourStatistics {
+        let hour = hour_of(self.clock.at(tick));
+        &mut self.by_zone_hour[hour * self.zones.count() + zone]
+    }
+
+    pub(crate) fn record_archived(&mut self, request: &Request) {
+        let zone = self.zones.of(request.pickup());
+        let cell = self.zone_hour(zone, request.spawned_at());
+        match request.outcome() {
+            Some(RequestOutcome::Fulfilled) => {
+                cell.requests_fulfilled += 1;
+                cell.total_fulfilled_wait += request.waited();
+            }
+            _ => cell.requests_unfulfilled += 1,
+        }
+        if let Some(v) = request.venue() {
+            let venue = &mut self.by_venue[v];
+            match request.outcome() {
+                Some(RequestOutcome::Fulfilled) => {
+                    venue.requests_fulfilled += 1;
+                    venue.total_fulfilled_wait += request.waited();
+                }
+                _ => venue.requests_unfulfilled += 1,
+            }
+        }
+        if let Some(l) = request.layer() {
+            let layer = &mut self.by_layer[l];
+            match request.outcome() {
+                Some(RequestOutcome::Fulfilled) => {
+                    layer.requests_fulfilled += 1;
+                    layer.total_fulfilled_wait += request.waited();
+                }
+                _ => layer.requests_unfulfilled += 1,
+            }
+        }
+
+        let group = &mut self.by_group[request.group()];
+        match request.outcome() {
+            Some(RequestOutcome::Fulfilled) => {
+                group.requests_fulfilled += 1;
+                group.total_fulfilled_wait += request.waited();
+                group.total_pickup_time += request.pickup_time();
+                group.revenue += request.fare();
+            }
+            _ => group.requests_unfulfilled += 1,
+        }
+
+        let party = self.by_party_size.entry(request.party_size()).or_default();
+        match request.outcome() {
+            Some(RequestOutcome::Fulfilled) => {
+                self.requests_fulfilled += 1;
+                self.total_fulfilled_wait += request.waited();
+                self.fulfilled_wait_quantiles.push(request.waited() as f64);
+                self.total_pickup_time += request.pickup_time();
+                self.total_stops += request.stops().len() as u64;
+                self.total_legs += request.leg_times().len() as u64;
+                self.total_leg_time += request.leg_times().iter().sum::<u64>();
+                self.trip_distances.record(request.trip_distance());
+                self.trip_durations.record(request.trip_duration() as f64);
+                self.total_quoted_eta += request.quoted_eta().unwrap_or(0.0);
+                self.revenue += request.fare();
+                party.requests_fulfilled += 1;
+                party.total_fulfilled_wait += request.waited();
+            }
+            Some(RequestOutcome::Canceled) => {
+                self.requests_canceled += 1;
+                party.requests_canceled += 1;
+            }
+            Some(RequestOutcome::NoShow) => {
+                self.requests_no_show += 1;
+                party.requests_no_show += 1;
+                self.no_show_taxi_ticks += request.pickup_time() + request.no_show_waited();
+            }
+            Some(RequestOutcome::Rejected) => {
+                self.requests_rejected += 1;
+                party.requests_rejected += 1;
+            }
+            None => unreachable!("Only dead requests can be archived."),
+        }
+    }
+
+    pub(crate) fn record_tick(&mut self, taxis: &[Taxi], surge_multiplier: f64, tick: u64) {
+        self.total_surge_multiplier += surge_multiplier;
+        self.occupied_taxi_ticks += taxis.iter().filter(|t| t.is_occupied()).count() as u64;
+        self.maintenance_taxi_ticks +=
+            taxis.iter().filter(|t| t.is_in_maintenance()).count() as u64;
+        self.taxi_ticks += taxis.len() as u64;
+        self.ticks += 1;
+        self.steps += 1;
+        self.fleet_mileage = taxis.iter().map(|t| t.mileage()).sum();
+
+        for v in &mut self.by_vehicle_type {
+            v.trips = 0;
+            v.mileage = 0.0;
+        }
+        for t in taxis {
+            let v = &mut self.by_vehicle_type[t.vehicle_type()];
+            v.occupied_taxi_ticks += t.is_occupied() as u64;
+            v.taxi_ticks += 1;
+            v.trips += t.trips();
+            v.mileage += t.mileage();
+            let group = &mut self.by_group[t.group()];
+            group.occupied_taxi_ticks += t.is_occupied() as u64;
+            group.taxi_ticks += 1;
+            if !t.is_in_maintenance() {
+                let zone = self.zones.of(t.position());
+                self.zone_hour(zone, tick).supply_ticks += 1;
+            }
+        }
+    }
+
+    pub(crate) fn record_matching_round(&mut self, waiting: usize) {
+        self.matching_rounds += 1;
+        self.total_batch_size += waiting as u64;
+    }
+
+    pub(crate) fn record_venue_riders(&mut self, venue: usize, riders: u32) {
+        self.by_venue[venue].riders += u64::from(riders);
+    }
+
+    pub(crate) fn record_layer_riders(&mut self, layer: usize, riders: u64) {
+        self.by_layer[layer].riders += riders;
+    }
+
+    /// Records a rider who made it onto the curb of `venue` after queuing inside for
+    /// `queue_time` ticks.
+    pub(crate) fn record_venue_queue_time(&mut self, venue: usize, queue_time: u64) {
+        let venue = &mut self.by_venue[venue];
+        venue.riders_released += 1;
+        venue.total_queue_time += queue_time;
+    }
+
+    /// Records `ticks` starting at `first` which were skipped over because nothing happened,
+    /// with no `Request`s and all `taxis` idle.
+    pub(crate) fn record_quiet_ticks(&mut self, taxis: &[Taxi], first: u64, ticks: u64) {
+        self.total_surge_multiplier += ticks as f64;
+        self.taxi_ticks += taxis.len() as u64 * ticks;
+        self.ticks += ticks;
+        for t in taxis {
+            self.by_vehicle_type[t.vehicle_type()].taxi_ticks += ticks;
+            self.by_group[t.group()].taxi_ticks += ticks;
+        }
+
+        // The skipped ticks may span several hours.
+        let end = first + ticks;
+        let mut tick = first;
+        while tick < end {
+            let until_next_hour =
+                SECONDS_PER_HOUR - self.clock.at(tick).seconds() % SECONDS_PER_HOUR;
+            let in_hour = until_next_hour.min(end - tick);
+            for t in taxis {
+                let zone = self.zones.of(t.position());
+                self.zone_hour(zone, tick).supply_ticks += in_hour;
+            }
+            tick += in_hour;
+        }
+    }
+
+    /// Summarizes the run so far, charging the fleet `costs`. Earnings are those of the current
+    /// `taxis`.
+    pub fn summary(&self, costs: &Costs, taxis: &[Taxi]) -> Summary {
+        let earnings: Vec<f64> = taxis.iter().map(|t| t.revenue()).collect();
+        let by_vehicle_type: Vec<VehicleTypeSummary> = self
+            .by_vehicle_type
+            .iter()
+            .map(|v| VehicleTypeSummary {
+                name: v.vehicle_type.name.clone(),
+                taxis: v.vehicle_type.count,
+                utilization: ratio(v.occupied_taxi_ticks, v.taxi_ticks),
+                trips: v.trips,
+                mileage: v.mileage,
+                costs: costs
+                    .for_vehicle(&v.vehicle_type)
+                    .of(v.mileage, v.taxi_ticks),
+            })
+            .collect();
+        let fleet_costs = by_vehicle_type.iter().map(|v| v.costs).sum();
+        let by_venue = self
+            .by_venue
+            .iter()
+            .map(|v| VenueSummary {
+                name: v.name.clone(),
+                riders: v.riders,
+                requests_fulfilled: v.requests_fulfilled,
+                requests_unfulfilled: v.requests_unfulfilled,
+                mean_queue_time: ratio(v.total_queue_time, v.riders_released),
+                mean_wait: ratio(v.total_fulfilled_wait, v.requests_fulfilled),
+            })
+            .collect();
+        let by_layer = self
+            .by_layer
+            .iter()
+            .map(|l| LayerSummary {
+                name: l.name.clone(),
+                riders: l.riders,
+                requests_fulfilled: l.requests_fulfilled,
+                requests_unfulfilled: l.requests_unfulfilled,
+                mean_wait: ratio(l.total_fulfilled_wait, l.requests_fulfilled),
+            })
+            .collect();
+
+        let zones = self.zones.count();
+        let mut by_zone = vec![ZoneHourStatistics::default(); zones];
+        let mut by_zone_hour = vec![];
+        for (i, cell) in self.by_zone_hour.iter().enumerate() {
+            let total = &mut by_zone[i % zones];
+            total.requests_fulfilled += cell.requests_fulfilled;
+            total.requests_unfulfilled += cell.requests_unfulfilled;
+            total.total_fulfilled_wait += cell.total_fulfilled_wait;
+            total.supply_ticks += cell.supply_ticks;
+            let hour = (i / zones) as u32;
+            if cell.requests_fulfilled + cell.requests_unfulfilled + cell.supply_ticks > 0 {
+                by_zone_hour.push(cell.summary(i % zones, Some(hour)));
+            }
+        }
+        let finished = self.requests_fulfilled
+            + self.requests_canceled
+            + self.requests_no_show
+            + self.requests_rejected;
+        let wait_quantiles = self.fulfilled_wait_quantiles.estimates();
+        Summary {
+            requests_spawned: self.requests_spawned,
+            re_requests: self.re_requests,
+            requests_priced_out: self.requests_priced_out,
+            requests_fulfilled: self.requests_fulfilled,
+            requests_canceled: self.requests_canceled,
+            requests_canceled_on_eta: self.requests_canceled_on_eta,
+            requests_to_transit: self.requests_to_transit,
+            requests_no_show: self.requests_no_show,
+            requests_rejected: self.requests_rejected,
+            no_show_rate: ratio(self.requests_no_show, finished),
+            wasted_taxi_time: self.no_show_taxi_ticks,
+            fulfillment_rate: ratio(self.requests_fulfilled, finished),
+            mean_wait: ratio(self.total_fulfilled_wait, self.requests_fulfilled),
+            wait_p50: wait_quantiles[0],
+            wait_p90: wait_quantiles[1],
+            wait_p99: wait_quantiles[2],
+            mean_pickup_time: ratio(self.total_pickup_time, self.requests_fulfilled),
+            mean_stops: ratio(self.total_stops, self.requests_fulfilled),
+            mean_leg_time: ratio(self.total_leg_time, self.total_legs),
+            mean_batch_size: ratio(self.total_batch_size, self.matching_rounds),
+            offers_sent: self.offers_sent,
+            offer_acceptance_rate: ratio(self.offers_accepted, self.offers_sent),
+            offers_timed_out: self.offers_timed_out,
+            requests_escalated: self.requests_escalated,
+            mean_quoted_eta: if self.requests_fulfilled == 0 {
+                0.0
+            } else {
+                self.total_quoted_eta / self.requests_fulfilled as f64
+            },
+            taxi_utilization: ratio(self.occupied_taxi_ticks, self.taxi_ticks),
+            revenue: self.revenue,
+            mean_fare: if self.requests_fulfilled == 0 {
+                0.0
+            } else {
+                self.revenue / self.requests_fulfilled as f64
+            },
+            mean_surge_multiplier: if self.ticks == 0 {
+                1.0
+            } else {
+                self.total_surge_multiplier / self.ticks as f64
+            },
+            fleet_costs,
+            fleet_profit: self.revenue - fleet_costs,
+            profit_per_taxi: if self.ticks == 0 || self.taxi_ticks == 0 {
+                0.0
+            } else {
+                (self.revenue - fleet_costs) / (self.taxi_ticks as f64 / self.ticks as f64)
+            },
+            fleet_mileage: self.fleet_mileage,
+            cruising_mileage: self.cruising_mileage,
+            empty_mileage: self.pickup_mileage + self.cruising_mileage,
+            earnings_stddev: sample_variance(&earnings).sqrt(),
+            earnings_gini: gini(&earnings),
+            maintenance_downtime: self.maintenance_taxi_ticks,
+            effective_fleet_size: ratio(self.taxi_ticks - self.maintenance_taxi_ticks, self.ticks),
+            time_step_speedup: if self.steps == 0 {
+                1.0
+            } else {
+                ratio(self.ticks, self.steps)
+            },
+            by_party_size: self
+                .by_party_size
+                .iter()
+                .map(|(&party_size, p)| PartySizeSummary {
+                    party_size,
+                    requests_fulfilled: p.requests_fulfilled,
+                    requests_canceled: p.requests_canceled,
+                    requests_no_show: p.requests_no_show,
+                    requests_rejected: p.requests_rejected,
+                    mean_wait: ratio(p.total_fulfilled_wait, p.requests_fulfilled),
+                })
+                .collect(),
+            by_vehicle_type,
+            by_venue,
+            by_layer,
+            by_zone: by_zone
+                .iter()
+                .enumerate()
+                .map(|(zone, total)| total.summary(zone, None))
+                .collect(),
+            by_zone_hour,
+            by_group: self
+                .by_group
+                .iter()
+                .zip(GROUPS)
+                .map(|(g, name)| GroupSummary {
+                    group: name.to_string(),
+                    requests_fulfilled: g.requests_fulfilled,
+                    requests_unfulfilled: g.requests_unfulfilled,
+                    fulfillment_rate: ratio(
+                        g.requests_fulfilled,
+                        g.requests_fulfilled + g.requests_unfulfilled,
+                    ),
+                    mean_wait: ratio(g.total_fulfilled_wait, g.requests_fulfilled),
+                    mean_pickup_time: ratio(g.total_pickup_time, g.requests_fulfilled),
+                    utilization: ratio(g.occupied_taxi_ticks, g.taxi_ticks),
+                    revenue: g.revenue,
+                })
+                .collect(),
+            trip_distances: self.trip_distances.clone(),
+            trip_durations: self.trip_durations.clone(),
+            units: None,
+        }
+    }
+}
+
+impl ZoneHourStatistics {
+    fn summary(&self, zone: usize, hour: Option<u32>) -> ZoneSummary {
+        ZoneSummary {
+            zone,
+            hour,
+            requests_fulfilled: self.requests_fulfilled,
+            requests_unfulfilled: self.requests_unfulfilled,
+            fulfillment_rate: ratio(
+                self.requests_fulfilled,
+                self.requests_fulfilled + self.requests_unfulfilled,
+            ),
+            mean_wait: ratio(self.total_fulfilled_wait, self.requests_fulfilled),
+            supply_hours: ticks_as_hours(self.supply_ticks as f64),
+        }
+    }
+}
+
+fn ratio(numerator: u64, denominator: u64) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// Key metrics of a single run.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Summary {
+    pub requests_spawned: u64,
+
+    /// Spawned `Request`s by riders who tried again after canceling because of the ETA or being
+    /// rejected.
+    pub re_requests: u64,
+
+    /// Riders who never submitted a `Request` because the fare was more than they were willing
+    /// to pay.
+    pub requests_priced_out: u64,
+
+    pub requests_fulfilled: u64,
+    pub requests_canceled: u64,
+
+    /// Canceled `Request`s whose riders gave up when they were quoted the pickup ETA.
+    pub requests_canceled_on_eta: u64,
+
+    /// Canceled `Request`s whose riders took transit instead once they were quoted the
+    /// pickup ETA.
+    pub requests_to_transit: u64,
+
+    /// `Request`s whose riders weren't at the pickup.
+    pub requests_no_show: u64,
+
+    /// `Request`s which dispatch turned down since no `Taxi` was within the `max_pickup` reach.
+    pub requests_rejected: u64,
+
+    /// Share of finished `Request`s whose riders didn't show up.
+    pub no_show_rate: f64,
+
+    /// Ticks `Taxi`s spent driving to and waiting for riders who didn't show up, summed over
+    /// all `Taxi`s.
+    pub wasted_taxi_time: u64,
+
+    /// Share of finished `Request`s which were fulfilled rather than canceled, no-shows or
+    /// rejected.
+    pub fulfillment_rate: f64,
+
+    /// Average ticks a fulfilled `Request` waited until a `Taxi` was assigned.
+    pub mean_wait: f64,
+
+    /// Quantiles of the ticks a fulfilled `Request` waited. Estimates in streaming mode.
+    pub wait_p50: f64,
+    pub wait_p90: f64,
+    pub wait_p99: f64,
+
+    /// Average ticks a `Taxi` drove to pick up a fulfilled `Request` once assigned.
+    pub mean_pickup_time: f64,
+
+    /// Average stops on the way to the dropoff per fulfilled `Request`.
+    pub mean_stops: f64,
+
+    /// Average ticks a leg of a fulfilled ride took, from the pickup or a stop to the next
+    /// stop or the dropoff. The same as the trip duration for direct rides.
+    pub mean_leg_time: f64,
+
+    /// Average number of `Request`s dispatch matched at once, counting only rounds in which
+    /// any were waiting.
+    pub mean_batch_size: f64,
+
+    /// Rides dispatch offered to drivers, counting every driver a ride was offered to.
+    pub offers_sent: u64,
+
+    /// Share of `offers_sent` which the drivers accepted.
+    pub offer_acceptance_rate: f64,
+
+    /// Offers which the drivers didn't answer in time.
+    pub offers_timed_out: u64,
+
+    /// `Request`s which `max_offers` drivers declined, and which were assigned outright or
+    /// rejected.
+    pub requests_escalated: u64,
+
+    /// Average pickup ETA in ticks quoted to riders whose `Request` was fulfilled. Compare
+    /// with `mean_pickup_time` to see how good the estimates are.
+    pub mean_quoted_eta: f64,
+
+    /// Average share of `Taxi`s which were occupied per tick.
+    pub taxi_utilization: f64,
+
+    /// Sum of the fares of all fulfilled `Request`s.
+    pub revenue: f64,
+
+    /// Average fare of a fulfilled `Request`.
+    pub mean_fare: f64,
+
+    /// Average surge multiplier per tick.
+    pub mean_surge_multiplier: f64,
+
+    /// Running costs of the whole fleet.
+    pub fleet_costs: f64,
+
+    /// `revenue` minus `fleet_costs`.
+    pub fleet_profit: f64,
+
+    /// `fleet_profit` per `Taxi` in the `World`.
+    pub profit_per_taxi: f64,
+
+    /// Kilometers driven by the whole fleet.
+    pub fleet_mileage: f64,
+
+    /// Part of `fleet_mileage` which idle `Taxi`s cruised around without a `Request`.
+    pub cruising_mileage: f64,
+
+    /// Part of `fleet_mileage` driven without riders, to pickups or cruising around.
+    pub empty_mileage: f64,
+
+    /// Standard deviation of the revenue of all `Taxi`s, the lower the more evenly drivers
+    /// earned.
+    pub earnings_stddev: f64,
+
+    /// Gini coefficient of the revenue of all `Taxi`s, from 0 if all drivers earned the same
+    /// to almost 1 if a single one earned everything.
+    pub earnings_gini: f64,
+
+    /// Ticks spent in maintenance, summed over all `Taxi`s.
+    pub maintenance_downtime: u64,
+
+    /// Average number of `Taxi`s on the road, i.e. not in maintenance, per tick.
+    pub effective_fleet_size: f64,
+
+    /// Ticks per tick actually computed. Above 1 only with an adaptive time step.
+    pub time_step_speedup: f64,
+
+    /// Breakdown of the finished `Request`s by party size, smallest parties first.
+    pub by_party_size: Vec<PartySizeSummary>,
+
+    /// Breakdown of the fleet by vehicle type, in the order of the `Scenario`.
+    pub by_vehicle_type: Vec<VehicleTypeSummary>,
+
+    /// Breakdown of the riders who came out of venues, in the order of the `Scenario`.
+    pub by_venue: Vec<VenueSummary>,
+
+    /// Breakdown of the riders who came from demand layers, in the order of the `Scenario`.
+    pub by_layer: Vec<LayerSummary>,
+
+    /// Breakdown by the zone `Request`s spawned in, for the whole day.
+    pub by_zone: Vec<ZoneSummary>,
+
+    /// Breakdown by zone and hour of the day `Request`s spawned in, hour by hour. Leaves out
+    /// zones and hours without any `Request`s or `Taxi`s.
+    pub by_zone_hour: Vec<ZoneSummary>,
+
+    /// Breakdown by the groups of an A/B test, A first. Only has group A without one.
+    pub by_group: Vec<GroupSummary>,
+
+    /// Kilometers from pickup to dropoff of the fulfilled `Request`s.
+    pub trip_distances: Histogram,
+
+    /// Ticks the rides of the fulfilled `Request`s took.
+    pub trip_durations: Histogram,
+
+    /// What the `Summary` is displayed in, if the `Scenario` says. Everything is in km either
+    /// way, and only labeled with units if this is set.
+    pub units: Option<Units>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartySizeSummary {
+    pub party_size: u32,
+    pub requests_fulfilled: u64,
+    pub requests_canceled: u64,
+    pub requests_no_show: u64,
+    pub requests_rejected: u64,
+    pub mean_wait: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VehicleTypeSummary {
+    pub name: String,
+    pub taxis: u32,
+
+    /// Average share of the `Taxi`s of this type which were occupied per tick.
+    pub utilization: f64,
+
+    pub trips: u64,
+    pub mileage: f64,
+    pub costs: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VenueSummary {
+    pub name: String,
+
+    /// Riders who came out, including those still queuing or who were priced out.
+    pub riders: u64,
+    pub requests_fulfilled: u64,
+
+    /// Canceled, no-shows or rejected.
+    pub requests_unfulfilled: u64,
+
+    /// Average ticks riders queued inside before there was room for them at the curb.
+    pub mean_queue_time: f64,
+
+    /// Average ticks a fulfilled `Request` waited at the curb until a `Taxi` was assigned.
+    pub mean_wait: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayerSummary {
+    pub name: String,
+
+    /// Riders who arrived, including those who were priced out or didn't fit in among
+    /// `max_active_requests`.
+    pub riders: u64,
+    pub requests_fulfilled: u64,
+
+    /// Canceled, no-shows or rejected.
+    pub requests_unfulfilled: u64,
+
+    /// Average ticks a fulfilled `Request` waited until a `Taxi` was assigned.
+    pub mean_wait: f64,
+}
+
+/// How well a zone was served, either during one `hour` of the day or throughout the day.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ZoneSummary {
+    /// Zones are numbered row by row, starting at the origin of the city.
+    pub zone: usize,
+    pub hour: Option<u32>,
+    pub requests_fulfilled: u64,
+
+    /// Canceled, no-shows or rejected.
+    pub requests_unfulfilled: u64,
+    pub fulfillment_rate: f64,
+
+    /// Average ticks a fulfilled `Request` waited until a `Taxi` was assigned.
+    pub mean_wait: f64,
+
+    /// Hours `Taxi`s which weren't in maintenance spent in the zone.
+    pub supply_hours: f64,
+}
+
+/// How well one group of an A/B test served the `Request`s routed to it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GroupSummary {
+    /// "a" or "b".
+    pub group: String,
+    pub requests_fulfilled: u64,
+
+    /// Canceled, no-shows or rejected.
+    pub requests_unfulfilled: u64,
+    pub fulfillment_rate: f64,
+    pub mean_wait: f64,
+    pub mean_pickup_time: f64,
+
+    /// Average share of the `Taxi`s in the group which were occupied per tick.
+    pub utilization: f64,
+    pub revenue: f64,
+}
+
+impl Summary {
+    /// All metrics as `(name, value)` pairs in a stable order, e.g. for writing result files.
+    pub fn metrics(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("requests_spawned", self.requests_spawned as f64),
+            ("re_requests", self.re_requests as f64),
+            ("requests_priced_out", self.requests_priced_out as f64),
+            ("requests_fulfilled", self.requests_fulfilled as f64),
+            ("requests_canceled", self.requests_canceled as f64),
+            (
+                "requests_canceled_on_eta",
+                self.requests_canceled_on_eta as f64,
+            ),
+            ("requests_to_transit", self.requests_to_transit as f64),
+            ("requests_no_show", self.requests_no_show as f64),
+            ("requests_rejected", self.requests_rejected as f64),
+            ("no_show_rate", self.no_show_rate),
+            ("wasted_taxi_time", self.wasted_taxi_time as f64),
+            ("fulfillment_rate", self.fulfillment_rate),
+            ("mean_wait", self.mean_wait),
+            ("wait_p50", self.wait_p50),
+            ("wait_p90", self.wait_p90),
+            ("wait_p99", self.wait_p99),
+            ("mean_pickup_time", self.mean_pickup_time),
+            ("mean_stops", self.mean_stops),
+            ("mean_leg_time", self.mean_leg_time),
+            ("mean_quoted_eta", self.mean_quoted_eta),
+            ("mean_batch_size", self.mean_batch_size),
+            ("offers_sent", self.offers_sent as f64),
+            ("offer_acceptance_rate", self.offer_acceptance_rate),
+            ("offers_timed_out", self.offers_timed_out as f64),
+            ("requests_escalated", self.requests_escalated as f64),
+            ("taxi_utilization", self.taxi_utilization),
+            ("revenue", self.revenue),
+            ("mean_fare", self.mean_fare),
+            ("mean_surge_multiplier", self.mean_surge_multiplier),
+            ("fleet_costs", self.fleet_costs),
+            ("fleet_profit", self.fleet_profit),
+            ("profit_per_taxi", self.profit_per_taxi),
+            ("fleet_mileage", self.fleet_mileage),
+            ("cruising_mileage", self.cruising_mileage),
+            ("empty_mileage", self.empty_mileage),
+            ("earnings_stddev", self.earnings_stddev),
+            ("earnings_gini", self.earnings_gini),
+            ("maintenance_downtime", self.maintenance_downtime as f64),
+            ("effective_fleet_size", self.effective_fleet_size),
+            ("time_step_speedup", self.time_step_speedup),
+        ]
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |name: &str| match &self.units {
+            Some(units) => units.label(name),
+            None => name.to_string(),
+        };
+        let convert = |name: &str, value: f64| match &self.units {
+            Some(units) => units.convert(name, value),
+            None => value,
+        };
+        for (name, value) in self.metrics() {
+            writeln!(f, "{:<24} {:>12.4}", label(name), convert(name, value))?;
+        }
+        writeln!(
+            f,
+            "{:<24} {:>12} {:>12} {:>12} {:>12} {:>12}",
+            "party_size", "fulfilled", "canceled", "no_show", "rejected", "mean_wait"
+        )?;
+        for p in &self.by_party_size {
+            writeln!(
+                f,
+                "{:<24} {:>12} {:>12} {:>12} {:>12} {:>12.4}",
+                p.party_size,
+                p.requests_fulfilled,
+                p.requests_canceled,
+                p.requests_no_show,
+                p.requests_rejected,
+                p.mean_wait
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<24} {:>12} {:>12} {:>12} {:>12}",
+            "vehicle_type",
+            "utilization",
+            "trips",
+            label("mileage"),
+            label("costs")
+        )?;
+        for v in &self.by_vehicle_type {
+            writeln!(
+                f,
+                "{:<24} {:>12.4} {:>12} {:>12.4} {:>12.4}",
+                v.name,
+                v.utilization,
+                v.trips,
+                convert("mileage", v.mileage),
+                v.costs
+            )?;
+        }
+        if !self.by_venue.is_empty() {
+            writeln!(
+                f,
+                "{:<24} {:>12} {:>12} {:>12} {:>12}",
+                "venue", "riders", "fulfilled", "queue_time", "mean_wait"
+            )?;
+            for v in &self.by_venue {
+                writeln!(
+                    f,
+                    "{:<24} {:>12} {:>12} {:>12.4} {:>12.4}",
+                    v.name, v.riders, v.requests_fulfilled, v.mean_queue_time, v.mean_wait
+                )?;
+            }
+        }
+        if !self.by_layer.is_empty() {
+            writeln!(
+                f,
+                "{:<24} {:>12} {:>12} {:>12} {:>12}",
+                "layer", "riders", "fulfilled", "unfulfilled", "mean_wait"
+            )?;
+            for l in &self.by_layer {
+                writeln!(
+                    f,
+                    "{:<24} {:>12} {:>12} {:>12} {:>12.4}",
+                    l.name, l.riders, l.requests_fulfilled, l.requests_unfulfilled, l.mean_wait
+                )?;
+            }
+        }
+        if self.by_zone.len() > 1 {
+            writeln!(
+                f,
+                "{:<24} {:>12} {:>12} {:>12} {:>12}",
+                "zone", "fulfilled", "rate", "mean_wait", "supply_hours"
+            )?;
+            for z in &self.by_zone {
+                writeln!(
+                    f,
+                    "{:<24} {:>12} {:>12.4} {:>12.4} {:>12.4}",
+                    z.zone, z.requests_fulfilled, z.fulfillment_rate, z.mean_wait, z.supply_hours
+                )?;
+            }
+        }
+        if self.by_group.len() > 1 {
+            writeln!(
+                f,
+                "{:<24} {:>12} {:>12} {:>12} {:>12} {:>12} {:>12}",
+                "group",
+                "fulfilled",
+                "rate",
+                "mean_wait",
+                "pickup_time",
+                "utilization",
+                label("revenue")
+            )?;
+            for g in &self.by_group {
+                writeln!(
+                    f,
+                    "{:<24} {:>12} {:>12.4} {:>12.4} {:>12.4} {:>12.4} {:>12.4}",
+                    g.group,
+                    g.requests_fulfilled,
+                    g.fulfillment_rate,
+                    g.mean_wait,
+                    g.mean_pickup_time,
+                    g.utilization,
+                    g.revenue
+                )?;
+            }
+        }
+        match &self.units {
+            Some(units) => {
+                writeln!(f, "{}", units.label("trip_distance"))?;
+                write!(
+                    f,
+                    "{}",
+                    self.trip_distances.scaled(units.distance.from_km(1.0))
+                )?;
+            }
+            None => {
+                writeln!(f, "trip_distance (km)")?;
+                write!(f, "{}", self.trip_distances)?;
+            }
+        }
+        writeln!(f, "trip_duration (ticks)")?;
+        write!(f, "{}", self.trip_durations)
+    }
+}
+
+pub fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Gini coefficient of non-negative `values`. Zero if they're all zero or there are none.
+pub fn gini(values: &[f64]) -> f64 {
+    let total: f64 = values.iter().sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).expect("Values are never NaN."));
+    let n = sorted.len() as f64;
+    let weighted: f64 = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (i + 1) as f64 * v)
+        .sum();
+    2.0 * weighted / (n * total) - (n + 1.0) / n
+}
+
+/// Unbiased sample variance. Zero for fewer than two values.
+pub fn sample_variance(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let m = mean(values);
+    values.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (values.len() - 1) as f64
+}
+
+/// Two-sided p-value of Welch's t-test for the means of `a` and `b` being equal.
+///
+/// `None` if either side has fewer than two samples or neither side varies at all, in which
+/// case the test can't say anything.
+pub fn welch_t_test(a: &[f64], b: &[f64]) -> Option<f64> {
+    if a.len() < 2 || b.len() < 2 {
+        return None;
+    }
+    let se_a = sample_variance(a) / a.len() as f64;
+    let se_b = sample_variance(b) / b.len() as f64;
+    let se = se_a + se_b;
+    if se == 0.0 {
+        return None;
+    }
+
+    let t = (mean(a) - mean(b)) / se.sqrt();
+    let df =
+        se.powi(2) / (se_a.powi(2) / (a.len() - 1) as f64 + se_b.powi(2) / (b.len() - 1) as f64);
+
+    // The two-sided tail of Student's t distribution expressed via the regularized incomplete
+    // beta function.
+    Some(regularized_incomplete_beta(
+        df / 2.0,
+        0.5,
+        df / (df + t * t),
+    ))
+}
+
+/// `I_x(a, b)`, evaluated with the continued fraction from Numerical Recipes.
+fn regularized_incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+
+    let ln_front = ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln();
+    let front = ln_front.exp();
+
+    // The continued fraction converges quickly only on one side of the mean, so use the
+    // symmetry `I_x(a, b) = 1 - I_{1-x}(b, a)` on the other one.
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: u32 = 300;
+    const EPSILON: f64 = 1e-14;
+    const TINY: f64 = 1e-300;
+
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+
+    for m in 1..=MAX_ITERATIONS {
+        let m = f64::from(m);
+
+        // Even step.
+        let numerator = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 + numerator * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + numerator / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        h *= d * c;
+
+        // Odd step.
+        let numerator = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 + numerator * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + numerator / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    h
+}
+
+/// Lanczos approximation of `ln(Γ(x))` for `x > 0`.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000_000_000_190_015;
+    for (i, c) in COEFFICIENTS.iter().enumerate() {
+        series += c / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} isn't {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn welch_t_test_matches_student_t_with_equal_variances() {
+        // Two samples of two with the same variance have 2 degrees of freedom, where the
+        // two-sided p-value is `1 - |t| / sqrt(2 + t²)`. Here `t = -√2`.
+        let p = welch_t_test(&[0.0, 2.0], &[2.0, 4.0]).unwrap();
+        assert_close(p, 1.0 - 0.5_f64.sqrt());
+
+        // Three and three have 4, where `t = 1.5` has a p-value of exactly 0.208.
+        let spread = 1.5 * (2.0_f64 / 3.0).sqrt();
+        let p = welch_t_test(&[0.0, 1.0, 2.0], &[-spread, 1.0 - spread, 2.0 - spread]).unwrap();
+        assert_close(p, 0.208);
+    }
+
+    #[test]
+    fn welch_t_test_with_one_constant_side() {
+        // Only `a` varies, so there's 1 degree of freedom, where the two-sided p-value is
+        // `1 - 2 atan(|t|) / π`. Here `t = -2`.
+        let p = welch_t_test(&[0.0, 2.0], &[3.0, 3.0]).unwrap();
+        assert_close(p, 1.0 - 2.0 * 2.0_f64.atan() / std::f64::consts::PI);
+    }
+
+    #[test]
+    fn welch_t_test_of_equal_means_is_one() {
+        assert_close(welch_t_test(&[1.0, 3.0], &[0.0, 4.0]).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn gini_of_equal_earnings_is_zero() {
+        assert_close(gini(&[5.0; 10]), 0.0);
+        assert_close(gini(&[0.0; 10]), 0.0);
+        assert_close(gini(&[]), 0.0);
+    }
+
+    #[test]
+    fn gini_of_a_single_earner_is_one_minus_one_over_n() {
+        for n in [1, 2, 10, 200] {
+            let mut earnings = vec![0.0; n];
+            earnings[n / 2] = 42.0;
+            assert_close(gini(&earnings), 1.0 - 1.0 / n as f64);
+        }
+    }
+
+    #[test]
+    fn welch_t_test_needs_samples_which_vary() {
+        assert_eq!(welch_t_test(&[1.0, 1.0], &[2.0, 2.0]), None);
+        assert_eq!(welch_t_test(&[1.0], &[2.0, 3.0]), None);
+        assert_eq!(welch_t_test(&[], &[]), None);
+    }
+}
@@ -0,0 +1,196 @@
+//! Simulated wall-clock time, so that reports can say "07:35 Tuesday" rather than "tick 27300"
+//! and demand can follow the hours of the day and the days of the week.
+//!
+//! Every tick is a second. A run starts at midnight on a Monday unless its `Scenario` says
+//! otherwise. Durations stay plain ticks.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub const SECONDS_PER_MINUTE: u64 = 60;
+pub const SECONDS_PER_HOUR: u64 = 60 * SECONDS_PER_MINUTE;
+pub const SECONDS_PER_DAY: u64 = 24 * SECONDS_PER_HOUR;
+
+/// Ticks in `minutes`, which may be fractional.
+pub fn minutes_as_ticks(minutes: f64) -> f64 {
+    minutes * SECONDS_PER_MINUTE as f64
+}
+
+/// `minutes` rounded to whole ticks. Saturates at 0 for negative durations and at `u64::MAX`
+/// for infinite ones.
+pub fn minutes_to_ticks(minutes: f64) -> u64 {
+    minutes_as_ticks(minutes).round() as u64
+}
+
+/// Minutes in `ticks`.
+pub fn ticks_as_minutes(ticks: f64) -> f64 {
+    ticks / SECONDS_PER_MINUTE as f64
+}
+
+/// Hours in `ticks`.
+pub fn ticks_as_hours(ticks: f64) -> f64 {
+    ticks / SECONDS_PER_HOUR as f64
+}
+
+/// A rate per hour, e.g. a speed in km/h, per tick.
+pub fn per_tick(per_hour: f64) -> f64 {
+    per_hour / SECONDS_PER_HOUR as f64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    /// Monday first.
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+        Weekday::Sunday,
+    ];
+
+    /// 0 for Monday up to 6 for Sunday.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Weekday {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Weekday::Monday => "Monday",
+            Weekday::Tuesday => "Tuesday",
+            Weekday::Wednesday => "Wednesday",
+            Weekday::Thursday => "Thursday",
+            Weekday::Friday => "Friday",
+            Weekday::Saturday => "Saturday",
+            Weekday::Sunday => "Sunday",
+        };
+        f.write_str(name)
+    }
+}
+
+/// When a run starts, as configured in the `[start]` section of a `Scenario`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Start {
+    pub weekday: Weekday,
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl Default for Start {
+    fn default() -> Start {
+        Start {
+            weekday: Weekday::Monday,
+            hour: 0,
+            minute: 0,
+        }
+    }
+}
+
+/// A moment in simulated time, in seconds since midnight on the Monday of the week the run
+/// starts in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SimTime(u64);
+
+impl SimTime {
+    pub fn from_seconds(seconds: u64) -> SimTime {
+        SimTime(seconds)
+    }
+
+    pub fn seconds(self) -> u64 {
+        self.0
+    }
+
+    /// `ticks` later.
+    pub fn after(self, ticks: u64) -> SimTime {
+        SimTime(self.0 + ticks)
+    }
+
+    /// Whole hours since midnight on the first Monday, e.g. to index hourly profiles.
+    pub fn hours(self) -> u64 {
+        self.0 / SECONDS_PER_HOUR
+    }
+
+    /// 0 up to 23.
+    pub fn hour_of_day(self) -> u32 {
+        (self.hours() % 24) as u32
+    }
+
+    /// 0 up to 59.
+    pub fn minute_of_hour(self) -> u32 {
+        (self.0 / SECONDS_PER_MINUTE % 60) as u32
+    }
+
+    pub fn weekday(self) -> Weekday {
+        Weekday::ALL[(self.0 / SECONDS_PER_DAY % 7) as usize]
+    }
+}
+
+impl fmt::Display for SimTime {
+    /// E.g. "07:35 Tuesday".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02} {}",
+            self.hour_of_day(),
+            self.minute_of_hour(),
+            self.weekday()
+        )
+    }
+}
+
+/// Turns the ticks of a run into `SimTime`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Clock {
+    /// When tick 0 is.
+    start: SimTime,
+}
+
+impl Clock {
+    pub fn new(start: Start) -> Clock {
+        let seconds = start.weekday.index() as u64 * SECONDS_PER_DAY
+            + u64::from(start.hour) * SECONDS_PER_HOUR
+            + u64::from(start.minute) * SECONDS_PER_MINUTE;
+        Clock {
+            start: SimTime(seconds),
+        }
+    }
+
+    pub fn at(self, tick: u64) -> SimTime {
+        self.start.after(tick)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minutes_round_to_whole_ticks() {
+        assert_eq!(minutes_to_ticks(1.5), 90);
+        assert_eq!(minutes_to_ticks(0.001), 0);
+        assert_eq!(minutes_to_ticks(0.01), 1);
+    }
+
+    #[test]
+    fn minutes_to_ticks_saturates() {
+        assert_eq!(minutes_to_ticks(f64::INFINITY), u64::MAX);
+        assert_eq!(minutes_to_ticks(-3.0), 0);
+    }
+}
@@ -0,0 +1,337 @@
+//! Public transit as an alternative to a `Taxi`, so that riders can switch modes when taxis
+//! are too expensive or too far away.
+//!
+//! Once a rider is quoted a pickup ETA, they compare the taxi with transit by fare plus the
+//! time it takes them, valued at `value_of_time`, and take whichever is cheaper. Transit
+//! either takes a constant time plus the distance at a constant speed, or the fastest direct
+//! trip of a GTFS feed. Riders go by taxi if there's no such trip.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::position::Position;
+use crate::time::{
+    minutes_to_ticks, per_tick, ticks_as_minutes, SimTime, SECONDS_PER_DAY, SECONDS_PER_HOUR,
+    SECONDS_PER_MINUTE,
+};
+
+/// The transit alternative, as configured in the `[transit]` section of a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transit {
+    pub fare: f64,
+
+    /// What riders would pay to save a minute, by taxi or by transit.
+    pub value_of_time: f64,
+    pub network: Network,
+}
+
+/// How long transit takes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Network {
+    /// `wait` minutes for walking and waiting, and then the straight distance at `speed` km/h.
+    Constant { wait: f64, speed: f64 },
+
+    /// The trips in the unzipped GTFS feed at `feed`, from the nearest stops within
+    /// `max_walk` km walked at `walking_speed` km/h. Only `stops.txt` and `stop_times.txt`
+    /// are read, and every trip runs on every day. The south-west corner of all stops is the
+    /// origin of the city.
+    Gtfs {
+        feed: PathBuf,
+        walking_speed: f64,
+        max_walk: f64,
+    },
+}
+
+impl Transit {
+    /// Why the parameters don't make up an alternative, if they don't. Tries to load a GTFS
+    /// feed.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+        let non_negative = |x: f64| x >= 0.0 && x.is_finite();
+        let positive = |x: f64| x > 0.0 && x.is_finite();
+        check(
+            non_negative(self.fare) && non_negative(self.value_of_time),
+            "transit fare and value_of_time must not be negative",
+        );
+        match &self.network {
+            Network::Constant { wait, speed } => {
+                check(non_negative(*wait), "transit wait must not be negative");
+                check(positive(*speed), "transit speed must be positive");
+            }
+            Network::Gtfs {
+                feed,
+                walking_speed,
+                max_walk,
+            } => {
+                check(
+                    positive(*walking_speed),
+                    "transit walking_speed must be positive",
+                );
+                check(
+                    non_negative(*max_walk),
+                    "transit max_walk must not be negative",
+                );
+                if let Err(e) = Timetable::load(feed) {
+                    check(
+                        false,
+                        &format!("transit feed {} can't be read: {}", feed.display(), e),
+                    );
+                }
+            }
+        }
+        problems
+    }
+}
+
+impl Transit {
+    /// Whether a rider rather takes a transit `journey` of so many ticks than paying `fare` for
+    /// a taxi which gets them there in `taxi_ticks`.
+    pub fn is_preferred(&self, fare: f64, taxi_ticks: f64, journey: u64) -> bool {
+        let cost = |fare: f64, ticks: f64| fare + self.value_of_time * ticks_as_minutes(ticks);
+        cost(self.fare, journey as f64) < cost(fare, taxi_ticks)
+    }
+}
+
+/// A stop on a trip, by index into `Timetable::stops`, with its times in seconds after
+/// midnight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct StopTime {
+    stop: usize,
+    arrival: u64,
+    departure: u64,
+}
+
+/// The stops and trips of a GTFS feed.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Timetable {
+    stops: Vec<Position>,
+
+    /// The stops of every trip in order.
+    trips: Vec<Vec<StopTime>>,
+
+    /// Every trip which calls at a stop, with where in the trip it does, by stop.
+    calls: Vec<Vec<(usize, usize)>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StopRow {
+    stop_id: String,
+    stop_lat: Option<f64>,
+    stop_lon: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StopTimeRow {
+    trip_id: String,
+    arrival_time: String,
+    departure_time: String,
+    stop_id: String,
+    stop_sequence: u32,
+}
+
+/// Seconds after midnight of a GTFS time like "25:10:00", which is past midnight of the next
+/// day.
+fn parse_time(time: &str) -> Option<u64> {
+    let mut parts = time.trim().splitn(3, ':').map(|p| p.parse::<u64>().ok());
+    let (h, m, s) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(h * SECONDS_PER_HOUR + m * SECONDS_PER_MINUTE + s)
+}
+
+impl Timetable {
+    /// Reads `stops.txt` and `stop_times.txt` from the directory `feed`.
+    pub fn load(feed: &Path) -> io::Result<Timetable> {
+        let invalid = |e: csv::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+
+        let mut ids = HashMap::new();
+        let mut coordinates = vec![];
+        for row in csv::Reader::from_path(feed.join("stops.txt"))
+            .map_err(invalid)?
+            .deserialize()
+        {
+            let row: StopRow = row.map_err(invalid)?;
+            // Entrances and the like may have no coordinates of their own.
+            if let (Some(lat), Some(lon)) = (row.stop_lat, row.stop_lon) {
+                ids.insert(row.stop_id, coordinates.len());
+                coordinates.push((lat, lon));
+            }
+        }
+        if coordinates.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the feed has no stops",
+            ));
+        }
+
+        // Equirectangular projection, which is good enough at the scale of a city.
+        let min_lat = coordinates
+            .iter()
+            .map(|c| c.0)
+            .fold(f64::INFINITY, f64::min);
+        let min_lon = coordinates
+            .iter()
+            .map(|c| c.1)
+            .fold(f64::INFINITY, f64::min);
+        let mean_lat = coordinates.iter().map(|c| c.0).sum::<f64>() / coordinates.len() as f64;
+        let km_per_degree = 111.32;
+        let stops = coordinates
+            .iter()
+            .map(|&(lat, lon)| {
+                Position::new(
+                    (lon - min_lon) * km_per_degree * mean_lat.to_radians().cos(),
+                    (lat - min_lat) * km_per_degree,
+                )
+            })
+            .collect();
+
+        let mut by_trip: HashMap<String, Vec<(u32, StopTime)>> = HashMap::new();
+        for row in csv::Reader::from_path(feed.join("stop_times.txt"))
+            .map_err(invalid)?
+            .deserialize()
+        {
+            let row: StopTimeRow = row.map_err(invalid)?;
+            let stop = match ids.get(&row.stop_id) {
+                Some(&stop) => stop,
+                None => continue,
+            };
+            // Stops without times are timed by interpolation, which isn't worth it here.
+            let (arrival, departure) = match (
+                parse_time(&row.arrival_time),
+                parse_time(&row.departure_time),
+            ) {
+                (Some(a), Some(d)) => (a, d),
+                _ => continue,
+            };
+            by_trip.entry(row.trip_id).or_default().push((
+                row.stop_sequence,
+                StopTime {
+                    stop,
+                    arrival,
+                    departure,
+                },
+            ));
+        }
+        let mut trip_ids: Vec<String> = by_trip.keys().cloned().collect();
+        trip_ids.sort();
+
+        let mut trips = vec![];
+        let mut calls = vec![vec![]; ids.len()];
+        for id in trip_ids {
+            let mut stop_times = by_trip.remove(&id).expect("Every trip has stop times.");
+            stop_times.sort_by_key(|&(sequence, _)| sequence);
+            for (i, (_, stop_time)) in stop_times.iter().enumerate() {
+                calls[stop_time.stop].push((trips.len(), i));
+            }
+            trips.push(stop_times.into_iter().map(|(_, s)| s).collect());
+        }
+        Ok(Timetable {
+            stops,
+            trips,
+            calls,
+        })
+    }
+}
+
+/// A `Network` ready to plan journeys, with its feed loaded.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TransitNetwork {
+    Constant {
+        wait: u64,
+
+        /// Km per tick.
+        speed: f64,
+    },
+    Timetable {
+        timetable: Timetable,
+
+        /// Km per tick.
+        walking_speed: f64,
+        max_walk: f64,
+    },
+}
+
+impl TransitNetwork {
+    /// Loads the feed of a GTFS `network`. Fails if it can't be read.
+    pub fn new(network: &Network) -> io::Result<TransitNetwork> {
+        Ok(match network {
+            Network::Constant { wait, speed } => TransitNetwork::Constant {
+                wait: minutes_to_ticks(*wait),
+                speed: per_tick(*speed),
+            },
+            Network::Gtfs {
+                feed,
+                walking_speed,
+                max_walk,
+            } => TransitNetwork::Timetable {
+                timetable: Timetable::load(feed).map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!("transit feed {} can't be read: {}", feed.display(), e),
+                    )
+                })?,
+                walking_speed: per_tick(*walking_speed),
+                max_walk: *max_walk,
+            },
+        })
+    }
+
+    /// Ticks from leaving `from` at `time` to arriving at `to`, if transit goes there.
+    pub fn journey(&self, from: Position, to: Position, time: SimTime) -> Option<u64> {
+        let (timetable, walking_speed, max_walk) = match self {
+            TransitNetwork::Constant { wait, speed } => {
+                return Some(wait + (from.distance(to) / speed).round() as u64)
+            }
+            TransitNetwork::Timetable {
+                timetable,
+                walking_speed,
+                max_walk,
+            } => (timetable, *walking_speed, *max_walk),
+        };
+        let walks = |position: Position| {
+            timetable
+                .stops
+                .iter()
+                .enumerate()
+                .map(move |(stop, &s)| (stop, s.distance(position)))
+                .filter(move |&(_, km)| km <= max_walk)
+                .map(move |(stop, km)| (stop, (km / walking_speed).round() as u64))
+        };
+        let egress: HashMap<usize, u64> = walks(to).collect();
+        if egress.is_empty() {
+            return None;
+        }
+
+        let now = time.seconds() % SECONDS_PER_DAY;
+        let mut fastest = None;
+        for (stop, access) in walks(from) {
+            for &(trip, i) in &timetable.calls[stop] {
+                let stop_times = &timetable.trips[trip];
+                if stop_times[i].departure < now + access {
+                    continue;
+                }
+                for later in &stop_times[i + 1..] {
+                    // Feeds don't promise that times go up along a trip.
+                    if later.arrival < stop_times[i].departure {
+                        continue;
+                    }
+                    if let Some(&walk) = egress.get(&later.stop) {
+                        let journey = later.arrival + walk - now;
+                        fastest = Some(fastest.map_or(journey, |f: u64| f.min(journey)));
+                    }
+                }
+            }
+        }
+        fastest
+    }
+}
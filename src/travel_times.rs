@@ -0,0 +1,183 @@
+//! Travel times between the cells of a grid over the city, worked out once up front so that
+//! ETAs are lookups rather than walks along the straight line through every speed limit.
+//!
+//! The matrix holds the pace, in ticks per km, of driving from the center of one cell to the
+//! center of another at the top speed of every vehicle type. A trip takes its distance at the
+//! pace between the cells it starts and ends in, which is exact without speed limits and close
+//! to it with a grid which is fine compared to the limited areas. Working out a fine grid takes
+//! a while, so the matrix can be cached on disk for every run of the same city and fleet.
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::position::Position;
+use crate::roads::{Roads, SpeedLimit};
+
+/// Most cells per side of the grid, which keeps the matrix within a few MB per vehicle type.
+pub const MAX_CELLS: u32 = 32;
+
+/// The travel time matrix, as configured in the `[travel_times]` section of a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TravelTimes {
+    /// Cells per side of the grid.
+    pub cells: u32,
+
+    /// File the matrix is read from if it was worked out for the same city and fleet before,
+    /// and written to otherwise.
+    pub cache: Option<PathBuf>,
+}
+
+impl TravelTimes {
+    /// Why the parameters don't make up a grid, if they don't.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if !(1..=MAX_CELLS).contains(&self.cells) {
+            problems.push(format!(
+                "travel_times.cells must be between 1 and {}",
+                MAX_CELLS
+            ));
+        }
+        problems
+    }
+}
+
+/// Everything a matrix depends on, so that a cached one is only used for the same city.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Key {
+    cells: u32,
+    city_size: f64,
+    speed_limits: Vec<SpeedLimit>,
+
+    /// Top speeds in km per tick, ascending.
+    speeds: Vec<f64>,
+}
+
+/// Paces between all cells for every top speed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TravelTimeMatrix {
+    key: Key,
+
+    /// By top speed, then by the cell a trip starts in, then by the cell it ends in.
+    paces: Vec<f64>,
+}
+
+impl TravelTimeMatrix {
+    /// Works out the matrix for `Taxi`s with top `speeds` in km per tick, or reads it from the
+    /// cache if that's for the same city and fleet. Fails if the cache can't be written.
+    pub fn new(
+        travel_times: &TravelTimes,
+        city_size: f64,
+        speed_limits: &[SpeedLimit],
+        speeds: &[f64],
+    ) -> io::Result<TravelTimeMatrix> {
+        let mut speeds = speeds.to_vec();
+        speeds.sort_by(|a, b| a.partial_cmp(b).expect("Speeds are never NaN."));
+        speeds.dedup();
+        let key = Key {
+            cells: travel_times.cells,
+            city_size,
+            speed_limits: speed_limits.to_vec(),
+            speeds,
+        };
+        let cache = match &travel_times.cache {
+            Some(cache) => cache,
+            None => return Ok(TravelTimeMatrix::compute(key)),
+        };
+        // A cache which can't be read or is for another city is just worked out again.
+        if let Some(matrix) = TravelTimeMatrix::read(cache).filter(|m| m.key == key) {
+            return Ok(matrix);
+        }
+        let matrix = TravelTimeMatrix::compute(key);
+        let unwritable = |e: io::Error| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "travel time cache {} can't be written: {}",
+                    cache.display(),
+                    e
+                ),
+            )
+        };
+        let file = File::create(cache).map_err(unwritable)?;
+        bincode::serialize_into(BufWriter::new(file), &matrix)
+            .map_err(|e| unwritable(io::Error::other(e)))?;
+        Ok(matrix)
+    }
+
+    fn read(path: &Path) -> Option<TravelTimeMatrix> {
+        let file = File::open(path).ok()?;
+        bincode::deserialize_from(BufReader::new(file)).ok()
+    }
+
+    fn compute(key: Key) -> TravelTimeMatrix {
+        let roads = Roads::new(&key.speed_limits);
+        let per_side = key.cells as usize;
+        let cell_size = key.city_size / per_side as f64;
+        let centers: Vec<Position> = (0..per_side * per_side)
+            .map(|cell| {
+                Position::new(
+                    ((cell % per_side) as f64 + 0.5) * cell_size,
+                    ((cell / per_side) as f64 + 0.5) * cell_size,
+                )
+            })
+            .collect();
+        let mut paces = Vec::with_capacity(key.speeds.len() * centers.len() * centers.len());
+        for &speed in &key.speeds {
+            for &from in &centers {
+                for &to in &centers {
+                    let distance = from.distance(to);
+                    paces.push(if distance > 0.0 {
+                        roads.cruise_time(from, to, speed, 1.0) / distance
+                    } else {
+                        // Trips within a cell go at whatever limit applies at its center.
+                        roads.limit_at(from).map_or(speed, |l| l.min(speed)).recip()
+                    });
+                }
+            }
+        }
+        TravelTimeMatrix { key, paces }
+    }
+
+    /// The cell `position` is in. Those outside the city are in the nearest cell.
+    fn cell(&self, position: Position) -> usize {
+        let per_side = self.key.cells as usize;
+        let index = |c: f64| {
+            if self.key.city_size > 0.0 {
+                ((c / self.key.city_size * per_side as f64) as usize).min(per_side - 1)
+            } else {
+                0
+            }
+        };
+        index(position.y) * per_side + index(position.x)
+    }
+
+    /// Ticks it takes to drive from `from` to `to` at `top_speed` km per tick, both times
+    /// `congestion`, if the matrix covers that speed.
+    pub fn cruise_time(
+        &self,
+        from: Position,
+        to: Position,
+        top_speed: f64,
+        congestion: f64,
+    ) -> Option<f64> {
+        let speed = self.key.speeds.iter().position(|&s| s == top_speed)?;
+        let distance = from.distance(to);
+        if distance == 0.0 {
+            return Some(0.0);
+        }
+        if congestion <= 0.0 {
+            return Some(f64::INFINITY);
+        }
+        let cells = (self.key.cells * self.key.cells) as usize;
+        let pace = self.paces[(speed * cells + self.cell(from)) * cells + self.cell(to)];
+        Some(distance * pace / congestion)
+    }
+
+    /// Whether the matrix was worked out for `roads`, rather than for other speed limits.
+    pub fn is_for(&self, roads: &Roads) -> bool {
+        Roads::new(&self.key.speed_limits) == *roads
+    }
+}
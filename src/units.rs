@@ -0,0 +1,136 @@
+//! Units which reports are written in, e.g. miles and dollars rather than kilometers.
+//!
+//! The simulation itself always works in km, ticks and whatever currency the `Pricing` is in.
+//! `Units` only change how distances are shown, what currency amounts of money are labeled
+//! with and how times of day are written, in the summary, exports and animations alike.
+//! Metrics keep their names, e.g. for `optimize` targets, and are only labeled with their
+//! unit in reports.
+
+use serde::{Deserialize, Serialize};
+
+use crate::time::SimTime;
+
+const KM_PER_MILE: f64 = 1.609_344;
+
+/// Metrics of a `Summary`, and columns of its breakdowns, which are distances.
+const DISTANCE_METRICS: [&str; 5] = [
+    "fleet_mileage",
+    "cruising_mileage",
+    "empty_mileage",
+    "mileage",
+    "trip_distance",
+];
+
+/// Metrics of a `Summary`, and columns of its breakdowns, which are amounts of money.
+const MONEY_METRICS: [&str; 8] = [
+    "revenue",
+    "mean_fare",
+    "fleet_costs",
+    "fleet_profit",
+    "profit_per_taxi",
+    "earnings_stddev",
+    "costs",
+    "profit",
+];
+
+/// The units of reports, as configured in the `[units]` section of a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Units {
+    pub distance: DistanceUnit,
+
+    /// Code or symbol amounts of money are labeled with, e.g. "EUR" or "$". They're left
+    /// unlabeled if this is missing.
+    pub currency: Option<String>,
+
+    pub clock: ClockFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceUnit {
+    #[default]
+    Km,
+    Miles,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ClockFormat {
+    /// E.g. "19:05".
+    #[default]
+    #[serde(rename = "24h")]
+    TwentyFourHour,
+
+    /// E.g. "7:05 PM".
+    #[serde(rename = "12h")]
+    TwelveHour,
+}
+
+impl DistanceUnit {
+    pub fn abbreviation(self) -> &'static str {
+        match self {
+            DistanceUnit::Km => "km",
+            DistanceUnit::Miles => "mi",
+        }
+    }
+
+    /// How many of this unit `km` kilometers are.
+    pub fn from_km(self, km: f64) -> f64 {
+        match self {
+            DistanceUnit::Km => km,
+            DistanceUnit::Miles => km / KM_PER_MILE,
+        }
+    }
+
+    /// How many kilometers one of this unit is.
+    pub fn km(self) -> f64 {
+        self.from_km(1.0).recip()
+    }
+}
+
+impl Units {
+    /// What `metric` is measured in, if it's a distance or an amount of money in a known
+    /// currency.
+    pub fn unit_of(&self, metric: &str) -> Option<&str> {
+        if DISTANCE_METRICS.contains(&metric) {
+            Some(self.distance.abbreviation())
+        } else if MONEY_METRICS.contains(&metric) {
+            self.currency.as_deref()
+        } else {
+            None
+        }
+    }
+
+    /// `metric` along with its unit, e.g. "fleet_mileage (mi)".
+    pub fn label(&self, metric: &str) -> String {
+        match self.unit_of(metric) {
+            Some(unit) => format!("{} ({})", metric, unit),
+            None => metric.to_string(),
+        }
+    }
+
+    /// `value` of `metric` in these units, given in km if it's a distance.
+    pub fn convert(&self, metric: &str, value: f64) -> f64 {
+        if DISTANCE_METRICS.contains(&metric) {
+            self.distance.from_km(value)
+        } else {
+            value
+        }
+    }
+
+    /// E.g. "19:05 Tuesday" or "7:05 PM Tuesday".
+    pub fn time_of_day(&self, time: SimTime) -> String {
+        let (hour, minute) = (time.hour_of_day(), time.minute_of_hour());
+        match self.clock {
+            ClockFormat::TwentyFourHour => format!("{:02}:{:02} {}", hour, minute, time.weekday()),
+            ClockFormat::TwelveHour => {
+                let period = if hour < 12 { "AM" } else { "PM" };
+                let hour = match hour % 12 {
+                    0 => 12,
+                    hour => hour,
+                };
+                format!("{}:{:02} {} {}", hour, minute, period, time.weekday())
+            }
+        }
+    }
+}
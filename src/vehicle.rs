@@ -0,0 +1,68 @@
+//! Mixed fleets of bikes, cars, vans, shuttles or whatever else a `Scenario` comes up with.
+
+use serde::{Deserialize, Serialize};
+
+/// Most vehicle types a fleet can have, so that `VehicleTypes` fit into a bit set.
+pub const MAX_VEHICLE_TYPES: usize = 64;
+
+/// A kind of vehicle in the fleet, as configured in the `[[vehicle_types]]` sections of a
+/// `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VehicleType {
+    pub name: String,
+
+    /// How many vehicles of this type the `World` starts out with.
+    pub count: u32,
+
+    /// How many passengers fit in.
+    pub capacity: u32,
+
+    /// How fast they drive in km/h, at most. Speed limits may slow them down further.
+    pub speed: f64,
+
+    /// How fast they speed up and brake in m/s². Instantly if missing.
+    pub acceleration: Option<f64>,
+
+    /// Fuel or energy per kilometer driven. Falls back to `costs.per_km` if missing.
+    pub cost_per_km: Option<f64>,
+}
+
+/// Share of riders who only accept some vehicle types, as configured in the
+/// `[[vehicle_preferences]]` sections of a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VehiclePreference {
+    /// Names of the acceptable vehicle types. Any type will do if this is empty.
+    #[serde(default)]
+    pub types: Vec<String>,
+
+    /// Relative frequency among all preferences.
+    pub weight: f64,
+}
+
+/// A set of vehicle types by their index in the fleet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VehicleTypes(u64);
+
+impl VehicleTypes {
+    pub const ALL: VehicleTypes = VehicleTypes(u64::MAX);
+
+    /// The types of `fleet` named in `preference`. Unknown names are ignored.
+    pub fn of(preference: &VehiclePreference, fleet: &[VehicleType]) -> VehicleTypes {
+        if preference.types.is_empty() {
+            return VehicleTypes::ALL;
+        }
+        let bits = fleet
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| preference.types.contains(&t.name))
+            .fold(0, |bits, (i, _)| bits | 1 << i);
+        VehicleTypes(bits)
+    }
+
+    pub fn contains(self, index: usize) -> bool {
+        index < MAX_VEHICLE_TYPES && self.0 & 1 << index != 0
+    }
+}
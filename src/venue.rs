@@ -0,0 +1,82 @@
+//! Airports, stadiums and other venues which release bursts of riders on top of the background
+//! demand, e.g. when a flight lands or a concert ends.
+//!
+//! Riders only fit onto the curb of a venue up to its capacity. Everybody else queues inside
+//! until a rider at the curb got picked up or gave up.
+
+use serde::{Deserialize, Serialize};
+
+use crate::position::Position;
+
+/// A place where lots of riders come out at once, as configured in the `[[venues]]` sections of
+/// a `Scenario`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Venue {
+    pub name: String,
+    pub position: Position,
+
+    /// Pickups are uniformly spread within this many km of `position`.
+    #[serde(default)]
+    pub radius: f64,
+
+    /// How many riders can wait for a `Taxi` at the curb at once.
+    pub capacity: u32,
+
+    pub bursts: Vec<Burst>,
+}
+
+/// `size` riders coming out of a `Venue` evenly spread over `duration` ticks starting at tick
+/// `at`, repeating `every` so many ticks if that's given.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Burst {
+    pub at: u64,
+    pub size: u32,
+
+    /// All riders come out at once if this is 0.
+    #[serde(default)]
+    pub duration: u64,
+
+    pub every: Option<u64>,
+}
+
+impl Burst {
+    /// How far into its current repetition the `Burst` is at `tick`, if it started yet.
+    fn offset(&self, tick: u64) -> Option<u64> {
+        let offset = tick.checked_sub(self.at)?;
+        Some(match self.every {
+            Some(every) => offset % every,
+            None => offset,
+        })
+    }
+
+    /// Ticks during which riders may come out.
+    fn length(&self) -> u64 {
+        self.duration.max(1)
+    }
+
+    /// How many riders come out during `tick`.
+    pub fn riders_at(&self, tick: u64) -> u32 {
+        let offset = match self.offset(tick) {
+            Some(offset) if offset < self.length() => offset,
+            _ => return 0,
+        };
+        let released = |ticks: u64| ticks * u64::from(self.size) / self.length();
+        (released(offset + 1) - released(offset)) as u32
+    }
+
+    /// The first tick after `tick` during which riders may come out, if there is any.
+    pub fn next_after(&self, tick: u64) -> Option<u64> {
+        let next = tick + 1;
+        let offset = match self.offset(next) {
+            Some(offset) => offset,
+            None => return Some(self.at),
+        };
+        if offset < self.length() {
+            Some(next)
+        } else {
+            self.every.map(|every| next + every - offset)
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Metrics over the last few simulated minutes of a run, e.g. for dashboards or strategies
+//! which react to recent demand.
+//!
+//! A `RollingWindow` keeps one bucket of counters per minute in a ring buffer, so keeping it up
+//! to date and summarizing it never depends on how many `Request`s were archived.
+
+use std::collections::VecDeque;
+
+use crate::time::SECONDS_PER_MINUTE;
+use crate::world::{Request, RequestOutcome, Taxi};
+
+/// Minutes a `RollingWindow` covers if the `Scenario` doesn't say.
+pub const DEFAULT_WINDOW_MINUTES: u64 = 15;
+
+/// What happened during one minute of the run.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Bucket {
+    requests_spawned: u64,
+    requests_fulfilled: u64,
+
+    /// Canceled, no-shows or rejected.
+    requests_unfulfilled: u64,
+    total_fulfilled_wait: u64,
+    revenue: f64,
+    occupied_taxi_ticks: u64,
+    taxi_ticks: u64,
+    ticks: u64,
+}
+
+/// Counters of the last `minutes` minutes of a run, by minute.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RollingWindow {
+    minutes: u64,
+
+    /// Oldest first. The last one is for `minute`.
+    buckets: VecDeque<Bucket>,
+
+    /// Minute of the run the last of the `buckets` is for.
+    minute: u64,
+}
+
+impl RollingWindow {
+    pub fn new(minutes: u64) -> RollingWindow {
+        RollingWindow {
+            minutes,
+            buckets: VecDeque::from(vec![Bucket::default()]),
+            minute: 0,
+        }
+    }
+
+    /// The bucket of the minute `tick` falls into, dropping those which fell out of the window
+    /// since.
+    fn bucket(&mut self, tick: u64) -> &mut Bucket {
+        let minute = tick / SECONDS_PER_MINUTE;
+        if minute > self.minute {
+            let new = (minute - self.minute).min(self.minutes);
+            for _ in 0..new {
+                self.buckets.push_back(Bucket::default());
+            }
+            while self.buckets.len() as u64 > self.minutes {
+                self.buckets.pop_front();
+            }
+            self.minute = minute;
+        }
+        self.buckets
+            .back_mut()
+            .expect("A window always has a bucket.")
+    }
+
+    pub(crate) fn record_spawned(&mut self, tick: u64) {
+        self.bucket(tick).requests_spawned += 1;
+    }
+
+    pub(crate) fn record_archived(&mut self, request: &Request, tick: u64) {
+        let bucket = self.bucket(tick);
+        match request.outcome() {
+            Some(RequestOutcome::Fulfilled) => {
+                bucket.requests_fulfilled += 1;
+                bucket.total_fulfilled_wait += request.waited();
+                bucket.revenue += request.fare();
+            }
+            _ => bucket.requests_unfulfilled += 1,
+        }
+    }
+
+    pub(crate) fn record_tick(&mut self, taxis: &[Taxi], tick: u64) {
+        let bucket = self.bucket(tick);
+        bucket.occupied_taxi_ticks += taxis.iter().filter(|t| t.is_occupied()).count() as u64;
+        bucket.taxi_ticks += taxis.len() as u64;
+        bucket.ticks += 1;
+    }
+
+    /// Records `ticks` starting at `first` in which all `taxis` were idle.
+    pub(crate) fn record_quiet_ticks(&mut self, taxis: &[Taxi], first: u64, ticks: u64) {
+        // Only the last few minutes of a long skip are still within the window.
+        let end = first + ticks;
+        let mut tick = first.max(end.saturating_sub(self.minutes * SECONDS_PER_MINUTE));
+        while tick < end {
+            let in_minute = (SECONDS_PER_MINUTE - tick % SECONDS_PER_MINUTE).min(end - tick);
+            let bucket = self.bucket(tick);
+            bucket.taxi_ticks += taxis.len() as u64 * in_minute;
+            bucket.ticks += in_minute;
+            tick += in_minute;
+        }
+    }
+
+    /// Sums up the window which ends at `tick`. The current minute counts as far as it went.
+    pub fn summary(&self, tick: u64) -> WindowSummary {
+        let minute = tick / SECONDS_PER_MINUTE;
+        let mut total = Bucket::default();
+        let recorded = self.buckets.len() as u64;
+        for (i, b) in self.buckets.iter().enumerate() {
+            let bucket_minute = self.minute + 1 + i as u64 - recorded;
+            if bucket_minute + self.minutes <= minute {
+                continue;
+            }
+            total.requests_spawned += b.requests_spawned;
+            total.requests_fulfilled += b.requests_fulfilled;
+            total.requests_unfulfilled += b.requests_unfulfilled;
+            total.total_fulfilled_wait += b.total_fulfilled_wait;
+            total.revenue += b.revenue;
+            total.occupied_taxi_ticks += b.occupied_taxi_ticks;
+            total.taxi_ticks += b.taxi_ticks;
+            total.ticks += b.ticks;
+        }
+        let ratio = |n: u64, d: u64| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+        let finished = total.requests_fulfilled + total.requests_unfulfilled;
+        WindowSummary {
+            ticks: total.ticks,
+            requests_spawned: total.requests_spawned,
+            requests_per_minute: ratio(total.requests_spawned * SECONDS_PER_MINUTE, total.ticks),
+            requests_fulfilled: total.requests_fulfilled,
+            requests_unfulfilled: total.requests_unfulfilled,
+            fulfillment_rate: ratio(total.requests_fulfilled, finished),
+            mean_wait: ratio(total.total_fulfilled_wait, total.requests_fulfilled),
+            taxi_utilization: ratio(total.occupied_taxi_ticks, total.taxi_ticks),
+            revenue: total.revenue,
+        }
+    }
+}
+
+/// Key metrics of the last few minutes of a run.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowSummary {
+    /// How many ticks the window covers, less than its length early on in a run.
+    pub ticks: u64,
+    pub requests_spawned: u64,
+
+    /// Spawned `Request`s per simulated minute, i.e. the recent demand.
+    pub requests_per_minute: f64,
+
+    /// `Request`s which were archived within the window.
+    pub requests_fulfilled: u64,
+
+    /// Canceled, no-shows or rejected.
+    pub requests_unfulfilled: u64,
+    pub fulfillment_rate: f64,
+    pub mean_wait: f64,
+    pub taxi_utilization: f64,
+    pub revenue: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn minutes_fall_out_of_the_window() {
+        let mut window = RollingWindow::new(3);
+        window.record_spawned(0);
+        window.record_spawned(60);
+        assert_eq!(window.summary(179).requests_spawned, 2);
+        assert_eq!(window.summary(180).requests_spawned, 1);
+        assert_eq!(window.summary(240).requests_spawned, 0);
+    }
+
+    #[test]
+    fn a_gap_longer_than_the_window_drops_every_bucket() {
+        let mut window = RollingWindow::new(3);
+        window.record_spawned(0);
+        window.record_spawned(60);
+        window.record_spawned(600);
+        assert_eq!(window.buckets.len(), 3);
+        assert_eq!(window.summary(600).requests_spawned, 1);
+    }
+
+    #[test]
+    fn quiet_ticks_count_as_far_as_the_window_reaches() {
+        let taxis: Vec<Taxi> = (0..2)
+            .map(|_| Taxi::new(0, 4, 1.0, Position::new(0.0, 0.0)))
+            .collect();
+        let mut window = RollingWindow::new(15);
+        window.record_quiet_ticks(&taxis, 0, 100);
+        let summary = window.summary(99);
+        assert_eq!(summary.ticks, 100);
+        assert_eq!(summary.taxi_utilization, 0.0);
+
+        // Only minutes 4 and 5 of ticks 30 up to 330 are left in a window of 2 minutes.
+        let mut window = RollingWindow::new(2);
+        window.record_quiet_ticks(&taxis, 30, 300);
+        assert_eq!(window.summary(329).ticks, 90);
+    }
+}
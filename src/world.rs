@@ -0,0 +1,2267 @@
+use rand::prelude::*;
+use rand_pcg::Pcg64Mcg;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use uuid::Uuid;
+
+use crate::ab_test::{GroupDispatch, GROUPS};
+use crate::archive::Archive;
+use crate::arrivals::{poisson, ArrivalProcess};
+use crate::demand::{Layer, ReplayedTrip};
+use crate::distribution::{Distribution, Weights};
+use crate::eta::EtaEstimator;
+use crate::events::{DispatchCandidate, Event};
+use crate::histogram::HistogramBuckets;
+use crate::incidents::{Effect, Incident, Incidents};
+use crate::observer::Observer;
+use crate::offers::{Escalation, Offer, Offers};
+use crate::position::Position;
+use crate::pricing::{Costs, Pricing};
+use crate::rider_choice::{Quote, RiderChoice, RiderChoiceModel};
+use crate::roads::Roads;
+use crate::scenario::{Cruising, Maintenance, NoShow, ReRequest, Scenario};
+use crate::stats::{Statistics, Summary};
+use crate::time::{minutes_to_ticks, per_tick, Clock, SimTime, SECONDS_PER_MINUTE};
+use crate::transit::{Transit, TransitNetwork};
+use crate::travel_times::TravelTimeMatrix;
+use crate::units::Units;
+use crate::vehicle::{VehicleType, VehicleTypes};
+use crate::venue::Venue;
+use crate::window::{RollingWindow, WindowSummary, DEFAULT_WINDOW_MINUTES};
+use crate::zones::{DispatchOptions, Zones};
+
+mod invariants;
+
+pub use self::invariants::InvariantViolation;
+
+/// Somebody who tries to hail a `Taxi` will issue a `Request`.
+/// A `Request` is therefore represents somebody's desire to be picked up by a `Taxi`.
+/// It has a `max_lifetime` which expires the `Request` as if it timed out because it didn't
+/// fulfilled quickly enough.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Request {
+    id: Uuid,
+
+    /// Who issued the `Request`. Riders who try again later keep their identity.
+    rider: Uuid,
+
+    /// How often the rider tried so far, including this `Request`.
+    attempt: u32,
+    remaining_waiting_time: u64,
+    assigned_taxi: Option<Uuid>,
+    fulfillment_time: u64,
+
+    /// How long the ride takes in total, unlike `fulfillment_time` which counts down.
+    trip_duration: u64,
+
+    /// How many ticks this `Request` has been waiting for a `Taxi` so far.
+    waited: u64,
+
+    /// Age of the `World` when the `Request` was submitted.
+    spawned_at: u64,
+
+    /// How many people want to ride together. Only a `Taxi` with at least this many seats can
+    /// take the `Request`.
+    party_size: u32,
+
+    /// What the rider was quoted for the trip when submitting the `Request`.
+    fare: f64,
+
+    /// Which kinds of `Taxi`s the rider is willing to get into.
+    vehicle_types: VehicleTypes,
+
+    /// Index of the `Venue` the rider came out of, if any.
+    venue: Option<usize>,
+
+    /// Index of the `DemandLayer` the rider came from, if any.
+    layer: Option<usize>,
+
+    pickup: Position,
+    dropoff: Position,
+    stops: Vec<Stop>,
+
+    /// How many of the `stops` the `Taxi` reached so far.
+    stops_reached: usize,
+
+    /// Ticks left for the `Taxi` to wait at the stop it just reached.
+    stop_time: u64,
+
+    /// Ticks the `Taxi` waited at stops so far.
+    stop_waited: u64,
+
+    /// How many ticks each finished leg of the ride took, from the pickup to the first stop
+    /// up to the last stop to the dropoff, not counting the time spent at stops.
+    leg_times: Vec<u64>,
+
+    /// Whether the assigned `Taxi` reached the pickup yet. The ride only starts then.
+    picked_up: bool,
+
+    /// How many ticks the assigned `Taxi` has been driving to the pickup so far.
+    pickup_time: u64,
+
+    /// Ticks left for the riders to get in once picked up, and to get out after the ride.
+    boarding_time: u64,
+    alighting_time: u64,
+
+    /// Pickup ETA in ticks the rider was told when a `Taxi` was assigned.
+    quoted_eta: Option<f64>,
+
+    /// Ticks the `Taxi` keeps waiting at the pickup for a rider who didn't show up.
+    no_show_remaining: Option<u64>,
+
+    /// How many ticks the `Taxi` waited for a rider who didn't show up so far.
+    no_show_waited: u64,
+
+    /// Whether dispatch turned the `Request` down since no `Taxi` was within reach.
+    rejected: bool,
+
+    /// Index of the group in `ab_test::GROUPS` which serves the `Request`.
+    group: usize,
+
+    /// The offer of the ride to a driver which is waiting for an answer, if any.
+    offer: Option<Offer>,
+
+    /// `Taxi`s whose drivers declined the ride or didn't answer in time.
+    declined_by: Vec<Uuid>,
+
+    /// Whether dispatch gave up on offering the ride and assigns it outright.
+    escalated: bool,
+
+    /// Ages of the `World` when the ride was first offered to a driver, when a `Taxi` was
+    /// assigned, when it picked the rider up and when the `Request` died, if it got that far.
+    offered_at: Option<u64>,
+    assigned_at: Option<u64>,
+    picked_up_at: Option<u64>,
+    ended_at: Option<u64>,
+}
+
+/// Where somebody wants to go, independent of how often they have to ask for it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Trip {
+    pub pickup: Position,
+    pub dropoff: Position,
+
+    /// Where the `Taxi` has to stop on the way to the dropoff, in order, e.g. to pick up a
+    /// friend. Empty for a direct ride.
+    pub stops: Vec<Stop>,
+
+    /// Ticks the ride takes once the rider is picked up, over all legs.
+    pub duration: u64,
+
+    pub party_size: u32,
+
+    /// Which kinds of `Taxi`s the rider is willing to get into.
+    pub vehicle_types: VehicleTypes,
+}
+
+/// A waypoint of a `Trip` between the pickup and the dropoff.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Stop {
+    pub position: Position,
+
+    /// Ticks into the ride at which the `Taxi` reaches the stop, not counting any time spent
+    /// at earlier stops.
+    pub reached_after: u64,
+}
+
+/// How a `Request` ended up once it is no longer alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestOutcome {
+    /// A `Taxi` picked the `Request` up and finished the trip.
+    Fulfilled,
+
+    /// Nobody picked the `Request` up before its waiting time ran out.
+    Canceled,
+
+    /// The rider wasn't at the pickup and the `Taxi` gave up waiting for them.
+    NoShow,
+
+    /// No `Taxi` was within the `max_pickup` reach when dispatch tried to match the `Request`.
+    Rejected,
+}
+
+/// A step in the life of a `Request`, see `Request::timeline`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Milestone {
+    Created,
+    Offered,
+    Assigned,
+    PickedUp,
+
+    /// The rider got out at the dropoff.
+    DroppedOff,
+
+    /// Canceled, no-show or rejected.
+    Expired,
+}
+
+impl RequestOutcome {
+    /// As it is written in exports.
+    pub fn name(self) -> &'static str {
+        match self {
+            RequestOutcome::Fulfilled => "fulfilled",
+            RequestOutcome::Canceled => "canceled",
+            RequestOutcome::NoShow => "no_show",
+            RequestOutcome::Rejected => "rejected",
+        }
+    }
+}
+
+impl Request {
+    /// A first attempt of a new rider. See `retry` for riders who try again.
+    pub fn new(max_waiting_time: u64, trip: Trip, fare: f64, dwell_times: (u64, u64)) -> Request {
+        Request {
+            id: Uuid::new_v4(),
+            rider: Uuid::new_v4(),
+            attempt: 1,
+            remaining_waiting_time: max_waiting_time,
+            assigned_taxi: None,
+            fulfillment_time: trip.duration,
+            trip_duration: trip.duration,
+            waited: 0,
+            spawned_at: 0,
+            party_size: trip.party_size,
+            fare,
+            vehicle_types: trip.vehicle_types,
+            venue: None,
+            layer: None,
+            pickup: trip.pickup,
+            dropoff: trip.dropoff,
+            stops: trip.stops,
+            stops_reached: 0,
+            stop_time: 0,
+            stop_waited: 0,
+            leg_times: vec![],
+            picked_up: false,
+            pickup_time: 0,
+            boarding_time: dwell_times.0,
+            alighting_time: dwell_times.1,
+            quoted_eta: None,
+            no_show_remaining: None,
+            no_show_waited: 0,
+            rejected: false,
+            group: 0,
+            offer: None,
+            declined_by: vec![],
+            escalated: false,
+            offered_at: None,
+            assigned_at: None,
+            picked_up_at: None,
+            ended_at: None,
+        }
+    }
+
+    /// The same rider asking for the same `trip` again as `attempt`.
+    pub fn retry(
+        rider: Uuid,
+        attempt: u32,
+        max_waiting_time: u64,
+        trip: Trip,
+        fare: f64,
+        dwell_times: (u64, u64),
+    ) -> Request {
+        Request {
+            rider,
+            attempt,
+            ..Request::new(max_waiting_time, trip, fare, dwell_times)
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn rider(&self) -> Uuid {
+        self.rider
+    }
+
+    /// 1 for a rider's first `Request`, and one more for every time they tried again.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn trip(&self) -> Trip {
+        Trip {
+            pickup: self.pickup,
+            dropoff: self.dropoff,
+            stops: self.stops.clone(),
+            duration: self.trip_duration,
+            party_size: self.party_size,
+            vehicle_types: self.vehicle_types,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.remaining_waiting_time > 0
+            && (self.fulfillment_time > 0 || self.alighting_time > 0)
+            && self.no_show_remaining != Some(0)
+    }
+
+    /// How many ticks this `Request` has been waiting for a `Taxi`.
+    pub fn waited(&self) -> u64 {
+        self.waited
+    }
+
+    pub fn assigned_taxi(&self) -> Option<Uuid> {
+        self.assigned_taxi
+    }
+
+    /// Age of the `World` when the `Request` was submitted.
+    pub fn spawned_at(&self) -> u64 {
+        self.spawned_at
+    }
+
+    pub fn party_size(&self) -> u32 {
+        self.party_size
+    }
+
+    pub fn fare(&self) -> f64 {
+        self.fare
+    }
+
+    /// Which kinds of `Taxi`s the rider is willing to get into.
+    pub fn vehicle_types(&self) -> VehicleTypes {
+        self.vehicle_types
+    }
+
+    /// Index of the `Venue` in `World::venues` the rider came out of, if any.
+    pub fn venue(&self) -> Option<usize> {
+        self.venue
+    }
+
+    /// Index of the `DemandLayer` of the `Scenario` the rider came from, if any.
+    pub fn layer(&self) -> Option<usize> {
+        self.layer
+    }
+
+    /// Index of the group in `ab_test::GROUPS` which serves the `Request`. Always 0 without an
+    /// A/B test.
+    pub fn group(&self) -> usize {
+        self.group
+    }
+
+    /// How many drivers declined the ride or didn't answer its offer in time.
+    pub fn offers_declined(&self) -> usize {
+        self.declined_by.len()
+    }
+
+    /// How many ticks the ride takes, not counting boarding and alighting.
+    pub fn trip_duration(&self) -> u64 {
+        self.trip_duration
+    }
+
+    /// Kilometers from the pickup via all stops to the dropoff.
+    pub fn trip_distance(&self) -> f64 {
+        let mut from = self.pickup;
+        let mut distance = 0.0;
+        for to in self
+            .stops
+            .iter()
+            .map(|s| s.position)
+            .chain(Some(self.dropoff))
+        {
+            distance += from.distance(to);
+            from = to;
+        }
+        distance
+    }
+
+    pub fn pickup(&self) -> Position {
+        self.pickup
+    }
+
+    pub fn dropoff(&self) -> Position {
+        self.dropoff
+    }
+
+    pub fn stops(&self) -> &[Stop] {
+        &self.stops
+    }
+
+    /// How many ticks each finished leg of the ride took, not counting the time spent at
+    /// stops. Once the `Request` is fulfilled, there's one more leg than there are `stops`.
+    pub fn leg_times(&self) -> &[u64] {
+        &self.leg_times
+    }
+
+    /// Ticks the `Taxi` waited at stops, for riders to get in or out along the way.
+    pub fn stop_waited(&self) -> u64 {
+        self.stop_waited
+    }
+
+    /// Where the `Taxi` is headed during the ride, and how many ticks of the current leg are
+    /// left. Only meaningful while `fulfillment_time` hasn't run out.
+    fn next_waypoint(&self) -> (Position, u64) {
+        let elapsed = self.trip_duration - self.fulfillment_time;
+        match self.stops.get(self.stops_reached) {
+            Some(stop) => (stop.position, stop.reached_after - elapsed),
+            None => (self.dropoff, self.fulfillment_time),
+        }
+    }
+
+    pub fn is_picked_up(&self) -> bool {
+        self.picked_up
+    }
+
+    /// How many ticks the assigned `Taxi` drove to the pickup.
+    pub fn pickup_time(&self) -> u64 {
+        self.pickup_time
+    }
+
+    /// Pickup ETA in ticks the rider was told, if a `Taxi` was ever assigned.
+    pub fn quoted_eta(&self) -> Option<f64> {
+        self.quoted_eta
+    }
+
+    /// How many ticks the assigned `Taxi` waited for a rider who didn't show up.
+    pub fn no_show_waited(&self) -> u64 {
+        self.no_show_waited
+    }
+
+    /// Ticks the assigned `Taxi` still spends on boarding, the ride and alighting, or waiting for
+    /// a rider who didn't show up.
+    pub fn remaining_stop_ticks(&self) -> u64 {
+        match self.no_show_remaining {
+            Some(remaining) => remaining,
+            None => {
+                self.boarding_time + self.stop_time + self.fulfillment_time + self.alighting_time
+            }
+        }
+    }
+
+    /// Fingerprint of everything about this `Request`, to notice when it changes.
+    pub(crate) fn digest(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.id.hash(&mut hasher);
+        self.rider.hash(&mut hasher);
+        self.attempt.hash(&mut hasher);
+        self.remaining_waiting_time.hash(&mut hasher);
+        self.assigned_taxi.hash(&mut hasher);
+        self.fulfillment_time.hash(&mut hasher);
+        self.trip_duration.hash(&mut hasher);
+        self.waited.hash(&mut hasher);
+        self.spawned_at.hash(&mut hasher);
+        self.party_size.hash(&mut hasher);
+        self.fare.to_bits().hash(&mut hasher);
+        self.vehicle_types.hash(&mut hasher);
+        self.venue.hash(&mut hasher);
+        self.layer.hash(&mut hasher);
+        for p in &[self.pickup, self.dropoff] {
+            p.x.to_bits().hash(&mut hasher);
+            p.y.to_bits().hash(&mut hasher);
+        }
+        for stop in &self.stops {
+            stop.position.x.to_bits().hash(&mut hasher);
+            stop.position.y.to_bits().hash(&mut hasher);
+            stop.reached_after.hash(&mut hasher);
+        }
+        self.stops_reached.hash(&mut hasher);
+        self.stop_time.hash(&mut hasher);
+        self.stop_waited.hash(&mut hasher);
+        self.leg_times.hash(&mut hasher);
+        self.picked_up.hash(&mut hasher);
+        self.pickup_time.hash(&mut hasher);
+        self.boarding_time.hash(&mut hasher);
+        self.alighting_time.hash(&mut hasher);
+        self.quoted_eta.map(f64::to_bits).hash(&mut hasher);
+        self.no_show_remaining.hash(&mut hasher);
+        self.no_show_waited.hash(&mut hasher);
+        self.rejected.hash(&mut hasher);
+        self.group.hash(&mut hasher);
+        self.offer.map(|o| (o.taxi, o.due)).hash(&mut hasher);
+        self.declined_by.hash(&mut hasher);
+        self.escalated.hash(&mut hasher);
+        self.offered_at.hash(&mut hasher);
+        self.assigned_at.hash(&mut hasher);
+        self.picked_up_at.hash(&mut hasher);
+        self.ended_at.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Ages of the `World` at every step the `Request` took so far, in order. Every step is
+    /// only listed once, for the first time it happened.
+    pub fn timeline(&self) -> Vec<(Milestone, u64)> {
+        let mut timeline = vec![(Milestone::Created, self.spawned_at)];
+        let steps = [
+            (Milestone::Offered, self.offered_at),
+            (Milestone::Assigned, self.assigned_at),
+            (Milestone::PickedUp, self.picked_up_at),
+        ];
+        timeline.extend(steps.iter().filter_map(|&(m, at)| Some((m, at?))));
+        if let Some(ended_at) = self.ended_at {
+            let end = match self.outcome() {
+                Some(RequestOutcome::Fulfilled) => Milestone::DroppedOff,
+                _ => Milestone::Expired,
+            };
+            timeline.push((end, ended_at));
+        }
+        timeline
+    }
+
+    pub fn offered_at(&self) -> Option<u64> {
+        self.offered_at
+    }
+
+    pub fn assigned_at(&self) -> Option<u64> {
+        self.assigned_at
+    }
+
+    /// Age of the `World` when the assigned `Taxi` picked the rider up, not counting riders who
+    /// didn't show up.
+    pub fn picked_up_at(&self) -> Option<u64> {
+        self.picked_up_at
+    }
+
+    /// `None` while the `Request` is still alive.
+    pub fn outcome(&self) -> Option<RequestOutcome> {
+        if self.no_show_remaining == Some(0) {
+            Some(RequestOutcome::NoShow)
+        } else if self.fulfillment_time == 0 && self.alighting_time == 0 {
+            Some(RequestOutcome::Fulfilled)
+        } else if self.rejected {
+            Some(RequestOutcome::Rejected)
+        } else if self.remaining_waiting_time == 0 {
+            Some(RequestOutcome::Canceled)
+        } else {
+            None
+        }
+    }
+}
+
+/// Where a new rider is picked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+    Anywhere,
+    Venue(usize),
+
+    /// The dispatch zone at this index, during a demand spike there.
+    Zone(usize),
+
+    /// Wherever the demand layer at this index spawns riders.
+    Layer(usize),
+}
+
+/// What a `Taxi` is currently up to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxiState {
+    /// Free to take a `Request`.
+    Idle,
+
+    /// Busy with a `Request`, either on the way to the pickup or driving the rider.
+    Occupied,
+
+    /// Off the road for maintenance or refueling for another `remaining` ticks.
+    InMaintenance { remaining: u64 },
+
+    /// The driver logged off during an `Incident` for another `remaining` ticks.
+    LoggedOff { remaining: u64 },
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Taxi {
+    id: Uuid,
+    state: TaxiState,
+
+    /// Index of the kind of vehicle this is in the fleet of the `World`.
+    vehicle_type: usize,
+
+    /// How many passengers fit into this `Taxi`.
+    capacity: u32,
+
+    /// Kilometers driven per tick without congestion, at most.
+    speed: f64,
+
+    /// Kilometers per tick the `Taxi` speeds up or brakes by per tick, if it can't do so
+    /// instantly.
+    acceleration: Option<f64>,
+
+    /// Kilometers per tick the `Taxi` drove at in the last tick, or 0 if it stands still.
+    velocity: f64,
+
+    position: Position,
+
+    /// Where the `Taxi` cruises to while idle, if it does.
+    cruise_target: Option<Position>,
+
+    /// Total kilometers driven.
+    mileage: f64,
+
+    /// Kilometers driven since the last maintenance stop.
+    mileage_since_maintenance: f64,
+
+    /// How many `Request`s this `Taxi` fulfilled.
+    trips: u64,
+
+    /// Sum of the fares of all fulfilled `Request`s.
+    revenue: f64,
+
+    /// Index of the group in `ab_test::GROUPS` the `Taxi` is in. It only takes `Request`s of
+    /// that group.
+    group: usize,
+
+    /// Index of the driver's profile in `Offers::profiles`, if rides are offered.
+    profile: usize,
+
+    /// Whether the driver was offered a ride and didn't answer yet.
+    offered: bool,
+}
+
+/// What a single `Taxi` earned and cost over the run so far.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TaxiEarnings {
+    pub id: Uuid,
+    pub trips: u64,
+    pub revenue: f64,
+    pub costs: f64,
+    pub profit: f64,
+}
+
+impl Taxi {
+    pub fn new(vehicle_type: usize, capacity: u32, speed: f64, position: Position) -> Taxi {
+        Taxi {
+            id: Uuid::new_v4(),
+            state: TaxiState::Idle,
+            vehicle_type,
+            capacity,
+            speed,
+            acceleration: None,
+            velocity: 0.0,
+            position,
+            cruise_target: None,
+            group: 0,
+            profile: 0,
+            offered: false,
+            mileage: 0.0,
+            mileage_since_maintenance: 0.0,
+            trips: 0,
+            revenue: 0.0,
+        }
+    }
+
+    /// Speeds up and brakes by at most `acceleration` km per tick per tick rather than
+    /// instantly.
+    pub fn accelerating(self, acceleration: Option<f64>) -> Taxi {
+        Taxi {
+            acceleration,
+            ..self
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn state(&self) -> TaxiState {
+        self.state
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.state == TaxiState::Idle
+    }
+
+    pub fn is_occupied(&self) -> bool {
+        self.state == TaxiState::Occupied
+    }
+
+    pub fn is_in_maintenance(&self) -> bool {
+        matches!(self.state, TaxiState::InMaintenance { .. })
+    }
+
+    /// Index of the kind of vehicle this is in `World::vehicle_types`.
+    pub fn vehicle_type(&self) -> usize {
+        self.vehicle_type
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Kilometers driven per tick without congestion.
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn mileage(&self) -> f64 {
+        self.mileage
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// How many `Request`s this `Taxi` fulfilled.
+    pub fn trips(&self) -> u64 {
+        self.trips
+    }
+
+    /// Sum of the fares this `Taxi` collected.
+    pub fn revenue(&self) -> f64 {
+        self.revenue
+    }
+
+    /// Whether this `Taxi` is free, has enough seats for `request` and is of a kind the rider
+    /// accepts.
+    pub fn can_take(&self, request: &Request) -> bool {
+        self.state == TaxiState::Idle && !self.offered && self.fits(request)
+    }
+
+    /// Whether this `Taxi` could take `request` once it's free, i.e. has enough seats, is of a
+    /// kind the rider accepts, serves the same group and didn't decline it.
+    pub fn fits(&self, request: &Request) -> bool {
+        self.capacity >= request.party_size
+            && request.vehicle_types.contains(self.vehicle_type)
+            && self.group == request.group
+            && !request.declined_by.contains(&self.id)
+    }
+
+    /// Whether the driver is free and may log off, which those who were offered a ride don't
+    /// before they answered.
+    fn can_log_off(&self) -> bool {
+        self.state == TaxiState::Idle && !self.offered
+    }
+
+    /// Puts the `Taxi` into the group in `ab_test::GROUPS` at index `group`.
+    pub fn in_group(self, group: usize) -> Taxi {
+        Taxi { group, ..self }
+    }
+
+    pub fn group(&self) -> usize {
+        self.group
+    }
+
+    /// Km per tick per tick this `Taxi` speeds up or brakes by, if it can't do so instantly.
+    pub fn acceleration(&self) -> Option<f64> {
+        self.acceleration
+    }
+
+    /// Drives towards `target` for a tick at up to `speed` km per tick, speeding up and braking
+    /// in time for the `target` as fast as the `acceleration` allows. Returns how far it got.
+    fn drive_step(&mut self, target: Position, speed: f64) -> f64 {
+        let speed = match self.acceleration {
+            Some(acceleration) => {
+                let braking_speed = (2.0 * acceleration * self.position.distance(target)).sqrt();
+                (self.velocity + acceleration).min(speed).min(braking_speed)
+            }
+            None => speed,
+        };
+        let driven = self.drive_towards(target, speed);
+        self.velocity = if self.position == target { 0.0 } else { speed };
+        driven
+    }
+
+    /// Drives at most `kilometers` straight towards `target` and returns how far it got.
+    fn drive_towards(&mut self, target: Position, kilometers: f64) -> f64 {
+        let next = self.position.towards(target, kilometers);
+        let driven = self.position.distance(next);
+        self.position = next;
+        self.mileage += driven;
+        self.mileage_since_maintenance += driven;
+        driven
+    }
+
+    /// Frees the `Taxi` after a trip, unless it is due for maintenance in which case it goes
+    /// off the road first.
+    fn finish_trip(&mut self, maintenance: Option<&Maintenance>) {
+        self.state = match maintenance {
+            Some(m) if self.mileage_since_maintenance >= m.interval => {
+                self.mileage_since_maintenance = 0.0;
+                TaxiState::InMaintenance {
+                    remaining: minutes_to_ticks(m.duration),
+                }
+            }
+            _ => TaxiState::Idle,
+        };
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct World {
+    /// How long the `World` updates for in ticks/seconds.
+    runtime: u64,
+
+    /// How long the `World` has been running for.
+    age: u64,
+
+    /// What time it is at every `age`.
+    clock: Clock,
+
+    /// Change to spawn a request per tick.
+    request_spawn_chance: f64,
+
+    /// Draws how many requests spawn per tick instead of `request_spawn_chance`, if set.
+    arrivals: Option<ArrivalProcess>,
+
+    /// When the number of `active_requests` reaches this number, no further requests will be
+    /// allowed to spawn.
+    max_active_requests: u32,
+
+    /// How long a freshly spawned `Request` is willing to wait for a `Taxi`.
+    max_waiting_time: u64,
+
+    /// How long a ride takes once the rider is picked up.
+    trip_duration: Distribution,
+
+    /// How long riders take to get in and out, if they take any time at all.
+    boarding_time: Option<Distribution>,
+    alighting_time: Option<Distribution>,
+
+    /// Picks the party size of new `Request`s. Index `i` stands for a party of `i + 1`.
+    party_sizes: Weights,
+
+    /// Picks how many stops new `Request`s make on the way, if any make stops at all.
+    stop_counts: Option<Weights>,
+
+    /// How long `Taxi`s wait at every stop on the way, if they wait at all.
+    stop_time: Option<Distribution>,
+
+    /// Kilometers per tick which sampled trip durations assume.
+    taxi_speed: f64,
+
+    /// The kinds of vehicles in the fleet, which `Taxi`s refer to by index.
+    vehicle_types: Vec<VehicleType>,
+
+    /// Picks which vehicle types new `Request`s accept, if riders have any preferences.
+    vehicle_preference_weights: Option<Weights>,
+    vehicle_preferences: Vec<VehicleTypes>,
+
+    /// Predicts how long `Taxi`s take to get to pickups, and how fast they drive there.
+    eta: EtaEstimator,
+
+    /// Riders who may not be at the pickup, if any aren't.
+    no_show: Option<NoShow>,
+
+    /// Riders who may try again after canceling because of the ETA, if any do.
+    re_request: Option<ReRequest>,
+
+    /// Riders who'll try again, with the tick they'll do so at, the attempt it'll be, their
+    /// `Trip` and the group they stay in.
+    pending_re_requests: Vec<(u64, Uuid, u32, Trip, usize)>,
+
+    /// Side length of the square city in km.
+    city_size: f64,
+
+    /// Where bursts of riders come out at scheduled times.
+    venues: Vec<Venue>,
+
+    /// Riders queuing inside each of the `venues` for room at the curb, by the tick they came
+    /// out at.
+    venue_queues: Vec<VecDeque<u64>>,
+
+    /// Further demand, each layer with random numbers of its own.
+    layers: Vec<Layer>,
+
+    /// How the city is split up for dispatch.
+    zones: Zones,
+
+    /// Dispatch runs every this many ticks.
+    matching_interval: u64,
+
+    /// How group B dispatches if the fleet is split for an A/B test. `zones` and
+    /// `matching_interval` are for group A then.
+    group_b: Option<GroupDispatch>,
+
+    /// The transit alternative riders compare their `Taxi` with, if there is one.
+    transit: Option<(Transit, TransitNetwork)>,
+
+    /// How rides are offered to drivers, if they aren't assigned outright.
+    offers: Option<Offers>,
+
+    /// Everything which goes wrong during the run, scheduled or injected.
+    incidents: Incidents,
+
+    /// What reports on this run are written in, if the `Scenario` says.
+    units: Option<Units>,
+
+    /// How many threads zones are dispatched on, and whether dispatch explains itself.
+    dispatch: DispatchOptions,
+
+    /// Whether to run `check_invariants` after every tick.
+    checks_invariants: bool,
+
+    /// Whether to skip over ticks in which nothing happens.
+    adaptive_time_step: bool,
+
+    /// Whether the next tick spawns a `Request` for sure, because the ticks without one were
+    /// skipped over already.
+    spawn_due: bool,
+
+    /// How idle `Taxi`s cruise around, if they do.
+    cruising: Option<Cruising>,
+
+    /// When `Taxi`s have to go off the road for maintenance, if at all.
+    maintenance: Option<Maintenance>,
+
+    pricing: Pricing,
+    costs: Costs,
+
+    /// Current factor on all fares, updated at the start of every tick.
+    surge_multiplier: f64,
+
+    /// How riders decide whether to request a `Taxi`, take it and keep waiting for it.
+    rider_choice: RiderChoice,
+
+    /// Decides in place of `rider_choice` if it was set. It isn't part of a serialized `World`
+    /// and has to be set again after deserializing it.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    custom_rider_choice: Option<Box<dyn RiderChoiceModel>>,
+
+    /// Current `Taxi`s in the `World`.
+    taxis: Vec<Taxi>,
+
+    /// Where each `Taxi` is in `taxis` by its id.
+    taxi_indices: HashMap<Uuid, usize>,
+
+    /// Currently active `Request`s in the `World`. These are either being waited for or are
+    /// being driven.
+    active_requests: Vec<Request>,
+
+    /// Canceled or fulfilled requests. Append only, though the `Scenario` may have old ones
+    /// dropped or spilled to disk.
+    archived_requests: Archive,
+
+    /// Running counters used to summarize the run.
+    stats: Statistics,
+
+    /// The same over the last few minutes only.
+    window: RollingWindow,
+
+    /// Everybody who follows the run, e.g. to export it. They aren't part of a serialized
+    /// `World` and have to be added again after deserializing it.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    observers: Vec<Box<dyn Observer>>,
+
+    /// The same generator as `SmallRng` on 64-bit platforms, but one which can be serialized.
+    rng: Pcg64Mcg,
+}
+
+impl World {
+    /// Builds a fresh `World` from a `Scenario`. The same `seed` always yields the same run.
+    ///
+    /// Panics if the travel time cache can't be written or a replayed demand layer or transit
+    /// feed can't be read, which `try_new` returns instead.
+    pub fn new(scenario: &Scenario, seed: u64) -> World {
+        World::try_new(scenario, seed).expect("Couldn't set up the world.")
+    }
+
+    /// Like `new`, but returns the error if the travel time cache can't be written or a
+    /// replayed demand layer or transit feed can't be read.
+    pub fn try_new(scenario: &Scenario, seed: u64) -> io::Result<World> {
+        let mut rng = Pcg64Mcg::seed_from_u64(seed);
+        let vehicle_types = scenario.fleet();
+        let mut taxis = vec![];
+        for (i, vehicle_type) in vehicle_types.iter().enumerate() {
+            for j in 0..vehicle_type.count {
+                let taxi = Taxi::new(
+                    i,
+                    vehicle_type.capacity,
+                    per_tick(vehicle_type.speed),
+                    Position::random(&mut rng, scenario.city_size),
+                );
+                let group = match &scenario.ab_test {
+                    Some(ab_test) => ab_test.has_taxi_in_b(j as usize) as usize,
+                    None => 0,
+                };
+                // m/s² in km per tick per tick.
+                taxis.push(
+                    taxi.accelerating(vehicle_type.acceleration.map(|a| a / 1000.0))
+                        .in_group(group),
+                );
+            }
+        }
+        let taxi_indices = taxis.iter().enumerate().map(|(i, t)| (t.id, i)).collect();
+        let clock = Clock::new(scenario.start.unwrap_or_default());
+        let arrivals = scenario
+            .arrivals
+            .as_ref()
+            .map(|a| ArrivalProcess::new(a, &mut rng));
+        if let Some(offers) = &scenario.offers {
+            let profiles = Weights::new(offers.profiles.iter().map(|p| p.share).collect())
+                .expect("offers.profiles need at least one positive share.");
+            for taxi in &mut taxis {
+                taxi.profile = profiles.sample(&mut rng);
+            }
+        }
+
+        let mut eta = EtaEstimator::new(
+            scenario.speed_profile.clone(),
+            Roads::new(&scenario.speed_limits),
+        );
+        if let Some(travel_times) = &scenario.travel_times {
+            let speeds: Vec<f64> = taxis.iter().map(|t| t.speed).collect();
+            let matrix = TravelTimeMatrix::new(
+                travel_times,
+                scenario.city_size,
+                &scenario.speed_limits,
+                &speeds,
+            )?;
+            eta = eta.with_travel_times(matrix);
+        }
+
+        let layers = scenario
+            .demand
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| Layer::new(layer, i, seed))
+            .collect::<io::Result<_>>()?;
+        let transit = match &scenario.transit {
+            Some(t) => Some((t.clone(), TransitNetwork::new(&t.network)?)),
+            None => None,
+        };
+
+        Ok(World {
+            runtime: scenario.runtime,
+            age: 0,
+            clock,
+            request_spawn_chance: scenario.request_spawn_chance,
+            arrivals,
+            max_active_requests: scenario.max_active_requests,
+            max_waiting_time: scenario.max_waiting_time,
+            trip_duration: scenario.trip_duration.clone(),
+            boarding_time: scenario.boarding_time.clone(),
+            alighting_time: scenario.alighting_time.clone(),
+            party_sizes: Weights::new(scenario.party_size_weights.clone())
+                .expect("party_size_weights need at least one positive weight."),
+            stop_counts: if scenario.stop_count_weights.len() > 1 {
+                Some(
+                    Weights::new(scenario.stop_count_weights.clone())
+                        .expect("stop_count_weights need at least one positive weight."),
+                )
+            } else {
+                None
+            },
+            stop_time: scenario.stop_time.clone(),
+            taxi_speed: per_tick(scenario.taxi_speed),
+            vehicle_preference_weights: if scenario.vehicle_preferences.is_empty() {
+                None
+            } else {
+                Some(
+                    Weights::new(
+                        scenario
+                            .vehicle_preferences
+                            .iter()
+                            .map(|p| p.weight)
+                            .collect(),
+                    )
+                    .expect("vehicle_preferences need at least one positive weight."),
+                )
+            },
+            vehicle_preferences: scenario
+                .vehicle_preferences
+                .iter()
+                .map(|p| VehicleTypes::of(p, &vehicle_types))
+                .collect(),
+            eta,
+            no_show: scenario.no_show.clone(),
+            re_request: scenario.re_request.clone(),
+            pending_re_requests: vec![],
+            city_size: scenario.city_size,
+            venues: scenario.venues.clone(),
+            venue_queues: vec![VecDeque::new(); scenario.venues.len()],
+            layers,
+            zones: Zones::new(scenario.zones, scenario.city_size)
+                .batched(scenario.matching_interval > 1)
+                .weighted(scenario.dispatch_weights)
+                .limited(scenario.max_pickup),
+            matching_interval: scenario.matching_interval,
+            group_b: scenario
+                .ab_test
+                .as_ref()
+                .map(|ab_test| GroupDispatch::new(scenario, ab_test)),
+            transit,
+            offers: scenario.offers.clone(),
+            incidents: Incidents::new(&scenario.incidents, &scenario.speed_limits),
+            units: scenario.units.clone(),
+            dispatch: DispatchOptions::default(),
+            checks_invariants: false,
+            adaptive_time_step: false,
+            spawn_due: false,
+            cruising: scenario.cruising.clone(),
+            maintenance: scenario.maintenance.clone(),
+            pricing: scenario.pricing.clone(),
+            costs: scenario.costs.clone(),
+            surge_multiplier: 1.0,
+            rider_choice: scenario.rider_choice(),
+            custom_rider_choice: None,
+            taxis,
+            taxi_indices,
+            active_requests: vec![],
+            archived_requests: Archive::new(scenario.archive.clone(), seed),
+            stats: Statistics::new(
+                scenario.statistics,
+                &HistogramBuckets {
+                    trip_distance: scenario.histograms.trip_distance
+                        * scenario.units.as_ref().map_or(1.0, |u| u.distance.km()),
+                    ..scenario.histograms.clone()
+                },
+                &vehicle_types,
+                &scenario.venues,
+                Zones::new(scenario.zones, scenario.city_size),
+                clock,
+                if scenario.ab_test.is_some() {
+                    GROUPS.len()
+                } else {
+                    1
+                },
+            )
+            .with_layers(&scenario.demand),
+            window: RollingWindow::new(scenario.rolling_window.unwrap_or(DEFAULT_WINDOW_MINUTES)),
+            vehicle_types,
+            observers: vec![],
+            rng,
+        })
+    }
+
+    /// Has riders decide according to `model` from now on, rather than according to the
+    /// `Scenario`.
+    pub fn set_rider_choice(&mut self, model: Box<dyn RiderChoiceModel>) {
+        self.custom_rider_choice = Some(model);
+    }
+
+    /// Has `observer` follow the run from now on.
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    /// Calls `f` for every `Observer`, which may look at the `World` in the meantime.
+    fn notify(&mut self, mut f: impl FnMut(&mut dyn Observer, &World)) {
+        let mut observers = std::mem::take(&mut self.observers);
+        for o in &mut observers {
+            f(o.as_mut(), self);
+        }
+        self.observers = observers;
+    }
+
+    fn emit(&mut self, event: Event) {
+        self.notify(|o, world| o.on_event(world, &event));
+    }
+
+    /// Dispatches zones on up to `threads` threads. This never changes the outcome of a run,
+    /// only how fast it is.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.dispatch.threads = threads.max(1);
+    }
+
+    /// Emits a `DispatchExplained` event for every match dispatch makes from now on.
+    pub fn set_explain_dispatch(&mut self, explain: bool) {
+        self.dispatch.explain = explain;
+    }
+
+    /// Runs `check_invariants` after every tick and panics as soon as one is violated. Slow, but
+    /// catches bugs right where they happen.
+    pub fn set_check_invariants(&mut self, check_invariants: bool) {
+        self.checks_invariants = check_invariants;
+    }
+
+    /// Skips straight to the next tick in which something happens whenever no `Request` is
+    /// active and all `Taxi`s are idle, unless they cruise. Runs look the same statistically,
+    /// though not tick by tick, and `Observer`s aren't told about the ticks skipped over.
+    pub fn set_adaptive_time_step(&mut self, adaptive_time_step: bool) {
+        self.adaptive_time_step = adaptive_time_step;
+    }
+
+    /// Changes the chance to spawn a `Request` per tick from the next tick on. Returns `false`
+    /// and changes nothing if an `arrivals` process spawns them instead.
+    pub fn set_request_spawn_chance(&mut self, request_spawn_chance: f64) -> bool {
+        if self.arrivals.is_some() {
+            return false;
+        }
+        self.request_spawn_chance = request_spawn_chance.clamp(0.0, 1.0);
+        self.parameter_changed("request_spawn_chance", self.request_spawn_chance);
+        true
+    }
+
+    /// Changes the highest surge multiplier from the next tick on. Returns `false` and changes
+    /// nothing if prices never surge in this `World`.
+    pub fn set_surge_cap(&mut self, cap: f64) -> bool {
+        match &mut self.pricing.surge {
+            Some(surge) => {
+                surge.cap = cap;
+                self.parameter_changed("surge_cap", cap);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Prices all trips quoted from the next tick on with `pricing`.
+    pub fn set_pricing(&mut self, pricing: Pricing) {
+        self.parameter_changed("pricing", &pricing);
+        self.pricing = pricing;
+    }
+
+    /// Has dispatch run every `matching_interval` ticks from now on, batching `Request`s if
+    /// that's more than one. Only applies to group A if there's an A/B test.
+    pub fn set_matching_interval(&mut self, matching_interval: u64) {
+        self.matching_interval = matching_interval.max(1);
+        self.zones = self.zones.batched(self.matching_interval > 1);
+        self.parameter_changed("matching_interval", self.matching_interval);
+    }
+
+    fn parameter_changed<T: Serialize>(&mut self, parameter: &str, value: T) {
+        let event = Event::ParameterChanged {
+            parameter: parameter.to_string(),
+            value: toml::Value::try_from(value).expect("Parameters can always be written as TOML."),
+        };
+        self.emit(event);
+    }
+
+    /// Schedules `incident` on top of those of the `Scenario`, and returns the index its
+    /// events refer to it by. It starts on the next tick if its `at` already passed.
+    pub fn inject_incident(&mut self, incident: Incident) -> usize {
+        self.incidents.schedule(incident)
+    }
+
+    /// Starts and ends `Incident`s which are due, and announces them.
+    pub fn update_incidents(&mut self) {
+        let (started, ended) = self.incidents.advance(self.age);
+        let mut roads_changed = false;
+        for &i in &ended {
+            let incident = self.incidents.get(i);
+            roads_changed |= matches!(incident.effect, Effect::RoadClosure { .. });
+            self.emit(Event::IncidentEnded { incident: i });
+        }
+        for &i in &started {
+            let incident = self.incidents.get(i).clone();
+            match incident.effect {
+                Effect::DriverLogoff { share } => {
+                    for t in &mut self.taxis {
+                        if t.can_log_off() && self.rng.gen_bool(share) {
+                            t.state = TaxiState::LoggedOff {
+                                remaining: incident.duration,
+                            };
+                            t.cruise_target = None;
+                        }
+                    }
+                }
+                Effect::RoadClosure { .. } => roads_changed = true,
+                Effect::DemandSpike { .. } | Effect::DispatcherLatency { .. } => (),
+            }
+            self.emit(Event::IncidentStarted {
+                incident: i,
+                effect: incident.effect,
+            });
+        }
+        if roads_changed {
+            self.eta
+                .set_roads(Roads::new(&self.incidents.speed_limits()));
+        }
+    }
+
+    /// How many ticks the `World` runs for in total.
+    pub fn runtime(&self) -> u64 {
+        self.runtime
+    }
+
+    /// How many ticks the `World` has been running for.
+    pub fn age(&self) -> u64 {
+        self.age
+    }
+
+    /// What time it is in the `World`.
+    pub fn now(&self) -> SimTime {
+        self.clock.at(self.age)
+    }
+
+    /// Turns ticks of this run, e.g. `Request::spawned_at`, into `SimTime`.
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+
+    /// Current factor on all fares.
+    pub fn surge_multiplier(&self) -> f64 {
+        self.surge_multiplier
+    }
+
+    /// Whether an `arrivals` process spawns `Request`s rather than `request_spawn_chance`.
+    pub fn has_arrivals(&self) -> bool {
+        self.arrivals.is_some()
+    }
+
+    /// Whether prices ever surge in this `World`.
+    pub fn has_surge(&self) -> bool {
+        self.pricing.surge.is_some()
+    }
+
+    pub fn eta_estimator(&self) -> &EtaEstimator {
+        &self.eta
+    }
+
+    /// Ticks until `taxi` could be at the pickup of `request`, finishing whatever it's busy with
+    /// first.
+    pub fn pickup_eta(&self, taxi: &Taxi, request: &Request) -> f64 {
+        let current = if taxi.is_occupied() {
+            self.active_requests
+                .iter()
+                .find(|r| r.assigned_taxi == Some(taxi.id))
+        } else {
+            None
+        };
+        self.eta.pickup_eta(taxi, current, request, self.now())
+    }
+
+    pub fn taxis(&self) -> &[Taxi] {
+        &self.taxis
+    }
+
+    /// The kinds of vehicles in the fleet, indexed by `Taxi::vehicle_type`.
+    pub fn vehicle_types(&self) -> &[VehicleType] {
+        &self.vehicle_types
+    }
+
+    /// Where bursts of riders come out, which `Request::venue` refers to by index.
+    pub fn venues(&self) -> &[Venue] {
+        &self.venues
+    }
+
+    /// `Request`s which are either being waited for or are being driven.
+    pub fn active_requests(&self) -> &[Request] {
+        &self.active_requests
+    }
+
+    /// Recomputes the surge multiplier from the current balance of waiting `Request`s and idle
+    /// `Taxi`s.
+    pub fn update_surge(&mut self) {
+        let waiting_requests = self
+            .active_requests
+            .iter()
+            .filter(|r| r.assigned_taxi.is_none())
+            .count();
+        let idle_taxis = self
+            .taxis
+            .iter()
+            .filter(|t| t.state == TaxiState::Idle)
+            .count();
+        self.surge_multiplier = self.pricing.surge_multiplier(waiting_requests, idle_taxis);
+    }
+
+    /// Spawns requests with a small chance, or as many as the `arrivals` process draws.
+    ///
+    /// Every rider is quoted a fare first. Riders who aren't willing to pay that much never
+    /// submit their `Request`.
+    pub fn maybe_spawn_request(&mut self) {
+        let max_active_requests: usize = self.max_active_requests.try_into().unwrap();
+        if let Some(arrivals) = &mut self.arrivals {
+            let arrived = arrivals.sample(&mut self.rng, self.clock.at(self.age));
+            for _ in 0..arrived {
+                if self.active_requests.len() >= max_active_requests {
+                    break;
+                }
+                self.spawn_request(Origin::Anywhere);
+            }
+            return;
+        }
+        let due = std::mem::take(&mut self.spawn_due);
+        if self.active_requests.len() < max_active_requests
+            && (due || self.rng.gen_bool(self.request_spawn_chance))
+        {
+            self.spawn_request(Origin::Anywhere);
+        }
+    }
+
+    /// Spawns the riders of the demand spikes going on, as many as a Poisson process at their
+    /// rate draws for this tick.
+    pub fn spawn_spike_requests(&mut self) {
+        let max_active_requests: usize = self.max_active_requests.try_into().unwrap();
+        let spikes: Vec<(usize, f64)> = self
+            .incidents
+            .active()
+            .filter_map(|effect| match effect {
+                Effect::DemandSpike {
+                    zone,
+                    requests_per_minute,
+                } => Some((*zone, *requests_per_minute)),
+                _ => None,
+            })
+            .collect();
+        for (zone, requests_per_minute) in spikes {
+            let rate = requests_per_minute / SECONDS_PER_MINUTE as f64;
+            for _ in 0..poisson(&mut self.rng, rate) {
+                if self.active_requests.len() >= max_active_requests {
+                    return;
+                }
+                self.spawn_request(Origin::Zone(zone));
+            }
+        }
+    }
+
+    /// Riders who come out of `venues` during this tick queue up inside, and as many of the
+    /// queued ones as fit onto the curb submit their `Request`s, first come first served.
+    /// They're quoted a fare like everybody else.
+    pub fn spawn_venue_requests(&mut self) {
+        let max_active_requests: usize = self.max_active_requests.try_into().unwrap();
+        for v in 0..self.venues.len() {
+            let riders: u32 = self.venues[v]
+                .bursts
+                .iter()
+                .map(|b| b.riders_at(self.age))
+                .sum();
+            let queue = &mut self.venue_queues[v];
+            queue.extend(std::iter::repeat_n(self.age, riders as usize));
+            self.stats.record_venue_riders(v, riders);
+
+            let mut at_curb = self
+                .active_requests
+                .iter()
+                .filter(|r| r.venue == Some(v) && !r.picked_up)
+                .count();
+            while at_curb < self.venues[v].capacity as usize
+                && self.active_requests.len() < max_active_requests
+            {
+                let came_out = match self.venue_queues[v].pop_front() {
+                    Some(came_out) => came_out,
+                    None => break,
+                };
+                self.stats.record_venue_queue_time(v, self.age - came_out);
+                if self.spawn_request(Origin::Venue(v)) {
+                    at_curb += 1;
+                }
+            }
+        }
+    }
+
+    /// Quotes a new rider from `origin` and submits their `Request` if they're willing to pay.
+    /// Returns whether they were.
+    fn spawn_request(&mut self, origin: Origin) -> bool {
+        let duration = self.trip_duration.sample_ticks(&mut self.rng);
+        let distance = duration as f64 * self.taxi_speed;
+        let party_size = self.party_sizes.sample(&mut self.rng) as u32 + 1;
+        let vehicle_types = match &self.vehicle_preference_weights {
+            Some(weights) => self.vehicle_preferences[weights.sample(&mut self.rng)],
+            None => VehicleTypes::ALL,
+        };
+        let stop_count = match &self.stop_counts {
+            Some(weights) => weights.sample(&mut self.rng),
+            None => 0,
+        };
+        let fare = self
+            .pricing
+            .fare(distance, duration, stop_count, self.surge_multiplier);
+        let quote = Quote {
+            fare,
+            trip_duration: duration,
+            trip_distance: distance,
+            party_size,
+        };
+        if !self.accepts_quote(&quote) {
+            return false;
+        }
+        let pickup = match origin {
+            Origin::Anywhere => Position::random(&mut self.rng, self.city_size),
+            Origin::Venue(v) => self.venues[v].position.random_within(
+                &mut self.rng,
+                self.venues[v].radius,
+                self.city_size,
+            ),
+            Origin::Zone(zone) => self.zones.random_in(&mut self.rng, zone),
+            Origin::Layer(l) => self.layers[l].pickup(&mut self.rng, &self.zones, self.city_size),
+        };
+        let (stops, dropoff, duration) = if stop_count == 0 {
+            let dropoff = pickup.random_at_distance(&mut self.rng, distance, self.city_size);
+            (vec![], dropoff, duration)
+        } else {
+            self.route_with_stops(pickup, stop_count, distance, duration)
+        };
+        let trip = Trip {
+            pickup,
+            dropoff,
+            stops,
+            duration,
+            party_size,
+            vehicle_types,
+        };
+        self.submit_trip(trip, fare, origin);
+        true
+    }
+
+    /// Whether the rider who's quoted `quote` submits their `Request`. Counts them as priced
+    /// out if they don't.
+    fn accepts_quote(&mut self, quote: &Quote) -> bool {
+        let riders = rider_choice(&mut self.rider_choice, &mut self.custom_rider_choice);
+        let requests = riders.requests(&mut self.rng, quote);
+        if !requests {
+            self.stats.requests_priced_out += 1;
+        }
+        requests
+    }
+
+    /// Submits the `Request` for `trip` of a new rider from `origin`, who accepted `fare`.
+    fn submit_trip(&mut self, trip: Trip, fare: f64, origin: Origin) {
+        let mut request = Request::new(self.max_waiting_time, trip, fare, self.dwell_times());
+        match origin {
+            Origin::Venue(v) => request.venue = Some(v),
+            Origin::Layer(l) => request.layer = Some(l),
+            _ => (),
+        }
+        if let Some(group_b) = &self.group_b {
+            request.group = self.rng.gen_bool(group_b.request_share) as usize;
+        }
+        self.submit(request);
+    }
+
+    /// Spawns the riders of every demand layer, as far as `max_active_requests` allow. They're
+    /// drawn from the layer's own random numbers, and quoted a fare like everybody else.
+    pub fn spawn_layer_requests(&mut self) {
+        let max_active_requests: usize = self.max_active_requests.try_into().unwrap();
+        let time = self.clock.at(self.age);
+        for l in 0..self.layers.len() {
+            let riders = self.layers[l].riders_at(self.age, time);
+            let replayed = self.layers[l].replayed_until(self.age);
+            self.stats
+                .record_layer_riders(l, riders + replayed.len() as u64);
+            std::mem::swap(&mut self.rng, &mut self.layers[l].rng);
+            for _ in 0..riders {
+                if self.active_requests.len() >= max_active_requests {
+                    break;
+                }
+                self.spawn_request(Origin::Layer(l));
+            }
+            for trip in &replayed {
+                if self.active_requests.len() >= max_active_requests {
+                    break;
+                }
+                self.spawn_replayed(l, trip);
+            }
+            std::mem::swap(&mut self.rng, &mut self.layers[l].rng);
+        }
+    }
+
+    /// Quotes the rider of a replayed `trip` of the layer at index `l` at the current prices,
+    /// and submits their `Request` if they're willing to pay.
+    fn spawn_replayed(&mut self, l: usize, trip: &ReplayedTrip) {
+        let (pickup, dropoff) = (trip.pickup(), trip.dropoff());
+        let distance = pickup.distance(dropoff);
+        let duration = trip.trip_duration.unwrap_or_else(|| {
+            if self.taxi_speed > 0.0 {
+                ((distance / self.taxi_speed).round() as u64).max(1)
+            } else {
+                1
+            }
+        });
+        let party_size = trip.party_size.unwrap_or(1);
+        let fare = self
+            .pricing
+            .fare(distance, duration, 0, self.surge_multiplier);
+        let quote = Quote {
+            fare,
+            trip_duration: duration,
+            trip_distance: distance,
+            party_size,
+        };
+        if !self.accepts_quote(&quote) {
+            return;
+        }
+        let trip = Trip {
+            pickup,
+            dropoff,
+            stops: vec![],
+            duration,
+            party_size,
+            vehicle_types: VehicleTypes::ALL,
+        };
+        self.submit_trip(trip, fare, Origin::Layer(l));
+    }
+
+    /// A ride of `distance` km taking `duration` ticks from `pickup` via `stop_count` stops,
+    /// which split it at uniformly random points. Returns the stops, the dropoff and the
+    /// duration, which is a little longer if it's too short for a tick per leg.
+    fn route_with_stops(
+        &mut self,
+        pickup: Position,
+        stop_count: usize,
+        distance: f64,
+        duration: u64,
+    ) -> (Vec<Stop>, Position, u64) {
+        let mut shares: Vec<f64> = (0..stop_count).map(|_| self.rng.gen()).collect();
+        shares.sort_by(|a, b| a.partial_cmp(b).expect("Shares are never NaN."));
+        let mut stops = vec![];
+        let mut from = (pickup, 0.0);
+        for share in shares.iter().copied().chain(Some(1.0)) {
+            let position = from.0.random_at_distance(
+                &mut self.rng,
+                (share - from.1) * distance,
+                self.city_size,
+            );
+            stops.push(Stop {
+                position,
+                reached_after: (share * duration as f64).round() as u64,
+            });
+            from = (position, share);
+        }
+        let dropoff = stops.pop().expect("There's always a last leg.").position;
+        let duration = space_legs(&mut stops, duration);
+        (stops, dropoff, duration)
+    }
+
+    /// Riders whose cooldown is over try again with the same `Trip`, quoted at the current
+    /// prices. They already made up their mind, so they don't check the fare against what
+    /// they're willing to pay again. Riders who come back while `max_active_requests` are
+    /// active keep trying on the following ticks.
+    pub fn spawn_re_requests(&mut self) {
+        let max_active_requests: usize = self.max_active_requests.try_into().unwrap();
+        let mut i = 0;
+        while i < self.pending_re_requests.len() {
+            let due = self.pending_re_requests[i].0;
+            if due > self.age || self.active_requests.len() >= max_active_requests {
+                i += 1;
+                continue;
+            }
+            let (_, rider, attempt, trip, group) = self.pending_re_requests.swap_remove(i);
+            let fare = self.pricing.fare(
+                trip.duration as f64 * self.taxi_speed,
+                trip.duration,
+                trip.stops.len(),
+                self.surge_multiplier,
+            );
+            let mut request = Request::retry(
+                rider,
+                attempt,
+                self.max_waiting_time,
+                trip,
+                fare,
+                self.dwell_times(),
+            );
+            request.group = group;
+            self.stats.re_requests += 1;
+            self.submit(request);
+        }
+    }
+
+    fn dwell_times(&mut self) -> (u64, u64) {
+        (
+            dwell_time(&self.boarding_time, &mut self.rng),
+            dwell_time(&self.alighting_time, &mut self.rng),
+        )
+    }
+
+    /// Adds a freshly spawned `Request` to the `World`.
+    fn submit(&mut self, mut request: Request) {
+        request.spawned_at = self.age;
+        let event = Event::RequestSpawned {
+            request: request.id,
+            rider: request.rider,
+            pickup: request.pickup,
+            dropoff: request.dropoff,
+            party_size: request.party_size,
+        };
+        self.active_requests.push(request);
+        self.stats.requests_spawned += 1;
+        self.window.record_spawned(self.age);
+        self.emit(event);
+    }
+
+    /// Try to distribute all waiting `Request`s to the closest unoccupied `Taxi`s with enough
+    /// seats, zone by zone and group by group. `Request`s with no `Taxi` within reach are
+    /// rejected if there's a `max_pickup`.
+    pub fn distribute_unfulfilled_requests(&mut self) {
+        for group in 0..self.group_count() {
+            self.dispatch_group(group);
+        }
+    }
+
+    fn group_count(&self) -> usize {
+        if self.group_b.is_some() {
+            GROUPS.len()
+        } else {
+            1
+        }
+    }
+
+    /// How the group at index `group` in `ab_test::GROUPS` is dispatched, and every how many
+    /// ticks.
+    fn group_dispatch(&self, group: usize) -> (Zones, u64) {
+        match &self.group_b {
+            Some(b) if group == 1 => (b.zones, b.matching_interval),
+            _ => (self.zones, self.matching_interval),
+        }
+    }
+
+    fn dispatch_group(&mut self, group: usize) {
+        let delay = self.incidents.dispatch_delay();
+        let waiting: Vec<usize> = (0..self.active_requests.len())
+            .filter(|&i| {
+                let request = &self.active_requests[i];
+                request.assigned_taxi.is_none()
+                    && request.offer.is_none()
+                    && request.group == group
+                    && self.age - request.spawned_at >= delay
+            })
+            .collect();
+        let (zones, _) = self.group_dispatch(group);
+        // Only those out of reach of the whole fleet are rejected. Those whose `Taxi`s within
+        // reach are just busy keep waiting for one.
+        let (waiting, unreachable): (Vec<usize>, Vec<usize>) =
+            waiting.into_iter().partition(|&r| {
+                zones.reachable(&self.active_requests[r], &self.taxis, &self.eta, self.now())
+            });
+        for r in unreachable {
+            let request = &mut self.active_requests[r];
+            request.rejected = true;
+            request.remaining_waiting_time = 0;
+            self.maybe_re_request(r);
+        }
+        if waiting.is_empty() {
+            return;
+        }
+        self.stats.record_matching_round(waiting.len());
+
+        let matches = zones.dispatch(
+            &self.active_requests,
+            &waiting,
+            &self.taxis,
+            &self.eta,
+            self.now(),
+            self.dispatch,
+        );
+        for m in matches {
+            let (r, t) = (m.request, m.taxi);
+            if let Some(explanation) = m.explanation {
+                let event = Event::DispatchExplained {
+                    request: self.active_requests[r].id,
+                    taxi: self.taxis[t].id,
+                    zone: explanation.zone,
+                    considered: explanation.considered,
+                    candidates: explanation
+                        .candidates
+                        .into_iter()
+                        .map(|(t, eta, score)| DispatchCandidate {
+                            taxi: self.taxis[t].id,
+                            eta,
+                            score,
+                        })
+                        .collect(),
+                };
+                self.emit(event);
+            }
+            match &self.offers {
+                Some(offers) if !self.active_requests[r].escalated => {
+                    let taxi = &mut self.taxis[t];
+                    let offer = offers.profiles[taxi.profile].answer(
+                        &mut self.rng,
+                        taxi.id,
+                        self.age,
+                        offers.window,
+                    );
+                    taxi.offered = true;
+                    let request = &mut self.active_requests[r];
+                    request.offer = Some(offer);
+                    request.offered_at.get_or_insert(self.age);
+                    self.stats.offers_sent += 1;
+                    let event = Event::RideOffered {
+                        request: request.id,
+                        taxi: offer.taxi,
+                    };
+                    self.emit(event);
+                }
+                _ => self.assign(r, t),
+            }
+        }
+    }
+
+    /// Quotes the rider of the `Request` at index `r` the pickup ETA of the `Taxi` at index
+    /// `t`, and assigns the `Taxi` unless they cancel because of it.
+    fn assign(&mut self, r: usize, t: usize) {
+        let eta = self
+            .eta
+            .pickup_eta(&self.taxis[t], None, &self.active_requests[r], self.now());
+        let request = &mut self.active_requests[r];
+        request.quoted_eta = Some(eta);
+        if let Some((transit, network)) = &self.transit {
+            let journey = network.journey(request.pickup, request.dropoff, self.clock.at(self.age));
+            let taxi_ticks = eta + request.trip_duration as f64;
+            if journey.is_some_and(|j| transit.is_preferred(request.fare, taxi_ticks, j)) {
+                request.remaining_waiting_time = 0;
+                self.stats.requests_to_transit += 1;
+                return;
+            }
+        }
+        let riders = rider_choice(&mut self.rider_choice, &mut self.custom_rider_choice);
+        if !riders.accepts(&mut self.rng, request, eta) {
+            request.remaining_waiting_time = 0;
+            self.stats.requests_canceled_on_eta += 1;
+            self.maybe_re_request(r);
+            return;
+        }
+
+        let taxi = &mut self.taxis[t];
+        request.assigned_taxi = Some(taxi.id);
+        request.assigned_at = Some(self.age);
+        taxi.state = TaxiState::Occupied;
+        taxi.cruise_target = None;
+        let event = Event::RequestAssigned {
+            request: request.id,
+            taxi: taxi.id,
+            eta,
+        };
+        self.emit(event);
+    }
+
+    /// Assigns the rides whose drivers accepted their offer by now. Rides whose drivers
+    /// declined or didn't answer in time go back to dispatch, or are escalated once
+    /// `max_offers` drivers declined them.
+    pub fn resolve_offers(&mut self) {
+        let offers = match &self.offers {
+            Some(offers) => offers.clone(),
+            None => return,
+        };
+        for r in 0..self.active_requests.len() {
+            let request = &mut self.active_requests[r];
+            let offer = match request.offer {
+                Some(offer) if offer.due <= self.age && request.is_alive() => offer,
+                _ => continue,
+            };
+            request.offer = None;
+            let t = self.taxi_indices[&offer.taxi];
+            self.taxis[t].offered = false;
+            if offer.accepted {
+                self.stats.offers_accepted += 1;
+                self.assign(r, t);
+                continue;
+            }
+
+            let request = &mut self.active_requests[r];
+            request.declined_by.push(offer.taxi);
+            let escalates = request.declined_by.len() >= offers.max_offers as usize;
+            let event = Event::RideDeclined {
+                request: request.id,
+                taxi: offer.taxi,
+                timed_out: offer.times_out,
+            };
+            if offer.times_out {
+                self.stats.offers_timed_out += 1;
+            }
+            self.emit(event);
+            if escalates {
+                self.stats.requests_escalated += 1;
+                let request = &mut self.active_requests[r];
+                match offers.escalation {
+                    Escalation::Assign => request.escalated = true,
+                    Escalation::Reject => {
+                        request.rejected = true;
+                        request.remaining_waiting_time = 0;
+                        self.maybe_re_request(r);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The rider of the `Request` at index `r`, who just gave up, tries again later with the
+    /// `re_request` probability.
+    fn maybe_re_request(&mut self, r: usize) {
+        if let Some(re_request) = &self.re_request {
+            if self.rng.gen_bool(re_request.probability) {
+                let request = &self.active_requests[r];
+                let due = self
+                    .age
+                    .saturating_add(minutes_to_ticks(re_request.cooldown));
+                self.pending_re_requests.push((
+                    due,
+                    request.rider,
+                    request.attempt + 1,
+                    request.trip(),
+                    request.group,
+                ));
+            }
+        }
+    }
+
+    /// Update and tick down all `Request`s and move their `Taxi`s along, first to the pickup
+    /// and then towards the dropoff. The `Taxi`s stand still while riders get in and out.
+    pub fn update_requests(&mut self) {
+        let mut pickups = vec![];
+        for r in &mut self.active_requests {
+            if !r.is_alive() {
+                continue;
+            }
+            let taxi = match r.assigned_taxi {
+                Some(taxi_id) => &mut self.taxis[self.taxi_indices[&taxi_id]],
+                None => {
+                    r.remaining_waiting_time -= 1;
+                    r.waited += 1;
+                    let riders =
+                        rider_choice(&mut self.rider_choice, &mut self.custom_rider_choice);
+                    if r.remaining_waiting_time > 0 && riders.cancels(&mut self.rng, r) {
+                        r.remaining_waiting_time = 0;
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(remaining) = &mut r.no_show_remaining {
+                *remaining -= 1;
+                r.no_show_waited += 1;
+                continue;
+            }
+            if !r.picked_up {
+                let speed = self.eta.speed_of(taxi, self.clock.at(self.age));
+                self.stats.pickup_mileage += taxi.drive_step(r.pickup, speed);
+                if taxi.position != r.pickup {
+                    r.pickup_time += 1;
+                    continue;
+                }
+                if let Some(no_show) = &self.no_show {
+                    if self.rng.gen_bool(no_show.probability) {
+                        r.no_show_remaining = Some(minutes_to_ticks(no_show.grace_period));
+                        continue;
+                    }
+                }
+                r.picked_up = true;
+                r.picked_up_at = Some(self.age);
+                // Sampled durations assume `taxi_speed`, so other vehicles take more or less
+                // time for the same trip.
+                let factor = self.taxi_speed / taxi.speed;
+                for stop in &mut r.stops {
+                    stop.reached_after = (stop.reached_after as f64 * factor).round() as u64;
+                }
+                r.trip_duration = space_legs(
+                    &mut r.stops,
+                    (r.trip_duration as f64 * factor).round() as u64,
+                );
+                r.fulfillment_time = r.trip_duration;
+                pickups.push(Event::RequestPickedUp {
+                    request: r.id,
+                    taxi: taxi.id,
+                });
+            }
+            if r.boarding_time > 0 {
+                r.boarding_time -= 1;
+            } else if r.stop_time > 0 {
+                r.stop_time -= 1;
+                r.stop_waited += 1;
+            } else if r.fulfillment_time > 0 {
+                // Rides take as long as they were sampled to take, whatever the distance, and
+                // every leg as long as its share of that.
+                let (waypoint, leg_left) = r.next_waypoint();
+                let step = taxi.position.distance(waypoint) / leg_left as f64;
+                taxi.drive_towards(waypoint, step);
+                r.fulfillment_time -= 1;
+                if leg_left == 1 {
+                    let elapsed = r.trip_duration - r.fulfillment_time;
+                    r.leg_times.push(elapsed - r.leg_times.iter().sum::<u64>());
+                    if r.stops_reached < r.stops.len() {
+                        r.stops_reached += 1;
+                        r.stop_time = dwell_time(&self.stop_time, &mut self.rng);
+                    }
+                }
+            } else {
+                r.alighting_time -= 1;
+            }
+        }
+
+        for event in pickups {
+            self.emit(event);
+        }
+    }
+
+    /// Brings the `Taxi`s in maintenance closer to being done and lets idle ones cruise.
+    pub fn update_taxis(&mut self) {
+        for t in &mut self.taxis {
+            match t.state {
+                TaxiState::Idle => {
+                    if let Some(cruising) = &self.cruising {
+                        let target = match t.cruise_target {
+                            Some(target) if target != t.position => target,
+                            _ => {
+                                let distance = self.rng.gen::<f64>() * cruising.range;
+                                t.position.random_at_distance(
+                                    &mut self.rng,
+                                    distance,
+                                    self.city_size,
+                                )
+                            }
+                        };
+                        t.cruise_target = Some(target);
+                        let speed = self.eta.speed_of(t, self.clock.at(self.age)) * cruising.speed;
+                        self.stats.cruising_mileage += t.drive_step(target, speed);
+                    }
+                }
+                TaxiState::InMaintenance { remaining } if remaining > 1 => {
+                    t.state = TaxiState::InMaintenance {
+                        remaining: remaining - 1,
+                    }
+                }
+                TaxiState::InMaintenance { .. } => t.state = TaxiState::Idle,
+                TaxiState::LoggedOff { remaining } if remaining > 1 => {
+                    t.state = TaxiState::LoggedOff {
+                        remaining: remaining - 1,
+                    }
+                }
+                TaxiState::LoggedOff { .. } => t.state = TaxiState::Idle,
+                TaxiState::Occupied => (),
+            }
+        }
+    }
+
+    /// Moved `Request`s from `active_requests` to `archived_requests` if they have either:
+    /// 1) reached their `fulfillment_time` or
+    /// 2) reached their `remaining_waiting_time`.
+    pub fn cleanup_requests(&mut self) {
+        // First step is to clone all eligible `Request`s from `active_requests` so they can be
+        // moved to `archived_requests`.
+        let mut newly_archived = vec![];
+        for r in &mut self.active_requests {
+            if !r.is_alive() {
+                r.ended_at = Some(self.age);
+                self.stats.record_archived(r);
+                self.window.record_archived(r, self.age);
+                newly_archived.push(r.clone());
+
+                // Don't forget to reset the `Taxi` so that it may now take a `Request` again.
+                // However, this is only important if this `Request` actually had a `Taxi`
+                // assigned. In the case of a canceled `Request`, it didn't have a `Taxi`.
+                if let Some(offer) = &r.offer {
+                    self.taxis[self.taxi_indices[&offer.taxi]].offered = false;
+                }
+                if let Some(taxi_id) = r.assigned_taxi {
+                    let taxi = &mut self.taxis[self.taxi_indices[&taxi_id]];
+                    if r.outcome() == Some(RequestOutcome::Fulfilled) {
+                        taxi.trips += 1;
+                        taxi.revenue += r.fare;
+                    }
+                    taxi.finish_trip(self.maintenance.as_ref());
+                }
+            }
+        }
+
+        // Second step is to bulk delete all th
+        self.active_requests.retain(|r| r.is_alive());
+
+        for r in newly_archived {
+            self.notify(|o, world| o.on_archived(world, &r));
+            self.archived_requests.push(r, self.age);
+        }
+    }
+
+    /// Whether nothing at all happens until the next `Request` spawns or riders come out of a
+    /// `Venue`. Never while an `arrivals` process or demand layers run, since their rates change
+    /// from tick to tick.
+    fn is_quiet(&self) -> bool {
+        self.arrivals.is_none()
+            && self.layers.is_empty()
+            && self.active_requests.is_empty()
+            && self.venue_queues.iter().all(|q| q.is_empty())
+            && self.cruising.is_none()
+            && self.incidents.active().next().is_none()
+            && self.taxis.iter().all(|t| t.is_idle())
+    }
+
+    /// Skips over the ticks before the next `Request` spawns, a rider tries again, a `Burst`
+    /// starts or the run ends, whichever comes first. Only valid while the `World` is quiet.
+    fn skip_quiet_ticks(&mut self) {
+        // The number of ticks without a spawn is geometrically distributed.
+        let p = self.request_spawn_chance;
+        let until_spawn = if self.max_active_requests == 0 || p <= 0.0 {
+            u64::MAX
+        } else if p >= 1.0 {
+            0
+        } else {
+            let u: f64 = self.rng.gen();
+            ((1.0 - u).ln() / (1.0 - p).ln()).floor() as u64
+        };
+        let until_re_request = self
+            .pending_re_requests
+            .iter()
+            .map(|&(due, ..)| due.saturating_sub(self.age + 1))
+            .min()
+            .unwrap_or(u64::MAX);
+        let until_burst = self
+            .venues
+            .iter()
+            .flat_map(|v| &v.bursts)
+            .filter_map(|b| b.next_after(self.age))
+            .map(|next| next - (self.age + 1))
+            .min()
+            .unwrap_or(u64::MAX);
+        let until_incident = self.incidents.next_after(self.age).unwrap_or(u64::MAX);
+        let until_end = self.runtime.saturating_sub(self.age);
+
+        let skip = until_spawn
+            .min(until_re_request)
+            .min(until_burst)
+            .min(until_incident)
+            .min(until_end);
+        // Otherwise the spawn is past the skipped ticks, and since the geometric distribution
+        // is memoryless, the following ticks can just roll again.
+        self.spawn_due = skip == until_spawn;
+        self.stats
+            .record_quiet_ticks(&self.taxis, self.age + 1, skip);
+        self.window
+            .record_quiet_ticks(&self.taxis, self.age + 1, skip);
+        self.age += skip;
+    }
+
+    /// Advances the `World` by a single tick, or by more than that if it is quiet and has an
+    /// adaptive time step.
+    pub fn tick(&mut self) {
+        if self.adaptive_time_step && self.is_quiet() {
+            self.skip_quiet_ticks();
+        }
+        self.age += 1;
+
+        self.update_incidents();
+        self.update_surge();
+        self.spawn_re_requests();
+        self.maybe_spawn_request();
+        self.spawn_venue_requests();
+        self.spawn_spike_requests();
+        self.spawn_layer_requests();
+        self.resolve_offers();
+        for group in 0..self.group_count() {
+            if self.age.is_multiple_of(self.group_dispatch(group).1) {
+                self.dispatch_group(group);
+            }
+        }
+        self.stats
+            .record_tick(&self.taxis, self.surge_multiplier, self.age);
+        self.window.record_tick(&self.taxis, self.age);
+        self.update_requests();
+        self.update_taxis();
+        self.cleanup_requests();
+
+        self.notify(|o, world| o.on_tick(world));
+
+        if self.checks_invariants {
+            if let Err(violation) = self.check_invariants() {
+                panic!("Invariant violated after tick {}: {}", self.age, violation);
+            }
+        }
+    }
+
+    /// Whether `age` has passed `runtime`.
+    pub fn is_done(&self) -> bool {
+        self.age > self.runtime
+    }
+
+    /// Runs until `age` reaches `runtime`.
+    pub fn run_till_done(&mut self) {
+        while !self.is_done() {
+            self.tick();
+        }
+        self.flush().expect("Couldn't flush the run's output.");
+    }
+
+    /// Writes out anything which is still buffered, such as spilled `Request`s or the output
+    /// of `Observer`s.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.archived_requests.flush()?;
+        for o in &mut self.observers {
+            o.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Key metrics of the run so far.
+    pub fn summary(&self) -> Summary {
+        Summary {
+            units: self.units.clone(),
+            ..self.stats.summary(&self.costs, &self.taxis)
+        }
+    }
+
+    /// What reports on this run are written in, if the `Scenario` says.
+    pub fn units(&self) -> Option<&Units> {
+        self.units.as_ref()
+    }
+
+    /// Key metrics of the last `rolling_window` minutes up to now.
+    pub fn recent(&self) -> WindowSummary {
+        self.window.summary(self.age)
+    }
+
+    /// What every `Taxi` earned and cost over the run so far.
+    pub fn taxi_earnings(&self) -> Vec<TaxiEarnings> {
+        self.taxis
+            .iter()
+            .map(|t| {
+                let costs = self
+                    .costs
+                    .for_vehicle(&self.vehicle_types[t.vehicle_type])
+                    .of(t.mileage, self.age);
+                TaxiEarnings {
+                    id: t.id,
+                    trips: t.trips,
+                    revenue: t.revenue,
+                    costs,
+                    profit: t.revenue - costs,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Makes every leg of a ride with `stops` in that order take at least a tick, and returns the
+/// `duration` of the whole ride, which may have to be longer for that.
+fn space_legs(stops: &mut [Stop], duration: u64) -> u64 {
+    let mut previous = 0;
+    for stop in stops.iter_mut() {
+        stop.reached_after = stop.reached_after.max(previous + 1);
+        previous = stop.reached_after;
+    }
+    if stops.is_empty() {
+        duration
+    } else {
+        duration.max(previous + 1)
+    }
+}
+
+/// How long a stop takes according to `distribution`, if there's one.
+fn dwell_time(distribution: &Option<Distribution>, rng: &mut Pcg64Mcg) -> u64 {
+    distribution.as_ref().map_or(0, |d| d.sample_ticks(rng))
+}
+
+/// The model riders decide by, which are the fields of the same names of a `World`. Taking
+/// them apart lets the model decide with the `World`'s generator while other fields are
+/// borrowed.
+fn rider_choice<'a>(
+    rider_choice: &'a mut RiderChoice,
+    custom_rider_choice: &'a mut Option<Box<dyn RiderChoiceModel>>,
+) -> &'a mut dyn RiderChoiceModel {
+    match custom_rider_choice {
+        Some(model) => model.as_mut(),
+        None => rider_choice,
+    }
+}
+
+impl fmt::Display for World {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let num_occupied_taxis = self.taxis.iter().filter(|t| t.is_occupied()).count();
+        let num_total_taxis = self.taxis.len();
+        let num_assigned_requests = self
+            .active_requests
+            .iter()
+            .filter(|r| r.assigned_taxi.is_some())
+            .count();
+        let num_waiting_requests = self
+            .active_requests
+            .iter()
+            .filter(|r| r.assigned_taxi.is_none())
+            .count();
+        let num_archived_requests = self.archived_requests.total();
+        write!(
+            f,
+            "Age: {}/{} ({}), Taxis: {} Occ/{} Tot, Requests: {} Asnd/{} Wai/{} Arch",
+            self.age,
+            self.runtime,
+            self.now(),
+            num_occupied_taxis,
+            num_total_taxis,
+            num_assigned_requests,
+            num_waiting_requests,
+            num_archived_requests,
+        )
+    }
+}
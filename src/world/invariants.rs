@@ -0,0 +1,187 @@
+//! Structural invariants of a `World` which hold between ticks.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use uuid::Uuid;
+
+use super::{TaxiState, World};
+
+/// The first thing found to be wrong with a `World`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvariantViolation {
+    /// More than one active `Request` is assigned to the same `Taxi`.
+    TaxiAssignedTwice { taxi: Uuid },
+
+    /// An active `Request` is assigned to a `Taxi` which doesn't exist.
+    MissingTaxi { request: Uuid, taxi: Uuid },
+
+    /// An active `Request` is assigned to a `Taxi` which isn't occupied.
+    AssignedTaxiNotOccupied { request: Uuid, taxi: Uuid },
+
+    /// A `Taxi` is occupied without any active `Request` assigned to it.
+    OccupiedTaxiWithoutRequest { taxi: Uuid },
+
+    /// An active `Request` is offered to a `Taxi` which isn't idle, or isn't marked as
+    /// offered a ride.
+    OfferedTaxiUnavailable { request: Uuid, taxi: Uuid },
+
+    /// A `Taxi` is marked as offered a ride which no active `Request` is offered to it.
+    OfferedTaxiWithoutRequest { taxi: Uuid },
+
+    /// A `Request` which is no longer alive wasn't archived.
+    DeadRequestActive { request: Uuid },
+
+    /// A `Request` which is still alive was archived.
+    ArchivedRequestAlive { request: Uuid },
+
+    /// An archived `Request` changed after it was archived.
+    ArchivedRequestMutated { request: Uuid },
+
+    /// Spawned `Request`s are neither active nor archived, or the other way around.
+    RequestsUnaccounted {
+        spawned: u64,
+        active: u64,
+        archived: u64,
+    },
+
+    /// A `Taxi` left the city.
+    TaxiOutsideCity { taxi: Uuid },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::TaxiAssignedTwice { taxi } => {
+                write!(f, "taxi {} is assigned to more than one request", taxi)
+            }
+            InvariantViolation::MissingTaxi { request, taxi } => write!(
+                f,
+                "request {} is assigned to taxi {} which doesn't exist",
+                request, taxi
+            ),
+            InvariantViolation::AssignedTaxiNotOccupied { request, taxi } => write!(
+                f,
+                "request {} is assigned to taxi {} which isn't occupied",
+                request, taxi
+            ),
+            InvariantViolation::OccupiedTaxiWithoutRequest { taxi } => {
+                write!(f, "taxi {} is occupied without a request", taxi)
+            }
+            InvariantViolation::OfferedTaxiUnavailable { request, taxi } => write!(
+                f,
+                "request {} is offered to taxi {} which can't answer it",
+                request, taxi
+            ),
+            InvariantViolation::OfferedTaxiWithoutRequest { taxi } => {
+                write!(f, "taxi {} is offered a ride without a request", taxi)
+            }
+            InvariantViolation::DeadRequestActive { request } => {
+                write!(f, "request {} is dead but still active", request)
+            }
+            InvariantViolation::ArchivedRequestAlive { request } => {
+                write!(f, "request {} is archived but still alive", request)
+            }
+            InvariantViolation::ArchivedRequestMutated { request } => {
+                write!(f, "request {} changed after it was archived", request)
+            }
+            InvariantViolation::RequestsUnaccounted {
+                spawned,
+                active,
+                archived,
+            } => write!(
+                f,
+                "{} requests were spawned but {} are active and {} archived",
+                spawned, active, archived
+            ),
+            InvariantViolation::TaxiOutsideCity { taxi } => {
+                write!(f, "taxi {} is outside the city", taxi)
+            }
+        }
+    }
+}
+
+impl Error for InvariantViolation {}
+
+impl World {
+    /// Verifies the structural invariants which hold between ticks, e.g. that no `Taxi` is
+    /// assigned to two live `Request`s and that archived `Request`s never change.
+    ///
+    /// This goes through everything in memory, so it's meant for debugging and testing. See
+    /// `set_check_invariants` to have it run after every tick.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        let mut assignments = HashMap::new();
+        let mut offers = HashMap::new();
+        for r in &self.active_requests {
+            if !r.is_alive() {
+                return Err(InvariantViolation::DeadRequestActive { request: r.id });
+            }
+            if let Some(offer) = &r.offer {
+                let available = self
+                    .taxi_indices
+                    .get(&offer.taxi)
+                    .and_then(|&i| self.taxis.get(i))
+                    .is_some_and(|t| t.state == TaxiState::Idle && t.offered);
+                if !available || offers.insert(offer.taxi, r.id).is_some() {
+                    return Err(InvariantViolation::OfferedTaxiUnavailable {
+                        request: r.id,
+                        taxi: offer.taxi,
+                    });
+                }
+            }
+            let taxi_id = match r.assigned_taxi {
+                Some(taxi_id) => taxi_id,
+                None => continue,
+            };
+            if assignments.insert(taxi_id, r.id).is_some() {
+                return Err(InvariantViolation::TaxiAssignedTwice { taxi: taxi_id });
+            }
+            let taxi = self
+                .taxi_indices
+                .get(&taxi_id)
+                .and_then(|&i| self.taxis.get(i))
+                .filter(|t| t.id == taxi_id)
+                .ok_or(InvariantViolation::MissingTaxi {
+                    request: r.id,
+                    taxi: taxi_id,
+                })?;
+            if taxi.state != TaxiState::Occupied {
+                return Err(InvariantViolation::AssignedTaxiNotOccupied {
+                    request: r.id,
+                    taxi: taxi_id,
+                });
+            }
+        }
+
+        for t in &self.taxis {
+            if t.state == TaxiState::Occupied && !assignments.contains_key(&t.id) {
+                return Err(InvariantViolation::OccupiedTaxiWithoutRequest { taxi: t.id });
+            }
+            if t.offered && !offers.contains_key(&t.id) {
+                return Err(InvariantViolation::OfferedTaxiWithoutRequest { taxi: t.id });
+            }
+            let within = |c: f64| (0.0..=self.city_size).contains(&c);
+            if !within(t.position.x) || !within(t.position.y) {
+                return Err(InvariantViolation::TaxiOutsideCity { taxi: t.id });
+            }
+        }
+
+        if let Some(r) = self.archived_requests.iter().find(|r| r.is_alive()) {
+            return Err(InvariantViolation::ArchivedRequestAlive { request: r.id });
+        }
+        if let Some(r) = self.archived_requests.first_mutated() {
+            return Err(InvariantViolation::ArchivedRequestMutated { request: r.id });
+        }
+
+        let active = self.active_requests.len() as u64;
+        let archived = self.archived_requests.total();
+        if self.stats.requests_spawned != active + archived {
+            return Err(InvariantViolation::RequestsUnaccounted {
+                spawned: self.stats.requests_spawned,
+                active,
+                archived,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,609 @@
+//! Dispatch in a city split into a grid of zones.
+//!
+//! Matching waiting `Request`s to idle `Taxi`s is by far the most expensive part of a tick in
+//! large cities. Zones are matched independently of each other, so they can be spread over
+//! several threads. `Request`s which found no `Taxi` in their own zone are matched against all
+//! `Taxi`s which are still free in a merge phase afterwards. Since every zone is matched the
+//! same way no matter which thread it ends up on, the number of threads never changes the
+//! outcome of a run.
+//!
+//! `Request`s are normally matched one after the other as they come in. Batches of `Request`s
+//! which accumulated over several ticks are matched all at once instead, which is slower to
+//! react but makes better use of the `Taxi`s.
+//!
+//! Dispatch normally gives every `Request` the `Taxi` which gets there first, but it can also
+//! trade that off against the distance `Taxi`s drive empty and how evenly drivers earn, by
+//! weighing those objectives in a score.
+//!
+//! Operators may refuse to send a `Taxi` further than some distance or ETA to a pickup. Such
+//! `Taxi`s are never offered the `Request`, and `Request`s which no `Taxi` of the fleet is
+//! within reach of, busy or not, are rejected instead of waiting.
+//!
+//! Dispatch can also explain itself, listing the candidates it picked each `Taxi` from, to
+//! debug assignments which look wrong.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::thread;
+
+use crate::eta::EtaEstimator;
+use crate::position::Position;
+use crate::time::{minutes_as_ticks, SimTime};
+use crate::world::{Request, Taxi};
+
+/// How many of the closest candidates an `Explanation` lists.
+pub const EXPLAINED_CANDIDATES: usize = 10;
+
+/// How dispatch goes about its work, which never changes whom it assigns to whom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DispatchOptions {
+    /// How many threads zones are matched on.
+    pub threads: usize,
+
+    /// Whether to explain every match.
+    pub explain: bool,
+}
+
+impl Default for DispatchOptions {
+    fn default() -> DispatchOptions {
+        DispatchOptions {
+            threads: 1,
+            explain: false,
+        }
+    }
+}
+
+/// How much each objective counts towards the score dispatch minimizes for every match, as
+/// configured in the `[dispatch_weights]` section of a `Scenario`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DispatchWeights {
+    /// Per tick of pickup ETA.
+    pub wait: f64,
+
+    /// Per km the `Taxi` drives empty to the pickup.
+    pub empty_distance: f64,
+
+    /// Per unit of revenue the driver earned above the fleet average, or below it to favor
+    /// those who earned less.
+    pub fairness: f64,
+
+    /// Per trip the driver made above the fleet average. Drivers at or below the average
+    /// aren't held back, so this balances assignments without favoring anybody.
+    pub balance: f64,
+}
+
+impl Default for DispatchWeights {
+    fn default() -> DispatchWeights {
+        DispatchWeights {
+            wait: 1.0,
+            empty_distance: 0.0,
+            fairness: 0.0,
+            balance: 0.0,
+        }
+    }
+}
+
+/// How far dispatch sends a `Taxi` to a pickup at most, as configured in the `[max_pickup]`
+/// section of a `Scenario`. Either limit may be missing.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaxPickup {
+    /// In km as the crow flies.
+    pub distance: Option<f64>,
+
+    /// Pickup ETA in minutes.
+    pub eta: Option<f64>,
+}
+
+/// A `Request` assigned to a `Taxi`, both by index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub request: usize,
+    pub taxi: usize,
+
+    /// Only if dispatch was asked to explain itself.
+    pub explanation: Option<Explanation>,
+}
+
+/// Why dispatch picked the `Taxi` it did for a `Request`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// Zone the match was made in, or `None` in the merge phase across zones.
+    pub zone: Option<usize>,
+
+    /// How many free `Taxi`s were looked at, including those which couldn't take the
+    /// `Request`.
+    pub considered: usize,
+
+    /// Up to `EXPLAINED_CANDIDATES` `Taxi`s which could still take the `Request` when its turn
+    /// came, by index, with their pickup ETAs and scores, lowest score first. The first one
+    /// got it.
+    pub candidates: Vec<(usize, f64, f64)>,
+}
+
+/// A grid of `per_side` by `per_side` square zones over the city.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Zones {
+    per_side: u32,
+    city_size: f64,
+
+    /// Whether `Request`s are matched as a batch rather than one after the other.
+    batch: bool,
+
+    weights: DispatchWeights,
+    max_pickup: Option<MaxPickup>,
+}
+
+impl Zones {
+    pub fn new(per_side: u32, city_size: f64) -> Zones {
+        Zones {
+            per_side: per_side.max(1),
+            city_size,
+            batch: false,
+            weights: DispatchWeights::default(),
+            max_pickup: None,
+        }
+    }
+
+    /// Matches all `Request`s of a zone at once, always picking the pair with the shortest
+    /// pickup ETA of all those left. Meant for batches which accumulated over several ticks.
+    pub fn batched(self, batch: bool) -> Zones {
+        Zones { batch, ..self }
+    }
+
+    /// Minimizes a score weighing several objectives rather than just the pickup ETA.
+    pub fn weighted(self, weights: DispatchWeights) -> Zones {
+        Zones { weights, ..self }
+    }
+
+    /// Never offers `Taxi`s beyond `max_pickup` any `Request`.
+    pub fn limited(self, max_pickup: Option<MaxPickup>) -> Zones {
+        Zones { max_pickup, ..self }
+    }
+
+    /// Whether any of `taxis` which fits `request`, free or not, is within `max_pickup` of its
+    /// pickup from where it is at `now`. Always true without limits.
+    pub fn reachable(
+        &self,
+        request: &Request,
+        taxis: &[Taxi],
+        eta: &EtaEstimator,
+        now: SimTime,
+    ) -> bool {
+        let max_pickup = match self.max_pickup {
+            Some(max_pickup) => max_pickup,
+            None => return true,
+        };
+        taxis.iter().any(|taxi| {
+            let (from, to) = (taxi.position(), request.pickup());
+            taxi.fits(request)
+                && max_pickup
+                    .distance
+                    .is_none_or(|limit| from.distance(to) <= limit)
+                && max_pickup.eta.is_none_or(|limit| {
+                    eta.travel_time(taxi, from, to, now) <= minutes_as_ticks(limit)
+                })
+        })
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        (self.per_side * self.per_side) as usize
+    }
+
+    /// Index of the zone `position` lies in. Positions on the far edges of the city belong to
+    /// the last zone.
+    pub fn of(&self, position: Position) -> usize {
+        let cell = |coordinate: f64| {
+            if self.city_size <= 0.0 {
+                return 0;
+            }
+            let cell = (coordinate / self.city_size * f64::from(self.per_side)) as u32;
+            cell.min(self.per_side - 1)
+        };
+        (cell(position.y) * self.per_side + cell(position.x)) as usize
+    }
+
+    /// A uniformly random position in the zone at index `zone`.
+    pub fn random_in<R: Rng + ?Sized>(&self, rng: &mut R, zone: usize) -> Position {
+        let size = self.city_size / f64::from(self.per_side);
+        let (row, column) = (zone as u32 / self.per_side, zone as u32 % self.per_side);
+        Position::new(
+            (f64::from(column) + rng.gen::<f64>()) * size,
+            (f64::from(row) + rng.gen::<f64>()) * size,
+        )
+    }
+
+    /// Assigns `waiting` `Request`s to the `Taxi`s with the lowest score at `now` which can
+    /// take them, first within each zone and then across zones. One after the other
+    /// in order, or as a batch if the `Zones` are `batched`. Returns the
+    /// `Match`es sorted by `Request`.
+    pub fn dispatch(
+        &self,
+        requests: &[Request],
+        waiting: &[usize],
+        taxis: &[Taxi],
+        eta: &EtaEstimator,
+        now: SimTime,
+        options: DispatchOptions,
+    ) -> Vec<Match> {
+        let matcher = Matcher {
+            requests,
+            taxis,
+            eta,
+            now,
+            explain: options.explain,
+            batch: self.batch,
+            weights: self.weights,
+            max_pickup: self.max_pickup,
+            mean_revenue: taxis.iter().map(|t| t.revenue()).sum::<f64>()
+                / taxis.len().max(1) as f64,
+            mean_trips: taxis.iter().map(|t| t.trips()).sum::<u64>() as f64
+                / taxis.len().max(1) as f64,
+        };
+        let mut zone_requests = vec![vec![]; self.count()];
+        for &r in waiting {
+            zone_requests[self.of(requests[r].pickup())].push(r);
+        }
+        let mut zone_taxis = vec![vec![]; self.count()];
+        for (i, t) in taxis.iter().enumerate() {
+            if t.is_idle() {
+                zone_taxis[self.of(t.position())].push(i);
+            }
+        }
+
+        let zones: Vec<_> = zone_requests
+            .into_iter()
+            .zip(zone_taxis)
+            .enumerate()
+            .filter(|(_, (requests, taxis))| !requests.is_empty() && !taxis.is_empty())
+            .collect();
+        let threads = options.threads.clamp(1, zones.len().max(1));
+        let mut matches: Vec<Match> = if threads == 1 {
+            zones
+                .into_iter()
+                .flat_map(|(zone, (r, mut t))| matcher.assign(&r, &mut t, Some(zone)))
+                .collect()
+        } else {
+            let chunk_size = zones.len().div_ceil(threads);
+            thread::scope(|scope| {
+                let workers: Vec<_> = zones
+                    .chunks(chunk_size)
+                    .map(|chunk| {
+                        scope.spawn(move || {
+                            chunk
+                                .iter()
+                                .flat_map(|(zone, (r, t))| {
+                                    matcher.assign(r, &mut t.clone(), Some(*zone))
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|w| w.join().expect("A dispatch thread panicked."))
+                    .collect()
+            })
+        };
+
+        // Merge phase: whoever is left gets a go at all the remaining `Taxi`s.
+        let mut taken = vec![false; taxis.len()];
+        let mut matched = vec![false; requests.len()];
+        for m in &matches {
+            taken[m.taxi] = true;
+            matched[m.request] = true;
+        }
+        let mut free: Vec<usize> = (0..taxis.len())
+            .filter(|&t| taxis[t].is_idle() && !taken[t])
+            .collect();
+        let leftover: Vec<usize> = waiting.iter().copied().filter(|&r| !matched[r]).collect();
+        matches.extend(matcher.assign(&leftover, &mut free, None));
+
+        matches.sort_unstable_by_key(|m| m.request);
+        matches
+    }
+}
+
+/// What every zone needs to be matched.
+#[derive(Clone, Copy)]
+struct Matcher<'a> {
+    requests: &'a [Request],
+    taxis: &'a [Taxi],
+    eta: &'a EtaEstimator,
+    now: SimTime,
+    explain: bool,
+    batch: bool,
+    weights: DispatchWeights,
+    max_pickup: Option<MaxPickup>,
+
+    /// Revenue and trips of the average `Taxi`, for fairness and balance.
+    mean_revenue: f64,
+    mean_trips: f64,
+}
+
+impl Matcher<'_> {
+    /// Pickup ETA and score of `taxi` for `request`, or `None` if it can't take `request` or
+    /// is beyond `max_pickup`.
+    fn score(&self, taxi: usize, request: &Request) -> Option<(f64, f64)> {
+        let taxi = &self.taxis[taxi];
+        if !taxi.can_take(request) {
+            return None;
+        }
+        if let Some(limit) = self.max_pickup.and_then(|m| m.distance) {
+            if taxi.position().distance(request.pickup()) > limit {
+                return None;
+            }
+        }
+        let eta = self.eta.pickup_eta(taxi, None, request, self.now);
+        // E.g. while a road closure brings traffic to a halt.
+        if !eta.is_finite() {
+            return None;
+        }
+        if let Some(limit) = self.max_pickup.and_then(|m| m.eta) {
+            if eta > minutes_as_ticks(limit) {
+                return None;
+            }
+        }
+        let mut score = 0.0;
+        if self.weights.wait != 0.0 {
+            score += self.weights.wait * eta;
+        }
+        if self.weights.empty_distance != 0.0 {
+            score += self.weights.empty_distance * taxi.position().distance(request.pickup());
+        }
+        if self.weights.fairness != 0.0 {
+            score += self.weights.fairness * (taxi.revenue() - self.mean_revenue);
+        }
+        if self.weights.balance != 0.0 {
+            score += self.weights.balance * (taxi.trips() as f64 - self.mean_trips).max(0.0);
+        }
+        Some((eta, score))
+    }
+
+    /// Assigns `free` `Taxi`s to `waiting` `Request`s with `closest` or as a `batch`.
+    fn assign(&self, waiting: &[usize], free: &mut Vec<usize>, zone: Option<usize>) -> Vec<Match> {
+        if self.batch {
+            self.batch(waiting, free, zone)
+        } else {
+            self.closest(waiting, free, zone)
+        }
+    }
+
+    /// Greedily gives each of `waiting` in order the `free` `Taxi` with the lowest score which
+    /// can take it. Matched `Taxi`s are removed from `free`. `zone` is only for
+    /// explanations.
+    fn closest(&self, waiting: &[usize], free: &mut Vec<usize>, zone: Option<usize>) -> Vec<Match> {
+        let mut matches = vec![];
+        for &r in waiting {
+            if free.is_empty() {
+                // A smaller party might still fit into some free `Taxi`, but once all of them
+                // are taken there's no point in looking any further.
+                break;
+            }
+            let request = &self.requests[r];
+            let mut candidates: Vec<(usize, f64, f64)> = free
+                .iter()
+                .enumerate()
+                .filter_map(|(i, &t)| {
+                    let (eta, score) = self.score(t, request)?;
+                    Some((i, eta, score))
+                })
+                .collect();
+            let by_score = |a: &(usize, f64, f64), b: &(usize, f64, f64)| a.2.total_cmp(&b.2);
+            let closest = match candidates.iter().copied().min_by(by_score) {
+                Some((i, ..)) => i,
+                None => continue,
+            };
+            let explanation = if self.explain {
+                // Stable, so the one which got picked stays in front of ties.
+                candidates.sort_by(by_score);
+                candidates.truncate(EXPLAINED_CANDIDATES);
+                Some(Explanation {
+                    zone,
+                    considered: free.len(),
+                    candidates: candidates
+                        .into_iter()
+                        .map(|(i, eta, score)| (free[i], eta, score))
+                        .collect(),
+                })
+            } else {
+                None
+            };
+            matches.push(Match {
+                request: r,
+                taxi: free.remove(closest),
+                explanation,
+            });
+        }
+        matches
+    }
+
+    /// Matches `waiting` to `free` all at once, always picking the pair with the lowest score
+    /// of all those left, so that a `Request` can't take a `Taxi` from another one which is
+    /// much closer to it. Matched `Taxi`s are removed from `free`.
+    fn batch(&self, waiting: &[usize], free: &mut Vec<usize>, zone: Option<usize>) -> Vec<Match> {
+        // Scores and ETAs with positions in `waiting` and `free`.
+        let mut pairs: Vec<(f64, f64, usize, usize)> = vec![];
+        for (i, &r) in waiting.iter().enumerate() {
+            let request = &self.requests[r];
+            for (j, &t) in free.iter().enumerate() {
+                if let Some((eta, score)) = self.score(t, request) {
+                    pairs.push((score, eta, i, j));
+                }
+            }
+        }
+        // Stable, so ties go to earlier `Request`s and `Taxi`s like in `closest`.
+        pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut matched = vec![false; waiting.len()];
+        let mut taken = vec![false; free.len()];
+        let mut matches = vec![];
+        for (k, &(_, _, i, j)) in pairs.iter().enumerate() {
+            if matched[i] || taken[j] {
+                continue;
+            }
+            let explanation = if self.explain {
+                Some(Explanation {
+                    zone,
+                    considered: free.len(),
+                    candidates: pairs[k..]
+                        .iter()
+                        .filter(|&&(_, _, other, t)| other == i && !taken[t])
+                        .take(EXPLAINED_CANDIDATES)
+                        .map(|&(score, eta, _, t)| (free[t], eta, score))
+                        .collect(),
+                })
+            } else {
+                None
+            };
+            matched[i] = true;
+            taken[j] = true;
+            matches.push(Match {
+                request: waiting[i],
+                taxi: free[j],
+                explanation,
+            });
+        }
+
+        let mut j = 0;
+        free.retain(|_| {
+            j += 1;
+            !taken[j - 1]
+        });
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roads::Roads;
+    use crate::time::{Clock, Start};
+    use crate::vehicle::VehicleTypes;
+    use crate::world::Trip;
+
+    fn request(x: f64, y: f64) -> Request {
+        let trip = Trip {
+            pickup: Position::new(x, y),
+            dropoff: Position::new(x, y),
+            stops: vec![],
+            duration: 1,
+            party_size: 1,
+            vehicle_types: VehicleTypes::ALL,
+        };
+        Request::new(100, trip, 0.0, (0, 0))
+    }
+
+    fn taxi(x: f64, y: f64, speed: f64) -> Taxi {
+        Taxi::new(0, 4, speed, Position::new(x, y))
+    }
+
+    /// `(request, taxi)` pairs `zones` matches all of `requests` to, in a city of 10 km.
+    fn dispatch(zones: Zones, requests: &[Request], taxis: &[Taxi]) -> Vec<(usize, usize)> {
+        let eta = EtaEstimator::new(vec![], Roads::new(&[]));
+        let now = Clock::new(Start::default()).at(0);
+        let waiting: Vec<usize> = (0..requests.len()).collect();
+        zones
+            .dispatch(
+                requests,
+                &waiting,
+                taxis,
+                &eta,
+                now,
+                DispatchOptions::default(),
+            )
+            .into_iter()
+            .map(|m| (m.request, m.taxi))
+            .collect()
+    }
+
+    #[test]
+    fn weights_trade_the_wait_off_against_the_empty_distance() {
+        // The slow one is 1 km away at 100 ticks, the fast one 3 km away at 3 ticks.
+        let requests = [request(1.0, 1.0)];
+        let taxis = [taxi(2.0, 1.0, 0.01), taxi(4.0, 1.0, 1.0)];
+        let zones = Zones::new(2, 10.0);
+        assert_eq!(dispatch(zones, &requests, &taxis), [(0, 1)]);
+        let weights = DispatchWeights {
+            wait: 0.0,
+            empty_distance: 1.0,
+            ..DispatchWeights::default()
+        };
+        assert_eq!(
+            dispatch(zones.weighted(weights), &requests, &taxis),
+            [(0, 0)]
+        );
+    }
+
+    #[test]
+    fn taxis_beyond_max_pickup_are_never_offered() {
+        let requests = [request(1.0, 1.0)];
+        let taxis = [taxi(2.0, 1.0, 0.01), taxi(4.0, 1.0, 1.0)];
+        let by_distance = Zones::new(2, 10.0).limited(Some(MaxPickup {
+            distance: Some(2.5),
+            eta: None,
+        }));
+        assert_eq!(dispatch(by_distance, &requests, &taxis), [(0, 0)]);
+
+        // The slow one takes longer than a minute, even though it drives the least.
+        let weights = DispatchWeights {
+            wait: 0.0,
+            empty_distance: 1.0,
+            ..DispatchWeights::default()
+        };
+        let by_eta = Zones::new(2, 10.0)
+            .weighted(weights)
+            .limited(Some(MaxPickup {
+                distance: None,
+                eta: Some(1.0),
+            }));
+        assert_eq!(dispatch(by_eta, &requests, &taxis), [(0, 1)]);
+
+        // Nobody is within 2.5 km of the far corner.
+        let requests = [request(9.0, 9.0)];
+        assert!(dispatch(by_distance, &requests, &taxis).is_empty());
+        let eta = EtaEstimator::new(vec![], Roads::new(&[]));
+        let now = Clock::new(Start::default()).at(0);
+        assert!(!by_distance.reachable(&requests[0], &taxis, &eta, now));
+        assert!(Zones::new(2, 10.0).reachable(&requests[0], &taxis, &eta, now));
+    }
+
+    #[test]
+    fn zones_are_matched_before_the_leftovers_are_merged() {
+        // The one in the next zone is closer, but the one in the same zone is matched first.
+        let requests = [request(4.0, 4.0), request(9.0, 4.0)];
+        let taxis = [taxi(1.0, 1.0, 1.0), taxi(6.0, 4.0, 1.0)];
+        assert_eq!(
+            dispatch(Zones::new(2, 10.0), &requests, &taxis),
+            [(0, 0), (1, 1)]
+        );
+        assert_eq!(
+            dispatch(Zones::new(1, 10.0), &requests, &taxis),
+            [(0, 1), (1, 0)]
+        );
+
+        // Nobody is in the far corner's zone, so it gets the one left in another zone.
+        let requests = [request(1.0, 1.0), request(9.0, 9.0)];
+        let taxis = [taxi(2.0, 1.0, 1.0), taxi(6.0, 1.0, 1.0)];
+        assert_eq!(
+            dispatch(Zones::new(2, 10.0), &requests, &taxis),
+            [(0, 0), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn batches_match_the_closest_pair_first() {
+        // In order, the first request takes the taxi the second one is much closer to.
+        let requests = [request(1.2, 1.0), request(2.5, 1.0)];
+        let taxis = [taxi(2.0, 1.0, 1.0), taxi(0.0, 1.0, 1.0)];
+        let zones = Zones::new(2, 10.0);
+        assert_eq!(dispatch(zones, &requests, &taxis), [(0, 0), (1, 1)]);
+        assert_eq!(
+            dispatch(zones.batched(true), &requests, &taxis),
+            [(0, 1), (1, 0)]
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Randomized stress test of the tick loop, run with
+//! `cargo test --profile stress --features stress --test stress`.
+//!
+//! Generates `Scenario`s from adversarial parameter combinations, like no taxis at all, a
+//! spawn chance of 1, no room for active requests or zero-length trips, and runs them with the
+//! invariant checker on, with and without the adaptive time step. The `stress` profile keeps
+//! overflow checks on, so any under- or overflow of the counters fails the run as well.
+//!
+//! `STRESS_TICKS` sets how many ticks are driven in total, `STRESS_SEED` which scenarios are
+//! generated. A failing scenario is printed as TOML to rerun it with `run --scenario`.
+#![cfg(feature = "stress")]
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+
+use taxi_simulation::archive::ArchivePolicy;
+use taxi_simulation::arrivals::Arrivals;
+use taxi_simulation::demand::{DemandLayer, DemandSource};
+use taxi_simulation::distribution::Distribution;
+use taxi_simulation::eta::EtaCancellation;
+use taxi_simulation::incidents::{Effect, Incident};
+use taxi_simulation::offers::{DriverProfile, Escalation, Offers};
+use taxi_simulation::position::Position;
+use taxi_simulation::rider_choice::RiderChoice;
+use taxi_simulation::scenario::{Cruising, Maintenance, NoShow, ReRequest, Scenario};
+use taxi_simulation::transit::{Network, Transit};
+use taxi_simulation::travel_times::TravelTimes;
+use taxi_simulation::venue::Burst;
+use taxi_simulation::world::World;
+use taxi_simulation::zones::MaxPickup;
+
+const DEFAULT_TICKS: u64 = 2_000_000;
+
+fn env_or(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+fn pick<T: Clone, R: Rng>(rng: &mut R, choices: &[T]) -> T {
+    choices.choose(rng).expect("There are choices.").clone()
+}
+
+/// Durations in ticks or minutes, mostly the degenerate ones.
+fn duration<R: Rng>(rng: &mut R) -> Distribution {
+    match rng.gen_range(0, 4) {
+        0 => Distribution::Constant { value: 0.0 },
+        1 => Distribution::Constant {
+            value: pick(rng, &[1.0, 2.0, 1000.0]),
+        },
+        2 => Distribution::Uniform {
+            min: 0.0,
+            max: rng.gen_range(0.0, 300.0),
+        },
+        _ => Distribution::Exponential {
+            mean: rng.gen_range(0.1, 200.0),
+        },
+    }
+}
+
+fn maybe<T, R: Rng>(rng: &mut R, make: impl FnOnce(&mut R) -> T) -> Option<T> {
+    if rng.gen_bool(0.3) {
+        Some(make(rng))
+    } else {
+        None
+    }
+}
+
+fn incident<R: Rng>(rng: &mut R, zones: usize, runtime: u64, city_size: f64) -> Incident {
+    let effect = match rng.gen_range(0, 4) {
+        0 => Effect::DemandSpike {
+            zone: rng.gen_range(0, zones),
+            requests_per_minute: pick(rng, &[0.1, 60.0, 600.0]),
+        },
+        1 => Effect::DriverLogoff {
+            share: pick(rng, &[0.0, 0.5, 1.0]),
+        },
+        2 => Effect::RoadClosure {
+            from: Position::new(0.0, 0.0),
+            to: Position::new(city_size, city_size),
+            speed: pick(rng, &[0.1, 5.0]),
+        },
+        _ => Effect::DispatcherLatency {
+            delay: pick(rng, &[1, 60, runtime + 1]),
+        },
+    };
+    Incident {
+        at: rng.gen_range(0, runtime + 2),
+        duration: pick(rng, &[1, 10, runtime + 1]),
+        effect,
+    }
+}
+
+/// Poisson arrivals or bursts, since replays need a file.
+fn layer<R: Rng>(rng: &mut R, index: usize, zones: usize, runtime: u64) -> DemandLayer {
+    let source = match rng.gen_range(0, 2) {
+        0 => DemandSource::Arrivals {
+            process: Arrivals::Poisson {
+                rate: pick(rng, &[0.0, 0.01, 5.0]),
+                profile: vec![],
+                weekdays: vec![],
+            },
+            zone: maybe(rng, |rng| rng.gen_range(0, zones)),
+        },
+        _ => DemandSource::Bursts {
+            position: Position::new(0.0, 0.0),
+            radius: pick(rng, &[0.0, 100.0]),
+            bursts: vec![Burst {
+                at: rng.gen_range(0, runtime + 2),
+                size: pick(rng, &[0, 1, 10_000]),
+                duration: pick(rng, &[0, 10]),
+                every: maybe(rng, |rng| pick(rng, &[10, 600])),
+            }],
+        },
+    };
+    DemandLayer {
+        name: format!("layer {}", index),
+        seed: maybe(rng, |rng| rng.gen()),
+        source,
+    }
+}
+
+fn scenario<R: Rng>(rng: &mut R, runtime: u64) -> Scenario {
+    let city_size = pick(rng, &[0.0, 0.1, 10.0]);
+    let zones = pick(rng, &[1, 2, 9]);
+    let incidents = (0..pick(rng, &[0, 0, 1, 3]))
+        .map(|_| incident(rng, zones as usize, runtime, city_size))
+        .collect();
+    let demand = (0..pick(rng, &[0, 0, 1, 3]))
+        .map(|i| layer(rng, i, (zones * zones) as usize, runtime))
+        .collect();
+    Scenario {
+        runtime,
+        request_spawn_chance: pick(rng, &[0.0, 0.001, 0.2, 1.0]),
+        max_active_requests: pick(rng, &[0, 1, 2000]),
+        number_of_taxis: pick(rng, &[0, 1, 30]),
+        max_waiting_time: pick(rng, &[1, 2, 100, 10_000]),
+        trip_duration: duration(rng),
+        party_size_weights: pick(rng, &[vec![1.0], vec![0.0, 0.0, 0.0, 0.0, 1.0]]),
+        taxi_capacity: pick(rng, &[1, 4]),
+        boarding_time: maybe(rng, duration),
+        alighting_time: maybe(rng, duration),
+        stop_count_weights: pick(rng, &[vec![1.0], vec![0.0, 0.0, 1.0]]),
+        stop_time: maybe(rng, duration),
+        taxi_speed: pick(rng, &[0.1, 30.0, 1000.0]),
+        taxi_acceleration: maybe(rng, |rng| pick(rng, &[0.01, 3.0])),
+        city_size,
+        travel_times: maybe(rng, |rng| TravelTimes {
+            cells: pick(rng, &[1, 4]),
+            cache: None,
+        }),
+        incidents,
+        demand,
+        zones,
+        matching_interval: pick(rng, &[1, 7, runtime + 1]),
+        max_pickup: maybe(rng, |rng| MaxPickup {
+            distance: Some(pick(rng, &[0.0, 1.0])),
+            eta: maybe(rng, |rng| pick(rng, &[0.0, 5.0])),
+        }),
+        offers: maybe(rng, |rng| Offers {
+            window: pick(rng, &[1, 30]),
+            max_offers: pick(rng, &[1, 3]),
+            escalation: pick(rng, &[Escalation::Assign, Escalation::Reject]),
+            profiles: vec![DriverProfile {
+                share: 1.0,
+                acceptance: pick(rng, &[0.0, 0.5, 1.0]),
+                response_time: duration(rng),
+            }],
+        }),
+        transit: maybe(rng, |rng| Transit {
+            fare: pick(rng, &[0.0, 3.0]),
+            value_of_time: pick(rng, &[0.0, 1.0]),
+            network: Network::Constant {
+                wait: pick(rng, &[0.0, 10.0]),
+                speed: 20.0,
+            },
+        }),
+        cruising: maybe(rng, |rng| Cruising {
+            speed: pick(rng, &[0.5, 1.0]),
+            range: pick(rng, &[0.0, 5.0]),
+        }),
+        maintenance: maybe(rng, |rng| Maintenance {
+            interval: pick(rng, &[0.1, 100.0]),
+            duration: pick(rng, &[0.0, 1.0, 30.0]),
+        }),
+        re_request: maybe(rng, |rng| ReRequest {
+            probability: pick(rng, &[0.5, 1.0]),
+            cooldown: pick(rng, &[0.0, 1.0]),
+        }),
+        no_show: maybe(rng, |rng| NoShow {
+            probability: pick(rng, &[0.5, 1.0]),
+            grace_period: pick(rng, &[0.0, 2.0]),
+        }),
+        eta_cancellation: maybe(rng, |rng| EtaCancellation {
+            patience: pick(rng, &[0.01, 10.0]),
+        }),
+        rider_choice: maybe(rng, |rng| match rng.gen_range(0, 2) {
+            0 => RiderChoice::AlwaysAccept,
+            _ => RiderChoice::Logit {
+                intercept: pick(rng, &[-100.0, 0.0, 100.0]),
+                price: -0.1,
+                eta: -0.3,
+                patience: maybe(rng, |rng| pick(rng, &[0.001, 5.0])),
+            },
+        }),
+        // The invariant checker goes through the whole archive after every tick, so long runs
+        // keep it short.
+        archive: match rng.gen_range(0, 3) {
+            0 if runtime <= 3600 => ArchivePolicy::Unbounded,
+            0 | 1 => ArchivePolicy::RingBuffer { limit: 1 },
+            _ => ArchivePolicy::Recent { minutes: 1.0 },
+        },
+        rolling_window: maybe(rng, |rng| pick(rng, &[1, 15])),
+        ..Scenario::default()
+    }
+}
+
+#[test]
+fn adversarial_scenarios_keep_the_invariants() {
+    let total = env_or("STRESS_TICKS", DEFAULT_TICKS);
+    let mut rng = Pcg64Mcg::seed_from_u64(env_or("STRESS_SEED", 0));
+    let (mut ticks, mut runs, mut invalid) = (0, 0, 0);
+    while ticks < total {
+        let runtime = pick(&mut rng, &[0, 1, 60, 3600, 20_000]);
+        let scenario = scenario(&mut rng, runtime);
+        // E.g. those with both `rider_choice` and the `eta_cancellation` it replaces.
+        if scenario.validate().is_err() {
+            invalid += 1;
+            continue;
+        }
+        let seed = rng.gen();
+        let adaptive_time_step = rng.gen_bool(0.5);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut world = World::new(&scenario, seed);
+            world.set_check_invariants(true);
+            world.set_adaptive_time_step(adaptive_time_step);
+            world.run_till_done();
+            let _ = world.summary().to_string();
+            world.age()
+        }));
+        match result {
+            Ok(age) => ticks += age.max(1),
+            Err(e) => {
+                eprintln!(
+                    "Failed with seed {} and adaptive_time_step = {}:\n{}",
+                    seed,
+                    adaptive_time_step,
+                    toml::to_string(&scenario).expect("Scenarios serialize.")
+                );
+                panic::resume_unwind(e);
+            }
+        }
+        runs += 1;
+    }
+    eprintln!(
+        "{} ticks over {} runs, {} invalid scenarios skipped",
+        ticks, runs, invalid
+    );
+}